@@ -0,0 +1,51 @@
+//! Duplicate-heavy workloads, compare with and without `--features cow_duplicate`.
+//! Sharing makes dropping a copy cheap, while modifying a copy pays for unsharing it.
+use awa_abyss::linked::Abyss;
+use awa_core::Abyss as _;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+
+/// Double bubble holding `outer` doubles of `inner` values each.
+fn nested(outer: usize, inner: usize) -> Abyss<isize> {
+    let mut abyss = Abyss::default();
+    for i in 0..outer {
+        abyss.blow_double(vec![i as isize; inner]).unwrap();
+    }
+    abyss.surround(outer).unwrap();
+    abyss
+}
+
+fn duplicate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("duplicate");
+    group.bench_function("duplicate and drop", |b| {
+        b.iter_batched_ref(
+            || nested(32, 32),
+            |abyss| {
+                for _ in 0..100 {
+                    abyss.duplicate().unwrap();
+                    abyss.double_pop().unwrap();
+                }
+                black_box(abyss.depth())
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("duplicate and modify", |b| {
+        b.iter_batched_ref(
+            || nested(32, 32),
+            |abyss| {
+                for _ in 0..100 {
+                    abyss.duplicate().unwrap();
+                    abyss.blow(1).unwrap();
+                    abyss.combine_single(|top, second| top + second).unwrap();
+                    abyss.double_pop().unwrap();
+                }
+                black_box(abyss.depth())
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, duplicate);
+criterion_main!(benches);
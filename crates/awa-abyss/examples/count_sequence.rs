@@ -0,0 +1,55 @@
+//! Run a pseudo-random sequence of abyss operations and print the result of `count` after each one.
+//!
+//! Usage: `count_sequence [SEED] [LENGTH]`
+//!
+//! The output only depends on the seed and length, so it can be compared between builds
+//! with different features (see `tests/cache_count.nu`).
+
+use std::env::args;
+
+use awa_abyss::linked::Abyss;
+use awa_core::Abyss as _;
+
+/// Minimal xorshift generator, so sequences are reproducible without extra dependencies.
+struct Sequence(u64);
+impl Sequence {
+    #[inline]
+    fn new(seed: u64) -> Self {
+        // NOTE: xorshift gets stuck on zero
+        Self(seed.max(1))
+    }
+    #[inline]
+    fn next(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % bound
+    }
+}
+
+fn main() {
+    let mut args = args().skip(1);
+    let seed = args.next().map_or(1, |seed| seed.parse().unwrap());
+    let length = args.next().map_or(1000, |length| length.parse().unwrap());
+    let (mut sequence, mut abyss) = (Sequence::new(seed), Abyss::<i64>::new());
+    for _ in 0..length {
+        let arg = sequence.next(4) as usize;
+        _ = match sequence.next(11) {
+            0..=2 => abyss.blow(sequence.next(100) as i64 - 50),
+            3 => abyss.pop(),
+            4 => abyss.double_pop(),
+            5 => abyss.duplicate(),
+            6 => abyss.surround(arg),
+            7 => abyss.submerge(arg),
+            8 => abyss.combine_single(|lhs, rhs| lhs - rhs),
+            9 => abyss.combine_double(i64::wrapping_sub, i64::wrapping_add),
+            _ => abyss.merge(),
+        };
+        if abyss.count().is_some() {
+            // SAFETY: unwrap: count pushes a single bubble
+            println!("{}", abyss.pop_value().unwrap());
+        } else {
+            println!("-");
+        }
+    }
+}
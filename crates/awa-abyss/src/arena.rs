@@ -1,4 +1,4 @@
-use std::mem::replace;
+use std::mem::{replace, size_of};
 
 #[cfg_attr(
     target_pointer_width = "64",
@@ -14,6 +14,10 @@ pub struct Index(usize);
 
 type Ref = Option<Index>;
 
+// NOTE: the valid range of Index leaves room for None, so Ref stays pointer sized
+const _: () = assert!(size_of::<Index>() == size_of::<usize>());
+const _: () = assert!(size_of::<Ref>() == size_of::<usize>());
+
 #[derive(Debug, Clone, Copy)]
 enum Entry<T> {
     Occupied(T),
@@ -43,10 +47,13 @@ impl<T> Entry<T> {
     }
 }
 
+/// Slot allocator that re-uses removed slots before growing,
+/// so the [`Index`] of a new value depends on previous removals.
 #[derive(Debug, Clone)]
 pub struct Arena<T> {
     heap: Vec<Entry<T>>,
     free_head: Ref,
+    free_len: usize,
 }
 impl<T> Arena<T> {
     #[inline(always)]
@@ -54,6 +61,7 @@ impl<T> Arena<T> {
         Self {
             heap: Vec::new(),
             free_head: None,
+            free_len: 0,
         }
     }
     #[inline(always)]
@@ -61,8 +69,27 @@ impl<T> Arena<T> {
         Self {
             heap: Vec::with_capacity(capacity),
             free_head: None,
+            free_len: 0,
         }
     }
+    /// Number of slots holding a value.
+    #[inline(always)]
+    pub fn occupied_len(&self) -> usize {
+        self.heap.len() - self.free_len
+    }
+    /// Number of removed slots waiting to be re-used.
+    #[inline(always)]
+    pub fn free_len(&self) -> usize {
+        self.free_len
+    }
+    /// Iterate over all values in slot order.
+    #[inline]
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.heap.iter().filter_map(|entry| match entry {
+            Entry::Occupied(value) => Some(value),
+            Entry::Free(_) => None,
+        })
+    }
     #[inline]
     pub fn insert(&mut self, value: T) -> Index {
         match self.free_head {
@@ -70,6 +97,7 @@ impl<T> Arena<T> {
                 let free = replace(&mut self.heap[index.0], Entry::Occupied(value));
                 // SAFETY: unwrap: free has to be a Free by construction
                 self.free_head = free.into_free().unwrap();
+                self.free_len -= 1;
                 index
             }
             None => {
@@ -87,6 +115,7 @@ impl<T> Arena<T> {
             Entry::Occupied(_) => {
                 let value = replace(entry, Entry::Free(self.free_head));
                 self.free_head = Some(index);
+                self.free_len += 1;
                 // SAFETY: unwrap: value is an Occupied by construction
                 Some(value.into_occupied().unwrap())
             }
@@ -148,3 +177,24 @@ impl<T> std::ops::IndexMut<Index> for Arena<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn occupied_and_free_counts() {
+        let mut arena = Arena::new();
+        let indices = (0..4).map(|i| arena.insert(i)).collect::<Vec<_>>();
+        assert_eq!((arena.occupied_len(), arena.free_len()), (4, 0));
+        assert_eq!(arena.remove(indices[1]), Some(1));
+        assert_eq!(arena.remove(indices[2]), Some(2));
+        assert_eq!(arena.remove(indices[2]), None);
+        assert_eq!((arena.occupied_len(), arena.free_len()), (2, 2));
+        arena.insert(4);
+        assert_eq!((arena.occupied_len(), arena.free_len()), (3, 1));
+        arena.insert(5);
+        arena.insert(6);
+        assert_eq!((arena.occupied_len(), arena.free_len()), (5, 0));
+    }
+}
@@ -4,8 +4,10 @@ use std::{
     ops::{Deref, DerefMut},
 };
 
-use awa_core::{Abyss, AwaSCII, Value};
-use num_traits::{cast, One, Zero};
+use awa_core::{Abyss, AwaSCII, Nested, Value};
+use num_traits::{cast, Zero};
+
+use crate::linked;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 enum BufferKind {
@@ -120,6 +122,34 @@ impl<A: Abyss + Default> Buffered<A> {
             buffer: Buffer::new(),
         }
     }
+    /// Create an abyss with a single bubble for every value.
+    /// Values are blown in order, so the last value will end up on top.
+    #[inline]
+    pub fn from_singles(values: impl IntoIterator<Item = A::Value>) -> Self {
+        let mut this = Self::new();
+        this.buffer.extend(values);
+        if !this.buffer.is_empty() {
+            this.buffer.kind = BufferKind::Singles;
+        }
+        this
+    }
+    /// Create an abyss containing a single double bubble with the given elements.
+    /// The last element will end up as the front, an empty slice results in an empty abyss.
+    #[inline]
+    pub fn from_double(values: impl AsRef<[A::Value]>) -> Self {
+        let mut this = Self::new();
+        this.buffer.extend_from_slice(values.as_ref());
+        if !this.buffer.is_empty() {
+            this.buffer.kind = BufferKind::Double;
+        }
+        this
+    }
+}
+impl<A: Abyss + Default> FromIterator<A::Value> for Buffered<A> {
+    #[inline(always)]
+    fn from_iter<I: IntoIterator<Item = A::Value>>(iter: I) -> Self {
+        Self::from_singles(iter)
+    }
 }
 impl<A: Abyss + Default> Default for Buffered<A> {
     #[inline(always)]
@@ -127,6 +157,41 @@ impl<A: Abyss + Default> Default for Buffered<A> {
         Self::new()
     }
 }
+impl<T: Value> Buffered<linked::Abyss<T>> {
+    /// Returns the values inside of the top double bubble without removing it, see [`linked::Abyss::top_group`].
+    #[inline]
+    pub fn top_group(&self) -> Option<Vec<T>> {
+        match self.buffer.kind {
+            BufferKind::Empty => self.inner.top_group(),
+            BufferKind::Singles => None,
+            BufferKind::Double => Some(self.buffer.iter().rev().copied().collect()),
+        }
+    }
+    /// Returns all bubbles from top to bottom without removing them, see [`linked::Abyss::to_nested`].
+    pub fn to_nested(&self) -> Vec<Nested<T>> {
+        let mut parts = Vec::new();
+        match self.buffer.kind {
+            BufferKind::Empty => (),
+            BufferKind::Singles => {
+                parts.extend(self.buffer.iter().rev().map(|v| Nested::Value(*v)))
+            }
+            BufferKind::Double => {
+                parts.push(Nested::Begin);
+                parts.extend(self.buffer.iter().rev().map(|v| Nested::Value(*v)));
+                parts.push(Nested::End);
+            }
+        }
+        parts.extend(self.inner.to_nested());
+        parts
+    }
+}
+// NOTE: the same bubbles can be split differently between the buffer and the inner abyss
+impl<T: Value> PartialEq for Buffered<linked::Abyss<T>> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.to_nested() == other.to_nested()
+    }
+}
 impl<A: Abyss> Buffered<A> {
     #[inline]
     pub fn from_inner(inner: A) -> Self {
@@ -189,15 +254,31 @@ impl<A: Abyss> Abyss for Buffered<A> {
         matches!(self.buffer.kind, BufferKind::Empty) && self.inner.is_empty()
     }
     #[inline]
+    fn depth(&self) -> usize {
+        let buffered = match self.buffer.kind {
+            BufferKind::Empty => 0,
+            BufferKind::Singles => self.buffer.len(),
+            BufferKind::Double => 1,
+        };
+        buffered + self.inner.depth()
+    }
+    #[inline]
     fn blow_awascii<B>(&mut self, awascii: B) -> Option<()>
     where
         B: AsRef<[AwaSCII]>,
     {
-        let (string, buffer) = (awascii.as_ref(), self.get_double_mut()?);
+        let string = awascii.as_ref();
+        // NOTE: same as the linked abyss, an empty string is a single zero
+        if string.is_empty() {
+            return self.blow(Self::Value::zero());
+        }
+        let buffer = self.get_double_mut()?;
+        // NOTE: the buffer stores the top bubble last, but the first character has to be on top
         // SAFETY: unwrap: even an i8 can fit all AwaSCII characters
         buffer.extend(
             string
                 .iter()
+                .rev()
                 .map(|char| cast::<_, Self::Value>(**char).unwrap()),
         );
         Some(())
@@ -249,6 +330,14 @@ impl<A: Abyss> Abyss for Buffered<A> {
         self.buffer.pop().map(|_| ()).or_else(|| self.inner.pop())
     }
     #[inline]
+    fn pop_value(&mut self) -> Option<Self::Value> {
+        match self.buffer.kind {
+            BufferKind::Empty => self.inner.pop_value(),
+            BufferKind::Singles => self.buffer.pop().flatten(),
+            BufferKind::Double => None,
+        }
+    }
+    #[inline]
     fn double_pop(&mut self) -> Option<()> {
         self.buffer
             .double_pop()
@@ -270,15 +359,18 @@ impl<A: Abyss> Abyss for Buffered<A> {
     }
     #[inline]
     fn surround(&mut self, count: usize) -> Option<()> {
+        if count.is_zero() {
+            return Some(());
+        }
         match self.buffer.kind {
             BufferKind::Empty => self.inner.surround(count),
             BufferKind::Singles => {
                 let len = self.buffer.len();
-                self.buffer.kind = BufferKind::Double;
                 match len.cmp(&count) {
+                    // NOTE: the inner abyss decides how to handle a count bigger than the total depth
                     Ordering::Less => {
                         self.commit()?;
-                        self.inner.merge_many(count - len - 1)?;
+                        return self.inner.surround(count);
                     }
                     Ordering::Equal => (),
                     Ordering::Greater => {
@@ -287,6 +379,7 @@ impl<A: Abyss> Abyss for Buffered<A> {
                         self.buffer.drain(..middle);
                     }
                 }
+                self.buffer.kind = BufferKind::Double;
                 Some(())
             }
             BufferKind::Double => {
@@ -324,23 +417,79 @@ impl<A: Abyss> Abyss for Buffered<A> {
         }
     }
     #[inline]
+    fn merge_top_n(&mut self, count: usize) -> Option<()> {
+        if count < 2 {
+            return Some(());
+        }
+        match self.buffer.kind {
+            BufferKind::Singles if self.buffer.len() >= count => {
+                let middle = self.buffer.len() - count;
+                self.inner.blow_many(&self.buffer[..middle])?;
+                self.buffer.drain(..middle);
+                self.buffer.kind = BufferKind::Double;
+                Some(())
+            }
+            BufferKind::Empty => self.inner.merge_top_n(count),
+            BufferKind::Singles | BufferKind::Double => {
+                self.commit()?;
+                self.inner.merge_top_n(count)
+            }
+        }
+    }
+    #[inline]
+    fn reverse_top(&mut self) -> Option<()> {
+        match self.buffer.kind {
+            BufferKind::Empty => self.inner.reverse_top(),
+            BufferKind::Singles => None,
+            BufferKind::Double => {
+                self.buffer.reverse();
+                Some(())
+            }
+        }
+    }
+    #[inline]
     fn count(&mut self) -> Option<()> {
         match self.buffer.kind {
             BufferKind::Empty => self.inner.count(),
             BufferKind::Singles => {
-                self.buffer.push(Self::Value::one());
+                self.buffer.push(Self::Value::zero());
                 Some(())
             }
             BufferKind::Double => {
-                let count = self.buffer.len();
+                let count = cast(self.buffer.len())?;
                 self.commit()?;
-                self.buffer.push(cast(count)?);
+                self.buffer.push(count);
                 self.buffer.kind = BufferKind::Singles;
                 Some(())
             }
         }
     }
     #[inline]
+    fn top_size(&self) -> Option<usize> {
+        match self.buffer.kind {
+            BufferKind::Empty => self.inner.top_size(),
+            BufferKind::Singles => Some(0),
+            BufferKind::Double => Some(self.buffer.len()),
+        }
+    }
+    #[inline]
+    fn peek<F>(&self, mut fun: F) -> Option<()>
+    where
+        F: FnMut(Self::Value),
+    {
+        match self.buffer.kind {
+            BufferKind::Empty => self.inner.peek(fun),
+            BufferKind::Singles => {
+                fun(*self.buffer.last()?);
+                Some(())
+            }
+            BufferKind::Double => {
+                self.buffer.iter().rev().copied().for_each(fun);
+                Some(())
+            }
+        }
+    }
+    #[inline]
     fn combine_single<F>(&mut self, op: F) -> Option<()>
     where
         F: Fn(Self::Value, Self::Value) -> Self::Value,
@@ -421,6 +570,30 @@ impl<A: Abyss> Abyss for Buffered<A> {
         }
     }
     #[inline]
+    fn consume_nested<F, E>(&mut self, mut fun: F) -> Result<Option<()>, E>
+    where
+        F: FnMut(Nested<Self::Value>) -> Result<(), E>,
+    {
+        match self.buffer.kind {
+            BufferKind::Empty => self.inner.consume_nested(fun),
+            BufferKind::Singles => {
+                fun(Nested::Value(*self.buffer.last().unwrap()))?;
+                self.buffer.pop();
+                Ok(Some(()))
+            }
+            BufferKind::Double => {
+                fun(Nested::Begin)?;
+                self.buffer
+                    .iter()
+                    .rev()
+                    .try_for_each(|value| fun(Nested::Value(*value)))?;
+                fun(Nested::End)?;
+                self.buffer.clear();
+                Ok(Some(()))
+            }
+        }
+    }
+    #[inline]
     fn blow_many<B>(&mut self, values: B) -> Option<()>
     where
         B: AsRef<[Self::Value]>,
@@ -527,3 +700,292 @@ impl<A: Abyss + Display> Display for Buffered<A> {
         self.inner.fmt(f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type BufferedLinked = Buffered<linked::Abyss<isize>>;
+
+    #[test]
+    fn equal_regardless_of_buffering() {
+        let expected = BufferedLinked::from_double([1, 2, 3]);
+        assert_eq!(
+            expected.to_nested(),
+            [
+                Nested::Begin,
+                Nested::Value(3),
+                Nested::Value(2),
+                Nested::Value(1),
+                Nested::End
+            ]
+        );
+        let mut surrounded = BufferedLinked::from_singles([1, 2, 3]);
+        surrounded.surround(3).unwrap();
+        assert_eq!(surrounded, expected);
+        // NOTE: blowing a single moves the buffered double into the inner abyss
+        let mut flushed = BufferedLinked::from_double([1, 2, 3]);
+        flushed.blow(4).unwrap();
+        flushed.pop().unwrap();
+        assert_eq!(flushed, expected);
+        assert_ne!(BufferedLinked::from_double([1, 2]), expected);
+        assert_ne!(BufferedLinked::from_singles([1, 2, 3]), expected);
+    }
+
+    #[test]
+    fn from_singles_and_double() {
+        let mut abyss = BufferedLinked::from_singles([1, 2, 3]);
+        assert_eq!(abyss.to_string(), "3\n2\n1\n-----\n");
+        assert_eq!(abyss.pop_value(), Some(3));
+        assert_eq!(abyss.depth(), 2);
+        let abyss = BufferedLinked::from_double([1, 2, 3]);
+        assert_eq!(
+            abyss.to_nested(),
+            linked::Abyss::from_double([1, 2, 3]).to_nested()
+        );
+        assert!(BufferedLinked::from_double([]).is_empty());
+    }
+
+    /// Build one of a few abysses mixing single and double bubbles, returns the depth.
+    fn setup<A: Abyss<Value = isize>>(abyss: &mut A, case: usize) -> usize {
+        match case {
+            0 => (1..=5).try_for_each(|value| abyss.blow(value)).unwrap(),
+            1 => {
+                abyss.blow(1).unwrap();
+                abyss.blow_double([2, 3]).unwrap();
+                abyss.blow(4).unwrap();
+                abyss.blow_double([5]).unwrap();
+                abyss.blow(6).unwrap();
+            }
+            _ => {
+                abyss.blow_double([1, 2]).unwrap();
+                abyss.blow_double([3, 4, 5]).unwrap();
+            }
+        }
+        abyss.depth()
+    }
+    const CASES: usize = 3;
+
+    #[test]
+    fn merge_top_n_matches_merge() {
+        for case in 0..CASES {
+            let depth = setup(&mut linked::Abyss::default(), case);
+            for count in 0..=depth + 1 {
+                let mut expected = linked::Abyss::default();
+                setup(&mut expected, case);
+                let merged = (1..count.max(1)).try_for_each(|_| expected.merge());
+                assert_eq!(merged.is_some(), count <= depth);
+
+                let mut linked = linked::Abyss::default();
+                setup(&mut linked, case);
+                let mut buffered = BufferedLinked::default();
+                setup(&mut buffered, case);
+                let results = (linked.merge_top_n(count), buffered.merge_top_n(count));
+                if merged.is_none() {
+                    assert_eq!(results, (None, None), "case {case}, count {count}");
+                    continue;
+                }
+                assert_eq!(results, (Some(()), Some(())), "case {case}, count {count}");
+                assert_eq!(linked, expected, "case {case}, count {count}");
+                assert_eq!(
+                    buffered.to_nested(),
+                    expected.to_nested(),
+                    "case {case}, count {count}"
+                );
+            }
+        }
+    }
+
+    /// `len` buffered singles on top of a single and a double bubble in the inner abyss.
+    fn singles_over_inner<A: Abyss<Value = isize>>(abyss: &mut A, len: isize) {
+        abyss.blow(6).unwrap();
+        abyss.blow_double([7, 8]).unwrap();
+        (1..=len).try_for_each(|value| abyss.blow(value)).unwrap();
+    }
+
+    #[test]
+    fn surround_matches_linked() {
+        let len = 3;
+        // NOTE: count < len, count == len, count > len and count > total depth
+        for count in 1..=len as usize + 3 {
+            let mut expected = linked::Abyss::default();
+            singles_over_inner(&mut expected, len);
+            let mut buffered = BufferedLinked::default();
+            singles_over_inner(&mut buffered, len);
+            assert_eq!(buffered.buffer.kind, BufferKind::Singles);
+            assert_eq!(buffered.buffer.len(), len as usize);
+
+            // NOTE: linked surrounds all bubbles when there are less than count
+            let results = (expected.surround(count), buffered.surround(count));
+            assert_eq!(results, (Some(()), Some(())), "count {count}");
+            assert_eq!(buffered.to_nested(), expected.to_nested(), "count {count}");
+            assert_eq!(buffered.depth(), expected.depth(), "count {count}");
+        }
+    }
+
+    #[test]
+    fn pop_value_matches_linked() {
+        let cases = [
+            BufferedLinked::from_singles([1, 2]),
+            BufferedLinked::from_double([1, 2]),
+            BufferedLinked::from_inner(linked::Abyss::from_singles([1, 2])),
+            BufferedLinked::from_inner(linked::Abyss::from_double([1, 2])),
+            BufferedLinked::new(),
+        ];
+        let expected = [
+            linked::Abyss::from_singles([1, 2]),
+            linked::Abyss::from_double([1, 2]),
+            linked::Abyss::from_singles([1, 2]),
+            linked::Abyss::from_double([1, 2]),
+            linked::Abyss::new(),
+        ];
+        for (mut buffered, mut expected) in cases.into_iter().zip(expected) {
+            for _ in 0..3 {
+                assert_eq!(buffered.pop_value(), expected.pop_value());
+                assert_eq!(buffered.to_nested(), expected.to_nested());
+            }
+        }
+    }
+
+    #[test]
+    fn top_size_matches_linked() {
+        let cases = [
+            BufferedLinked::from_singles([1, 2]),
+            BufferedLinked::from_double([1, 2, 3]),
+            BufferedLinked::from_inner(linked::Abyss::from_singles([1, 2])),
+            BufferedLinked::from_inner(linked::Abyss::from_double([1, 2, 3])),
+            BufferedLinked::new(),
+        ];
+        let expected = [Some(0), Some(3), Some(0), Some(3), None];
+        for (mut buffered, expected) in cases.into_iter().zip(expected) {
+            let kind = buffered.buffer.kind;
+            assert_eq!(buffered.top_size(), expected);
+            // NOTE: looking up the size must not commit the buffer
+            assert_eq!(buffered.buffer.kind, kind);
+            let counted = buffered.count().and_then(|_| buffered.pop_value());
+            assert_eq!(counted, expected.map(|size| size as isize));
+        }
+        assert_eq!(linked::Abyss::from_double([1, 2, 3]).top_size(), Some(3));
+        assert_eq!(linked::Abyss::from_singles([1, 2]).top_size(), Some(0));
+        assert_eq!(linked::Abyss::<isize>::new().top_size(), None);
+    }
+
+    #[test]
+    fn count_single_matches_linked() {
+        let cases = [
+            BufferedLinked::from_singles([4]),
+            BufferedLinked::from_singles([1, 2, 3]),
+            BufferedLinked::from_inner(linked::Abyss::from_singles([4])),
+        ];
+        let expected = [
+            linked::Abyss::from_singles([4]),
+            linked::Abyss::from_singles([1, 2, 3]),
+            linked::Abyss::from_singles([4]),
+        ];
+        for (mut buffered, mut expected) in cases.into_iter().zip(expected) {
+            assert_eq!(buffered.count(), Some(()));
+            assert_eq!(expected.count(), Some(()));
+            assert_eq!(buffered.to_nested(), expected.to_nested());
+            assert_eq!(buffered.pop_value(), Some(0));
+        }
+    }
+
+    #[test]
+    fn top_group_spells_string() {
+        let hi = [b'h', b'i'].map(|byte| AwaSCII::from_ascii(byte).unwrap());
+        let codes = hi.map(|awascii| *awascii as isize).to_vec();
+        let mut linked = linked::Abyss::<isize>::new();
+        linked.blow_awascii(hi).unwrap();
+        let mut buffered = BufferedLinked::new();
+        buffered.blow_awascii(hi).unwrap();
+        assert_eq!(linked.top_group(), Some(codes.clone()));
+        assert_eq!(buffered.top_group(), Some(codes.clone()));
+        assert_eq!(BufferedLinked::from_inner(linked).top_group(), Some(codes));
+
+        // [[1, 2], 3] is flattened front first
+        let mut nested = linked::Abyss::<isize>::new();
+        nested.blow(3).unwrap();
+        nested.blow_double([2, 1]).unwrap();
+        nested.surround(2).unwrap();
+        assert_eq!(nested.top_group(), Some(vec![1, 2, 3]));
+        assert_eq!(linked::Abyss::from_singles([1]).top_group(), None);
+        assert_eq!(BufferedLinked::from_singles([1]).top_group(), None);
+        assert_eq!(BufferedLinked::new().top_group(), None);
+    }
+
+    #[test]
+    fn submerge_max_and_zero() {
+        for distance in [31, 0] {
+            let mut expected = linked::Abyss::from_singles(1..=40);
+            let mut buffered = BufferedLinked::from_singles(1..=40);
+            let mut inner = BufferedLinked::from_inner(linked::Abyss::from_singles(1..=40));
+            assert_eq!(expected.submerge(distance), Some(()));
+            assert_eq!(buffered.submerge(distance), Some(()));
+            assert_eq!(inner.submerge(distance), Some(()));
+            assert_eq!(
+                buffered.to_nested(),
+                expected.to_nested(),
+                "distance {distance}"
+            );
+            assert_eq!(
+                inner.to_nested(),
+                expected.to_nested(),
+                "distance {distance}"
+            );
+            // NOTE: 40 was on top, 0 moves it to the bottom
+            let position = expected
+                .to_nested()
+                .iter()
+                .position(|part| *part == Nested::Value(40));
+            let expected_position = if distance == 0 { 39 } else { distance };
+            assert_eq!(position, Some(expected_position), "distance {distance}");
+        }
+        // NOTE: distances past the bottom behave like 0
+        let mut short = linked::Abyss::from_singles(1..=3);
+        short.submerge(31).unwrap();
+        assert_eq!(short.to_nested().last(), Some(&Nested::Value(3)));
+    }
+
+    #[test]
+    fn peek_matches_consume() {
+        use std::convert::Infallible;
+
+        fn consumed(abyss: &mut impl Abyss<Value = isize>) -> Option<Vec<isize>> {
+            let mut values = Vec::new();
+            abyss
+                .consume::<_, Infallible>(|value| {
+                    values.push(value);
+                    Ok(())
+                })
+                .unwrap()
+                .map(|_| values)
+        }
+        fn peeked(abyss: &impl Abyss<Value = isize>) -> Option<Vec<isize>> {
+            let mut values = Vec::new();
+            abyss.peek(|value| values.push(value)).map(|_| values)
+        }
+        let cases = [
+            BufferedLinked::from_singles([1, 2]),
+            BufferedLinked::from_double([1, 2, 3]),
+            BufferedLinked::from_inner(linked::Abyss::from_singles([1, 2])),
+            BufferedLinked::from_inner(linked::Abyss::from_double([1, 2, 3])),
+            BufferedLinked::new(),
+        ];
+        let linked = [
+            linked::Abyss::from_singles([1, 2]),
+            linked::Abyss::from_double([1, 2, 3]),
+            linked::Abyss::from_singles([1, 2]),
+            linked::Abyss::from_double([1, 2, 3]),
+            linked::Abyss::new(),
+        ];
+        for (mut buffered, mut linked) in cases.into_iter().zip(linked) {
+            let (kind, depth) = (buffered.buffer.kind, buffered.depth());
+            let values = peeked(&buffered);
+            assert_eq!(buffered.buffer.kind, kind);
+            assert_eq!(buffered.depth(), depth);
+            assert_eq!(peeked(&linked), values);
+            assert_eq!(consumed(&mut linked), values);
+            assert_eq!(consumed(&mut buffered), values);
+        }
+    }
+}
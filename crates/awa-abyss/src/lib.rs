@@ -12,6 +12,18 @@ pub mod linked;
 
 cfg_if::cfg_if!(if #[cfg(feature = "default_buffered-linked")] {
     pub type Abyss<T> = Buffered<linked::Abyss<T>>;
+    /// Name of the implementation used for [`Abyss`].
+    pub const DEFAULT_BACKEND: &str = "buffered-linked";
 } else if #[cfg(feature = "default_linked")] {
     pub use linked::Abyss;
+    /// Name of the implementation used for [`Abyss`].
+    pub const DEFAULT_BACKEND: &str = "linked";
 });
+
+/// Optional features this crate was compiled with.
+pub const FEATURES: &[&str] = &[
+    #[cfg(feature = "cache_count")]
+    "cache_count",
+    #[cfg(feature = "cow_duplicate")]
+    "cow_duplicate",
+];
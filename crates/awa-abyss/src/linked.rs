@@ -1,6 +1,8 @@
-use std::{fmt::Display, mem::replace};
+#[cfg(feature = "cow_duplicate")]
+use std::collections::BTreeMap;
+use std::{convert::Infallible, fmt::Display, mem::replace};
 
-use awa_core::Value;
+use awa_core::{Abyss as _, Nested, Value};
 use num_traits::{cast, Zero};
 
 use crate::{Arena, Index};
@@ -20,6 +22,11 @@ enum Bubble<T: Value> {
         count: T,
     },
 }
+// NOTE: bubbles are stored densely in the arena, both variants have to stay this small
+#[cfg(not(feature = "cache_count"))]
+const _: () = assert!(std::mem::size_of::<Bubble<isize>>() == 3 * std::mem::size_of::<usize>());
+#[cfg(feature = "cache_count")]
+const _: () = assert!(std::mem::size_of::<Bubble<isize>>() == 4 * std::mem::size_of::<usize>());
 impl<T: Value> Bubble<T> {
     #[inline]
     const fn next(&self) -> Ref {
@@ -37,24 +44,26 @@ impl<T: Value> Bubble<T> {
     }
     #[cfg(feature = "cache_count")]
     #[inline]
-    fn count(&self, _arena: &Arena<Self>) -> T {
+    fn count(&self, _arena: &Arena<Self>) -> Option<T> {
         match self {
-            Self::Single { .. } => T::zero(),
-            Self::Double { count, .. } => *count,
+            Self::Single { .. } => Some(T::zero()),
+            Self::Double { count, .. } => Some(*count),
         }
     }
+    /// Returns `None` when the count does not fit into `T`.
     #[cfg(not(feature = "cache_count"))]
     #[inline]
-    fn count(&self, arena: &Arena<Self>) -> T {
+    fn count(&self, arena: &Arena<Self>) -> Option<T> {
         match self {
-            Self::Single { .. } => T::zero(),
+            Self::Single { .. } => Some(T::zero()),
             Self::Double {
                 inner: (first, _), ..
-            } => find_count(arena, *first),
+            } => cast(find_count(arena, *first)),
         }
     }
 }
 
+#[cfg(not(feature = "cow_duplicate"))]
 #[inline]
 fn deep_copy(arena: &mut Arena<Bubble<impl Value>>, root: Index) -> Index {
     let copy = arena[root];
@@ -82,16 +91,66 @@ fn deep_copy(arena: &mut Arena<Bubble<impl Value>>, root: Index) -> Index {
     index
 }
 #[inline]
-fn move_next<T: Value>(arena: &Arena<Bubble<T>>, mut first: Index, count: usize) -> (Index, T) {
-    let (mut result, one) = (T::zero(), T::one());
+fn move_next<T: Value>(arena: &Arena<Bubble<T>>, mut first: Index, count: usize) -> (Index, usize) {
+    let mut result = 0;
     for _ in 0..count {
         let Some(next) = arena[first].next() else {
             break;
         };
-        (first, result) = (next, result + one);
+        (first, result) = (next, result + 1);
     }
     (first, result)
 }
+/// Call `fun` with all values of the bubble at `index` in the order of [`awa_core::Abyss::consume`].
+/// Returns the next bubble.
+fn visit<T: Value, E>(
+    arena: &Arena<Bubble<T>>,
+    index: Index,
+    fun: &mut impl FnMut(T) -> Result<(), E>,
+) -> Result<Ref, E> {
+    match arena[index] {
+        Bubble::Single { value, next } => {
+            fun(value)?;
+            Ok(next)
+        }
+        Bubble::Double {
+            inner: (mut index, _),
+            next,
+            ..
+        } => loop {
+            if let Some(next) = visit(arena, index, fun)? {
+                index = next;
+            } else {
+                return Ok(next);
+            }
+        },
+    }
+}
+/// Same as [`visit`], but calls `fun` with [`Nested::Begin`] and [`Nested::End`] around double bubbles.
+fn visit_nested<T: Value, E>(
+    arena: &Arena<Bubble<T>>,
+    index: Index,
+    fun: &mut impl FnMut(Nested<T>) -> Result<(), E>,
+) -> Result<Ref, E> {
+    match arena[index] {
+        Bubble::Single { value, next } => {
+            fun(Nested::Value(value))?;
+            Ok(next)
+        }
+        Bubble::Double {
+            inner: (mut index, _),
+            next,
+            ..
+        } => {
+            fun(Nested::Begin)?;
+            while let Some(next) = visit_nested(arena, index, fun)? {
+                index = next;
+            }
+            fun(Nested::End)?;
+            Ok(next)
+        }
+    }
+}
 #[inline]
 fn remove_all(arena: &mut Arena<Bubble<impl Value>>, mut first: Index) {
     loop {
@@ -115,25 +174,42 @@ fn remove_all(arena: &mut Arena<Bubble<impl Value>>, mut first: Index) {
 }
 #[cfg(not(feature = "cache_count"))]
 #[inline]
-fn find_count<T>(arena: &Arena<Bubble<T>>, mut first: Index) -> T
+fn find_count<T>(arena: &Arena<Bubble<T>>, mut first: Index) -> usize
 where
     T: Value,
 {
-    let (mut count, step) = (T::zero(), T::one());
+    let mut count = 1;
     loop {
         if let Some(next) = arena[first].next() {
-            (first, count) = (next, count + step);
+            (first, count) = (next, count + 1);
         } else {
             return count;
         }
     }
 }
 
+/// Number of arena slots used by an [`Abyss`], see [`Abyss::arena_footprint`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FootprintStats {
+    /// Slots holding a single bubble.
+    pub singles: usize,
+    /// Slots holding a double bubble, not including the bubbles inside of it.
+    pub doubles: usize,
+    /// Removed slots waiting to be re-used.
+    pub free: usize,
+}
+
 /// Represent an [`awa_core::Abyss`] that uses a linked list backed by an arena allocator to store bubbles.
+/// The layout inside the arena depends on the history of operations,
+/// but equality and [`Display`] only depend on the bubbles themselves.
 #[derive(Debug, Clone)]
 pub struct Abyss<T: Value> {
     arena: Arena<Bubble<T>>,
     top: Ref,
+    /// Number of additional owners of inner bubbles shared by duplicated double bubbles,
+    /// indexed by the first inner bubble.
+    #[cfg(feature = "cow_duplicate")]
+    shared: BTreeMap<Index, usize>,
 }
 impl<T: Value> Abyss<T> {
     #[inline(always)]
@@ -141,6 +217,8 @@ impl<T: Value> Abyss<T> {
         Self {
             arena: Arena::new(),
             top: None,
+            #[cfg(feature = "cow_duplicate")]
+            shared: BTreeMap::new(),
         }
     }
     #[inline(always)]
@@ -148,7 +226,181 @@ impl<T: Value> Abyss<T> {
         Self {
             arena: Arena::with_capacity(capacity),
             top: None,
+            #[cfg(feature = "cow_duplicate")]
+            shared: BTreeMap::new(),
+        }
+    }
+    /// Returns the values inside of the top double bubble without removing it,
+    /// nested double bubbles are flattened in the same order as [`awa_core::Abyss::consume`].
+    /// Returns `None` if there is no top bubble or it is a single bubble.
+    pub fn top_group(&self) -> Option<Vec<T>> {
+        let top = self.top?;
+        if let Bubble::Single { .. } = self.arena[top] {
+            return None;
+        }
+        let mut values = Vec::new();
+        let result = visit::<_, Infallible>(&self.arena, top, &mut |value| {
+            values.push(value);
+            Ok(())
+        });
+        match result {
+            Ok(_) => Some(values),
+            Err(never) => match never {},
+        }
+    }
+    /// Returns all bubbles from top to bottom without removing them,
+    /// double bubbles are visited in the same way as [`awa_core::Abyss::consume_nested`].
+    pub fn to_nested(&self) -> Vec<Nested<T>> {
+        let (mut parts, mut current) = (Vec::new(), self.top);
+        while let Some(index) = current {
+            let result = visit_nested::<_, Infallible>(&self.arena, index, &mut |part| {
+                parts.push(part);
+                Ok(())
+            });
+            current = match result {
+                Ok(next) => next,
+                Err(never) => match never {},
+            };
+        }
+        parts
+    }
+    /// Count the arena slots used by each kind of bubble.
+    /// Inner bubbles shared between duplicated double bubbles only occupy their slots once.
+    pub fn arena_footprint(&self) -> FootprintStats {
+        let mut stats = FootprintStats {
+            free: self.arena.free_len(),
+            ..Default::default()
+        };
+        for bubble in self.arena.values() {
+            match bubble {
+                Bubble::Single { .. } => stats.singles += 1,
+                Bubble::Double { .. } => stats.doubles += 1,
+            }
+        }
+        stats
+    }
+    /// Insert a copy of a bubble, inner bubbles of a double bubble will be shared with the original.
+    #[cfg(feature = "cow_duplicate")]
+    #[inline]
+    fn share(&mut self, index: Index) -> Index {
+        let copy = self.arena[index];
+        if let Bubble::Double {
+            inner: (first, _), ..
+        } = copy
+        {
+            *self.shared.entry(first).or_default() += 1;
         }
+        self.arena.insert(copy)
+    }
+    /// Give a double bubble its own copy of its inner bubbles in case they are shared.
+    /// Nested double bubbles will stay shared.
+    #[cfg(feature = "cow_duplicate")]
+    #[inline]
+    fn unshare(&mut self, index: Index) {
+        let Bubble::Double {
+            inner: (first, _), ..
+        } = self.arena[index]
+        else {
+            return;
+        };
+        let Some(owners) = self.shared.get_mut(&first) else {
+            return;
+        };
+        *owners -= 1;
+        if owners.is_zero() {
+            self.shared.remove(&first);
+        }
+        let (mut current, copy_first) = (first, self.share(first));
+        let mut copy_last = copy_first;
+        while let Some(next) = self.arena[current].next() {
+            let copy = self.share(next);
+            *self.arena[copy_last].next_mut() = Some(copy);
+            (current, copy_last) = (next, copy);
+        }
+        // SAFETY: index is a double bubble by construction
+        let Some(Bubble::Double { inner, .. }) = self.arena.get_mut(index) else {
+            unreachable!()
+        };
+        *inner = (copy_first, copy_last);
+    }
+    #[cfg(not(feature = "cow_duplicate"))]
+    #[inline(always)]
+    fn unshare(&mut self, _index: Index) {}
+    /// Make sure no bubble inside of a bubble is shared.
+    #[cfg(feature = "cow_duplicate")]
+    fn unshare_deep(&mut self, index: Index) {
+        self.unshare(index);
+        let Bubble::Double {
+            inner: (mut current, _),
+            ..
+        } = self.arena[index]
+        else {
+            return;
+        };
+        loop {
+            self.unshare_deep(current);
+            let Some(next) = self.arena[current].next() else {
+                return;
+            };
+            current = next;
+        }
+    }
+    #[cfg(not(feature = "cow_duplicate"))]
+    #[inline(always)]
+    fn unshare_deep(&mut self, _index: Index) {}
+    /// Remove a bubble including all inner bubbles that are not shared.
+    /// Returns the next bubble.
+    #[cfg(feature = "cow_duplicate")]
+    fn release(&mut self, index: Index) -> Ref {
+        match self.arena.remove(index) {
+            Some(Bubble::Single { next, .. }) => next,
+            Some(Bubble::Double {
+                inner: (first, _),
+                next,
+                ..
+            }) => {
+                if let Some(owners) = self.shared.get_mut(&first) {
+                    *owners -= 1;
+                    if owners.is_zero() {
+                        self.shared.remove(&first);
+                    }
+                } else {
+                    let mut current = Some(first);
+                    while let Some(index) = current {
+                        current = self.release(index);
+                    }
+                }
+                next
+            }
+            // SAFETY: index exists by construction
+            None => unreachable!(),
+        }
+    }
+    /// Create an abyss with a single bubble for every value.
+    /// Values are blown in order, so the last value will end up on top.
+    #[inline]
+    pub fn from_singles(values: impl IntoIterator<Item = T>) -> Self {
+        let mut this = Self::new();
+        for value in values {
+            // SAFETY: unwrap: linked abyss can't be full
+            this.blow(value).unwrap();
+        }
+        this
+    }
+    /// Create an abyss containing a single double bubble with the given elements.
+    /// The last element will end up as the front, an empty slice results in an empty abyss.
+    #[inline]
+    pub fn from_double(values: impl AsRef<[T]>) -> Self {
+        let mut this = Self::new();
+        // SAFETY: unwrap: linked abyss can't be full
+        this.blow_double(values).unwrap();
+        this
+    }
+}
+impl<T: Value> FromIterator<T> for Abyss<T> {
+    #[inline(always)]
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::from_singles(iter)
     }
 }
 impl<T: Value> Default for Abyss<T> {
@@ -164,6 +416,14 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
         self.top.is_none()
     }
     #[inline]
+    fn depth(&self) -> usize {
+        let (mut current, mut depth) = (self.top, 0);
+        while let Some(index) = current {
+            (current, depth) = (self.arena[index].next(), depth + 1);
+        }
+        depth
+    }
+    #[inline]
     fn blow_awascii<B>(&mut self, awascii: B) -> Option<()>
     where
         B: AsRef<[awa_core::AwaSCII]>,
@@ -172,16 +432,16 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
         let inner = awascii
             .iter()
             .rev()
-            .fold((None, None), |(first, last), char| {
+            .fold((None, None), |(last, first), char| {
                 let bubble = Bubble::Single {
                     // SAFETY: unwrap: even i8 can hold all valid AwaSCII characters
                     value: cast(**char).unwrap(),
-                    next: last,
+                    next: first,
                 };
                 let index = Some(self.arena.insert(bubble));
-                (first.or(index), index)
+                (last.or(index), index)
             });
-        let bubble = if let (Some(first), Some(last)) = inner {
+        let bubble = if let (Some(last), Some(first)) = inner {
             Bubble::Double {
                 inner: (first, last),
                 next: self.top,
@@ -221,7 +481,9 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
     }
     #[inline]
     fn pop(&mut self) -> Option<()> {
-        match self.arena.remove(self.top?)? {
+        let top = self.top?;
+        self.unshare(top);
+        match self.arena.remove(top)? {
             Bubble::Single { next, .. } => self.top = next,
             Bubble::Double {
                 inner: (first, last),
@@ -235,6 +497,17 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
         Some(())
     }
     #[inline]
+    fn pop_value(&mut self) -> Option<Self::Value> {
+        let top = self.top?;
+        let Bubble::Single { value, next } = self.arena[top] else {
+            return None;
+        };
+        self.arena.remove(top);
+        self.top = next;
+        Some(value)
+    }
+    #[cfg(not(feature = "cow_duplicate"))]
+    #[inline]
     fn duplicate(&mut self) -> Option<()> {
         let index = self.top?;
         let copy = deep_copy(&mut self.arena, index);
@@ -242,6 +515,15 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
         self.top = Some(copy);
         Some(())
     }
+    #[cfg(feature = "cow_duplicate")]
+    #[inline]
+    fn duplicate(&mut self) -> Option<()> {
+        let index = self.top?;
+        let copy = self.share(index);
+        *self.arena[copy].next_mut() = Some(index);
+        self.top = Some(copy);
+        Some(())
+    }
     #[inline]
     fn surround(&mut self, count: usize) -> Option<()> {
         if count.is_zero() {
@@ -250,11 +532,13 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
         let first = self.top?;
         #[cfg_attr(not(feature = "cache_count"), allow(unused_variables))]
         let (last, count) = move_next(&self.arena, first, count - 1);
+        #[cfg(feature = "cache_count")]
+        let count = cast(count + 1)?;
         let bubble = Bubble::Double {
             inner: (first, last),
             next: self.arena[last].next_mut().take(),
             #[cfg(feature = "cache_count")]
-            count: count + T::one(),
+            count,
         };
         self.top = Some(self.arena.insert(bubble));
         Some(())
@@ -262,6 +546,10 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
     #[inline]
     fn merge(&mut self) -> Option<()> {
         let first = self.top?;
+        self.unshare(first);
+        if let Some(second) = self.arena[first].next() {
+            self.unshare(second);
+        }
         match self.arena[first] {
             Bubble::Single { next, .. } => {
                 let second = next?;
@@ -345,8 +633,85 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
         Some(())
     }
     #[inline]
+    fn merge_top_n(&mut self, count: usize) -> Option<()> {
+        if count < 2 {
+            return Some(());
+        }
+        // NOTE: check first, so the abyss is left unchanged on failure
+        let mut current = self.top;
+        for _ in 0..count {
+            current = self.arena[current?].next();
+        }
+        current = self.top;
+        let (mut first, mut last): (Ref, Ref) = (None, None);
+        #[cfg(feature = "cache_count")]
+        let mut total = T::zero();
+        for _ in 0..count {
+            let index = current?;
+            self.unshare(index);
+            current = self.arena[index].next();
+            let (inner_first, inner_last) = match self.arena[index] {
+                Bubble::Single { .. } => {
+                    #[cfg(feature = "cache_count")]
+                    (total = total + T::one());
+                    (index, index)
+                }
+                Bubble::Double {
+                    inner,
+                    #[cfg(feature = "cache_count")]
+                    count,
+                    ..
+                } => {
+                    #[cfg(feature = "cache_count")]
+                    (total = total + count);
+                    self.arena.remove(index);
+                    inner
+                }
+            };
+            match last {
+                Some(last) => *self.arena[last].next_mut() = Some(inner_first),
+                None => first = Some(inner_first),
+            }
+            last = Some(inner_last);
+        }
+        // SAFETY: unwrap: count is at least 2, so both are set here
+        let (first, last) = (first.unwrap(), last.unwrap());
+        *self.arena[last].next_mut() = None;
+        let bubble = Bubble::Double {
+            inner: (first, last),
+            next: current,
+            #[cfg(feature = "cache_count")]
+            count: total,
+        };
+        self.top = Some(self.arena.insert(bubble));
+        Some(())
+    }
+    #[inline]
+    fn reverse_top(&mut self) -> Option<()> {
+        let top = self.top?;
+        self.unshare(top);
+        let Bubble::Double {
+            inner: (first, last),
+            ..
+        } = self.arena[top]
+        else {
+            return None;
+        };
+        let (mut previous, mut current) = (None, Some(first));
+        while let Some(index) = current {
+            current = replace(self.arena[index].next_mut(), previous);
+            previous = Some(index);
+        }
+        // SAFETY: top is a double bubble by construction
+        let Some(Bubble::Double { inner, .. }) = self.arena.get_mut(top) else {
+            unreachable!()
+        };
+        *inner = (last, first);
+        Some(())
+    }
+    #[inline]
     fn count(&mut self) -> Option<()> {
-        let count = self.arena[self.top?].count(&self.arena);
+        let count = self.arena[self.top?].count(&self.arena)?;
         let bubble = Bubble::Single {
             value: count,
             next: self.top,
@@ -355,6 +720,24 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
         Some(())
     }
     #[inline]
+    fn top_size(&self) -> Option<usize> {
+        cast(self.arena[self.top?].count(&self.arena)?)
+    }
+    #[inline]
+    fn peek<F>(&self, mut fun: F) -> Option<()>
+    where
+        F: FnMut(Self::Value),
+    {
+        let result = visit::<_, Infallible>(&self.arena, self.top?, &mut |value| {
+            fun(value);
+            Ok(())
+        });
+        match result {
+            Ok(_) => Some(()),
+            Err(never) => match never {},
+        }
+    }
+    #[inline]
     fn combine_single<F>(&mut self, op: F) -> Option<()>
     where
         F: Fn(Self::Value, Self::Value) -> Self::Value,
@@ -387,8 +770,9 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
         }
         /// Handle `double op double` case.
         /// `lhs`/`rhs` is first bubble in double, not the root.
+        /// Bubbles without partner are removed.
         /// # Returns
-        /// In case of bubbles with different sizes, will return the first bubble without partner.
+        /// Will return the last remaining bubble of `rhs`.
         #[inline]
         fn map_double<T: Value>(
             arena: &mut Arena<Bubble<T>>,
@@ -396,30 +780,37 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
             mut rhs: Index,
             op: &impl Fn(T, T) -> T,
             #[cfg(feature = "cache_count")] count: &mut T,
-        ) -> Ref {
+        ) -> Index {
             #[cfg_attr(not(feature = "cache_count"), allow(unused_variables))]
             let one = T::one();
             loop {
                 #[cfg(feature = "cache_count")]
                 (*count = *count + one);
-                let (next, _) = inner(arena, lhs, rhs, op);
-                match next {
+                match inner(arena, lhs, rhs, op) {
                     (Some(next_lhs), Some(next_rhs)) => (lhs, rhs) = (next_lhs, next_rhs),
-                    (Some(rest), None) | (None, Some(rest)) => return Some(rest),
-                    (None, None) => return None,
+                    (Some(rest), None) => {
+                        remove_all(arena, rest);
+                        return rhs;
+                    }
+                    (None, Some(rest)) => {
+                        *arena[rhs].next_mut() = None;
+                        remove_all(arena, rest);
+                        return rhs;
+                    }
+                    (None, None) => return rhs,
                 }
             }
         }
         /// Handle unknown bubbles.
+        /// The result is stored at `rhs` and `lhs` is removed.
         /// # Returns
         /// Will return next pointers for both operands.
-        /// Also returns `true` when `rhs` was removed.
         fn inner<T: Value>(
             arena: &mut Arena<Bubble<T>>,
             lhs: Index,
             rhs: Index,
             op: &impl Fn(T, T) -> T,
-        ) -> ((Ref, Ref), bool) {
+        ) -> (Ref, Ref) {
             // SAFETY: lhs and rhs exist and are distinct by construction
             match unsafe { arena.get_many_unchecked_mut([lhs, rhs]) } {
                 [Bubble::Single {
@@ -432,7 +823,7 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
                     let next = (*next_lhs, *next_rhs);
                     *value_rhs = op(*value_lhs, *value_rhs);
                     arena.remove(lhs);
-                    (next, false)
+                    next
                 }
                 [Bubble::Single {
                     value,
@@ -445,7 +836,7 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
                     let (next, value, inner) = ((*next_lhs, *next_rhs), *value, *inner);
                     arena.remove(lhs);
                     map_right(arena, value, inner, op);
-                    (next, false)
+                    next
                 }
                 [Bubble::Double {
                     inner: (inner, _),
@@ -456,9 +847,13 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
                     next: next_rhs,
                 }] => {
                     let (next, value, inner) = ((*next_lhs, *next_rhs), *value, *inner);
-                    arena.remove(rhs);
+                    // NOTE: move lhs into the place of rhs, so the bubble linking to rhs stays valid
+                    // SAFETY: unwrap: lhs exists by construction
+                    let mut bubble = arena.remove(lhs).unwrap();
+                    *bubble.next_mut() = next.1;
+                    arena[rhs] = bubble;
                     map_right(arena, value, inner, &|a, b| op(b, a));
-                    (next, true)
+                    next
                 }
                 [Bubble::Double {
                     inner: (inner_lhs, _),
@@ -474,7 +869,7 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
                     arena.remove(lhs);
                     #[cfg(feature = "cache_count")]
                     let mut new_count = T::zero();
-                    let rest = map_double(
+                    let new_last = map_double(
                         arena,
                         inner_lhs,
                         inner_rhs,
@@ -482,29 +877,29 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
                         #[cfg(feature = "cache_count")]
                         &mut new_count,
                     );
-                    if let Some(rest) = rest {
-                        remove_all(arena, rest);
-                    }
+                    // SAFETY: rhs is a double bubble by construction
+                    let Some(Bubble::Double {
+                        inner: (_, last),
+                        #[cfg(feature = "cache_count")]
+                        count,
+                        ..
+                    }) = arena.get_mut(rhs)
+                    else {
+                        unreachable!()
+                    };
+                    *last = new_last;
                     #[cfg(feature = "cache_count")]
-                    {
-                        // SAFETY: rhs is a double bubble by construction
-                        let Some(Bubble::Double { count, .. }) = arena.get_mut(rhs) else {
-                            unreachable!()
-                        };
-                        *count = new_count
-                    }
-                    (next, false)
+                    (*count = new_count);
+                    next
                 }
             }
         }
         let lhs = self.top?;
         let rhs = self.arena[lhs].next()?;
-        let ((_, third), relink) = inner(&mut self.arena, lhs, rhs, &op);
-        if relink {
-            *self.arena[rhs].next_mut() = third;
-        } else {
-            self.top = Some(rhs);
-        }
+        self.unshare_deep(lhs);
+        self.unshare_deep(rhs);
+        inner(&mut self.arena, lhs, rhs, &op);
+        self.top = Some(rhs);
         Some(())
     }
 
@@ -513,10 +908,28 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
         F1: Fn(Self::Value, Self::Value) -> Self::Value,
         F2: Fn(Self::Value, Self::Value) -> Self::Value,
     {
+        /// Replace the bubble at `index` with a double bubble holding `first` on top of `second`.
+        #[inline]
+        fn split<T: Value>(arena: &mut Arena<Bubble<T>>, index: Index, first: T, second: T) {
+            let next = arena[index].next();
+            let second = arena.insert(Bubble::Single {
+                value: second,
+                next: None,
+            });
+            let first = arena.insert(Bubble::Single {
+                value: first,
+                next: Some(second),
+            });
+            arena[index] = Bubble::Double {
+                inner: (first, second),
+                next,
+                // SAFETY: unwrap: 2 should fit into any number type
+                #[cfg(feature = "cache_count")]
+                count: cast::<_, T>(2).unwrap(),
+            };
+        }
         /// Handle `single op double` case.
         /// `rhs` is first bubble in double, not the root.
-        /// # Returns
-        /// Will return the pointer to thr wrapping double bubble
         fn map_right<T: Value>(
             arena: &mut Arena<Bubble<T>>,
             lhs: T,
@@ -524,33 +937,10 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
             op1: &impl Fn(T, T) -> T,
             op2: &impl Fn(T, T) -> T,
         ) {
-            let mut last = None;
-            let mut left_value;
             loop {
-                let next = match &mut arena[rhs] {
-                    Bubble::Single {
-                        value: right_value,
-                        next,
-                    } => {
-                        let next = next.take();
-                        (left_value, *right_value) =
-                            (op1(lhs, *right_value), op2(lhs, *right_value));
-                        let left = Bubble::Single {
-                            value: left_value,
-                            next: Some(rhs),
-                        };
-                        let left_index = arena.insert(left);
-                        let outer = Bubble::Double {
-                            inner: (left_index, rhs),
-                            next: None,
-                            // SAFETY: unwrap: 2 should fit into any number type
-                            #[cfg(feature = "cache_count")]
-                            count: cast::<_, T>(2).unwrap(),
-                        };
-                        let index = arena.insert(outer);
-                        if let Some(last) = last {
-                            *arena[last].next_mut() = Some(index);
-                        }
+                let next = match arena[rhs] {
+                    Bubble::Single { value, next } => {
+                        split(arena, rhs, op1(lhs, value), op2(lhs, value));
                         next
                     }
                     Bubble::Double {
@@ -558,19 +948,19 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
                         next,
                         ..
                     } => {
-                        let (inner, next) = (*inner, *next);
                         map_right(arena, lhs, inner, op1, op2);
                         next
                     }
                 };
                 let Some(next) = next else { return };
-                (last, rhs) = (Some(rhs), next);
+                rhs = next;
             }
         }
         /// Handle `double op double` case.
         /// `lhs`/`rhs` is first bubble in double, not the root.
+        /// Bubbles without partner are removed.
         /// # Returns
-        /// In case of bubbles with different sizes, will return the first bubble without partner.
+        /// Will return the last remaining bubble of `rhs`.
         #[inline]
         fn map_double<T: Value>(
             arena: &mut Arena<Bubble<T>>,
@@ -579,129 +969,134 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
             op1: &impl Fn(T, T) -> T,
             op2: &impl Fn(T, T) -> T,
             #[cfg(feature = "cache_count")] count: &mut T,
-        ) -> Ref {
-            let mut last = None;
+        ) -> Index {
             #[cfg_attr(not(feature = "cache_count"), allow(unused_variables))]
             let one = T::one();
             loop {
                 #[cfg(feature = "cache_count")]
                 (*count = *count + one);
-                let (outer, next) = inner(arena, lhs, rhs, op1, op2);
-                if let Some(last) = last {
-                    *arena[last].next_mut() = Some(outer);
-                }
-                last = Some(outer);
-                match next {
+                match inner(arena, lhs, rhs, op1, op2) {
                     (Some(next_lhs), Some(next_rhs)) => (lhs, rhs) = (next_lhs, next_rhs),
-                    (Some(rest), None) | (None, Some(rest)) => return Some(rest),
-                    (None, None) => return None,
+                    (Some(rest), None) => {
+                        remove_all(arena, rest);
+                        return rhs;
+                    }
+                    (None, Some(rest)) => {
+                        *arena[rhs].next_mut() = None;
+                        remove_all(arena, rest);
+                        return rhs;
+                    }
+                    (None, None) => return rhs,
                 }
             }
         }
         /// Handle unknown bubbles.
+        /// The result is stored at `rhs` and `lhs` is removed.
         /// # Returns
-        /// Will return the pointer to the wrapping double bubble
-        /// Will also return next pointers for both operands.
+        /// Will return next pointers for both operands.
         fn inner<T: Value>(
             arena: &mut Arena<Bubble<T>>,
             lhs: Index,
             rhs: Index,
             op1: &impl Fn(T, T) -> T,
             op2: &impl Fn(T, T) -> T,
-        ) -> (Index, (Ref, Ref)) {
+        ) -> (Ref, Ref) {
             // SAFETY: lhs and rhs exist and are distinct by construction
             match unsafe { arena.get_many_unchecked_mut([lhs, rhs]) } {
                 [Bubble::Single {
-                    value: left_value,
-                    next: left_next,
+                    value: value_lhs,
+                    next: next_lhs,
                 }, Bubble::Single {
-                    value: right_value,
-                    next: right_next,
+                    value: value_rhs,
+                    next: next_rhs,
                 }] => {
-                    let next = (replace(left_next, Some(rhs)), right_next.take());
-                    (*left_value, *right_value) = (
-                        op1(*left_value, *right_value),
-                        op2(*left_value, *right_value),
+                    let (next, value_lhs, value_rhs) =
+                        ((*next_lhs, *next_rhs), *value_lhs, *value_rhs);
+                    arena.remove(lhs);
+                    split(
+                        arena,
+                        rhs,
+                        op1(value_lhs, value_rhs),
+                        op2(value_lhs, value_rhs),
                     );
-                    let outer = Bubble::Double {
-                        inner: (lhs, rhs),
-                        next: None,
-                        // SAFETY: unwrap: 2 should fit into any number type
-                        #[cfg(feature = "cache_count")]
-                        count: cast::<_, T>(2).unwrap(),
-                    };
-                    let index = arena.insert(outer);
-                    (index, next)
+                    next
                 }
                 [Bubble::Single {
                     value,
-                    next: left_next,
+                    next: next_lhs,
                 }, Bubble::Double {
                     inner: (inner, _),
-                    next: right_next,
+                    next: next_rhs,
                     ..
                 }] => {
-                    let (value, inner, next) = (*value, *inner, (*left_next, *right_next));
+                    let (next, value, inner) = ((*next_lhs, *next_rhs), *value, *inner);
                     arena.remove(lhs);
                     map_right(arena, value, inner, op1, op2);
-                    (rhs, next)
+                    next
                 }
                 [Bubble::Double {
                     inner: (inner, _),
-                    next: left_next,
+                    next: next_lhs,
                     ..
                 }, Bubble::Single {
                     value,
-                    next: right_next,
+                    next: next_rhs,
                 }] => {
-                    let (value, inner, next) = (*value, *inner, (*left_next, *right_next));
-                    arena.remove(rhs);
+                    let (next, value, inner) = ((*next_lhs, *next_rhs), *value, *inner);
+                    // NOTE: move lhs into the place of rhs, so the bubble linking to rhs stays valid
+                    // SAFETY: unwrap: lhs exists by construction
+                    let mut bubble = arena.remove(lhs).unwrap();
+                    *bubble.next_mut() = next.1;
+                    arena[rhs] = bubble;
                     map_right(arena, value, inner, &|a, b| op1(b, a), &|a, b| op2(b, a));
-                    (lhs, next)
+                    next
                 }
                 [Bubble::Double {
-                    inner: (left_inner, _),
-                    next: left_next,
+                    inner: (inner_lhs, _),
+                    next: next_lhs,
                     ..
                 }, Bubble::Double {
-                    inner: (right_inner, _),
-                    next: right_next,
+                    inner: (inner_rhs, _),
+                    next: next_rhs,
                     ..
                 }] => {
-                    let (left_inner, right_inner, next) =
-                        (*left_inner, *right_inner, (*left_next, *right_next));
+                    let (next, inner_lhs, inner_rhs) =
+                        ((*next_lhs, *next_rhs), *inner_lhs, *inner_rhs);
                     arena.remove(lhs);
                     #[cfg(feature = "cache_count")]
                     let mut new_count = T::zero();
-                    let rest = map_double(
+                    let new_last = map_double(
                         arena,
-                        left_inner,
-                        right_inner,
+                        inner_lhs,
+                        inner_rhs,
                         op1,
                         op2,
                         #[cfg(feature = "cache_count")]
                         &mut new_count,
                     );
-                    if let Some(rest) = rest {
-                        remove_all(arena, rest);
-                    }
+                    // SAFETY: rhs is a double bubble by construction
+                    let Some(Bubble::Double {
+                        inner: (_, last),
+                        #[cfg(feature = "cache_count")]
+                        count,
+                        ..
+                    }) = arena.get_mut(rhs)
+                    else {
+                        unreachable!()
+                    };
+                    *last = new_last;
                     #[cfg(feature = "cache_count")]
-                    {
-                        // SAFETY: rhs is a double bubble by construction
-                        let Some(Bubble::Double { count, .. }) = arena.get_mut(rhs) else {
-                            unreachable!()
-                        };
-                        *count = new_count
-                    }
-                    (rhs, next)
+                    (*count = new_count);
+                    next
                 }
             }
         }
         let lhs = self.top?;
         let rhs = self.arena[lhs].next()?;
-        let (outer, (_, third)) = inner(&mut self.arena, lhs, rhs, &op1, &op2);
-        *self.arena[outer].next_mut() = third;
-        self.top = Some(outer);
+        self.unshare_deep(lhs);
+        self.unshare_deep(rhs);
+        inner(&mut self.arena, lhs, rhs, &op1, &op2);
+        self.top = Some(rhs);
         Some(())
     }
 
@@ -723,35 +1118,31 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
     where
         F: FnMut(Self::Value) -> Result<(), E>,
     {
-        fn inner<T: Value, E>(
-            arena: &mut Arena<Bubble<T>>,
-            index: Index,
-            fun: &mut impl FnMut(T) -> Result<(), E>,
-        ) -> Result<Ref, E> {
-            match arena.remove(index) {
-                Some(Bubble::Single { value, next }) => {
-                    fun(value)?;
-                    Ok(next)
-                }
-                Some(Bubble::Double {
-                    inner: (mut index, _),
-                    next,
-                    ..
-                }) => loop {
-                    if let Some(next) = inner(arena, index, fun)? {
-                        index = next;
-                    } else {
-                        return Ok(next);
-                    }
-                },
-                // SAFETY: top exists by construction
-                None => unreachable!(),
-            }
-        }
         let Some(top) = self.top else { return Ok(None) };
-        self.top = inner(&mut self.arena, top, &mut fun)?;
+        // NOTE: only remove the bubble after visiting all values, so it stays intact when `fun` fails
+        visit(&self.arena, top, &mut fun)?;
+        // SAFETY: unwrap: top bubble exists
+        self.double_pop().unwrap();
+        Ok(Some(()))
+    }
+    #[inline]
+    fn consume_nested<F, E>(&mut self, mut fun: F) -> Result<Option<()>, E>
+    where
+        F: FnMut(Nested<Self::Value>) -> Result<(), E>,
+    {
+        let Some(top) = self.top else { return Ok(None) };
+        // NOTE: only remove the bubble after visiting all values, so it stays intact when `fun` fails
+        visit_nested(&self.arena, top, &mut fun)?;
+        // SAFETY: unwrap: top bubble exists
+        self.double_pop().unwrap();
         Ok(Some(()))
     }
+    #[cfg(feature = "cow_duplicate")]
+    fn double_pop(&mut self) -> Option<()> {
+        self.top = self.release(self.top?);
+        Some(())
+    }
+    #[cfg(not(feature = "cow_duplicate"))]
     fn double_pop(&mut self) -> Option<()> {
         self.top = match self.arena.remove(self.top?) {
             Some(Bubble::Single { next, .. }) => next,
@@ -769,6 +1160,48 @@ impl<T: Value> awa_core::Abyss for Abyss<T> {
         Some(())
     }
 }
+impl<T: Value> PartialEq for Abyss<T> {
+    fn eq(&self, other: &Self) -> bool {
+        #[inline]
+        fn eq_chain<T: Value>(
+            lhs: &Arena<Bubble<T>>,
+            mut left: Ref,
+            rhs: &Arena<Bubble<T>>,
+            mut right: Ref,
+        ) -> bool {
+            loop {
+                let (left_index, right_index) = match (left, right) {
+                    (None, None) => return true,
+                    (Some(left), Some(right)) => (left, right),
+                    _ => return false,
+                };
+                match (lhs[left_index], rhs[right_index]) {
+                    (
+                        Bubble::Single {
+                            value: left_value, ..
+                        },
+                        Bubble::Single {
+                            value: right_value, ..
+                        },
+                    ) if left_value == right_value => (),
+                    (
+                        Bubble::Double {
+                            inner: (left_inner, _),
+                            ..
+                        },
+                        Bubble::Double {
+                            inner: (right_inner, _),
+                            ..
+                        },
+                    ) if eq_chain(lhs, Some(left_inner), rhs, Some(right_inner)) => (),
+                    _ => return false,
+                }
+                (left, right) = (lhs[left_index].next(), rhs[right_index].next());
+            }
+        }
+        eq_chain(&self.arena, self.top, &other.arena, other.top)
+    }
+}
 impl<T: Value> Display for Abyss<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         #[inline]
@@ -808,3 +1241,285 @@ impl<T: Value> Display for Abyss<T> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use awa_core::Abyss as _;
+    use std::mem::size_of;
+
+    use super::*;
+
+    const WORD: usize = size_of::<usize>();
+
+    #[test]
+    fn layout() {
+        assert_eq!(size_of::<Index>(), WORD);
+        assert_eq!(size_of::<Ref>(), WORD);
+        // NOTE: payloads of Bubble::Single and Bubble::Double
+        assert_eq!(size_of::<(isize, Ref)>(), 2 * WORD);
+        assert_eq!(size_of::<((Index, Index), Ref)>(), 3 * WORD);
+        let words = if cfg!(feature = "cache_count") { 4 } else { 3 };
+        assert_eq!(size_of::<Bubble<isize>>(), words * WORD);
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(size_of::<Bubble<i64>>(), words * WORD);
+    }
+
+    #[test]
+    fn from_singles_and_double() {
+        let mut abyss = Abyss::from_singles([1, 2, 3]);
+        assert_eq!(abyss.to_string(), "3\n2\n1\n");
+        assert_eq!(abyss.pop_value(), Some(3));
+        assert_eq!(abyss.depth(), 2);
+        let abyss = Abyss::from_double([1, 2, 3]);
+        assert_eq!(abyss.to_string(), "[3, 2, 1]\n");
+        assert_eq!(abyss.depth(), 1);
+        assert!(Abyss::<isize>::from_double([]).is_empty());
+        assert_eq!(Abyss::from_iter([1, 2, 3]), Abyss::from_singles([1, 2, 3]));
+    }
+
+    /// `[[1, 2], 3]` duplicated, so both copies share their inner bubbles with `cow_duplicate`.
+    fn duplicated() -> Abyss<isize> {
+        let mut abyss = Abyss::from_double([1, 2]);
+        abyss.blow(3).unwrap();
+        abyss.surround(2).unwrap();
+        abyss.duplicate().unwrap();
+        abyss
+    }
+
+    #[test]
+    fn duplicates_are_independent() {
+        const COPY: &str = "[3, [2, 1]]\n";
+        let check = |op: fn(&mut Abyss<isize>), expected: &str| {
+            let mut abyss = duplicated();
+            op(&mut abyss);
+            assert_eq!(abyss.to_string(), format!("{expected}{COPY}"));
+            // NOTE: the remaining copy has to survive freeing the other one and stay mutable
+            while abyss.depth() > 1 {
+                abyss.double_pop().unwrap();
+            }
+            assert_eq!(abyss.to_string(), COPY);
+            abyss.reverse_top().unwrap();
+            assert_eq!(abyss.to_string(), "[[2, 1], 3]\n");
+        };
+        assert_eq!(duplicated().to_string(), format!("{COPY}{COPY}"));
+        check(|abyss| abyss.pop().unwrap(), "3\n[2, 1]\n");
+        check(
+            |abyss| {
+                abyss.pop().unwrap();
+                abyss.submerge(1).unwrap();
+                abyss.pop().unwrap();
+            },
+            "2\n1\n3\n",
+        );
+        check(
+            |abyss| {
+                abyss.blow(7).unwrap();
+                abyss.merge().unwrap();
+            },
+            "[7, 3, [2, 1]]\n",
+        );
+        check(
+            |abyss| {
+                abyss.blow(10).unwrap();
+                abyss.combine_single(|top, second| top + second).unwrap();
+            },
+            "[13, [12, 11]]\n",
+        );
+        check(|abyss| abyss.reverse_top().unwrap(), "[[2, 1], 3]\n");
+    }
+
+    #[test]
+    fn blow_double_beyond_surround_limit() {
+        let values = (0..40).collect::<Vec<isize>>();
+        let mut abyss = Abyss::default();
+        abyss.blow(-1).unwrap();
+        abyss.blow_double(&values).unwrap();
+        assert_eq!(abyss.depth(), 2);
+        let mut consumed = Vec::new();
+        abyss
+            .consume(|value| {
+                consumed.push(value);
+                Ok::<_, Infallible>(())
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(consumed, values.iter().rev().copied().collect::<Vec<_>>());
+        assert_eq!(abyss.pop_value(), Some(-1));
+    }
+
+    #[test]
+    fn equality_ignores_arena_layout() {
+        let mut reused = Abyss::default();
+        reused.blow(1).unwrap();
+        for value in 5..10 {
+            reused.blow(value).unwrap();
+        }
+        reused.blow_double([2, 3]).unwrap();
+        reused.submerge(5).unwrap();
+        for _ in 5..10 {
+            reused.pop().unwrap();
+        }
+        reused.blow(4).unwrap();
+
+        let mut direct = Abyss::default();
+        direct.blow(1).unwrap();
+        direct.blow_double([2, 3]).unwrap();
+        direct.blow(4).unwrap();
+        let collected = {
+            let mut abyss = Abyss::from_singles([1]);
+            abyss.blow(2).unwrap();
+            abyss.blow(3).unwrap();
+            abyss.surround(2).unwrap();
+            abyss.blow(4).unwrap();
+            abyss
+        };
+
+        // NOTE: the top bubble reused a freed slot
+        assert_ne!(reused.top, direct.top);
+        assert_eq!(reused, direct);
+        assert_eq!(collected, direct);
+        assert_eq!(reused.to_string(), direct.to_string());
+        assert_eq!(reused.to_nested(), direct.to_nested());
+
+        // NOTE: same values in the same order, but nested differently
+        let mut nested = Abyss::from_double([2, 3]);
+        nested.surround(1).unwrap();
+        assert_ne!(nested, Abyss::from_double([2, 3]));
+        assert_ne!(Abyss::from_singles([2, 3]), Abyss::from_double([2, 3]));
+        let mut front = Abyss::from_double([1]);
+        front.blow(2).unwrap();
+        front.surround(2).unwrap();
+        let mut back = Abyss::from_singles([1]);
+        back.blow_double([2]).unwrap();
+        back.surround(2).unwrap();
+        assert_eq!(front.to_nested().len(), back.to_nested().len());
+        assert_ne!(front, back);
+    }
+
+    #[test]
+    fn pop_value_only_takes_singles() {
+        let mut abyss = Abyss::from_singles([1, 2]);
+        assert_eq!(abyss.pop_value(), Some(2));
+        assert_eq!(abyss.pop_value(), Some(1));
+        assert_eq!(abyss.pop_value(), None);
+        let mut abyss = Abyss::from_double([1, 2]);
+        assert_eq!(abyss.pop_value(), None);
+        assert_eq!(abyss.to_nested(), Abyss::from_double([1, 2]).to_nested());
+        abyss.blow(3).unwrap();
+        assert_eq!(abyss.pop_value(), Some(3));
+        assert_eq!(abyss.depth(), 1);
+    }
+
+    #[test]
+    fn combine_single_operand_shapes() {
+        let sub = |setup: fn(&mut Abyss<isize>)| {
+            let mut abyss = Abyss::default();
+            setup(&mut abyss);
+            abyss.combine_single(|top, second| top - second).unwrap();
+            abyss.to_string()
+        };
+        assert_eq!(
+            sub(|abyss| {
+                abyss.blow(3).unwrap();
+                abyss.blow(10).unwrap();
+            }),
+            "7\n"
+        );
+        // NOTE: the top bubble stays the first operand when the shapes differ
+        assert_eq!(
+            sub(|abyss| {
+                abyss.blow_double([1, 2]).unwrap();
+                abyss.blow(10).unwrap();
+            }),
+            "[8, 9]\n"
+        );
+        assert_eq!(
+            sub(|abyss| {
+                abyss.blow(10).unwrap();
+                abyss.blow_double([1, 2]).unwrap();
+            }),
+            "[-8, -9]\n"
+        );
+        assert_eq!(
+            sub(|abyss| {
+                abyss.blow_double([1, 2, 3]).unwrap();
+                abyss.blow_double([10, 20]).unwrap();
+            }),
+            "[17, 8]\n"
+        );
+        assert_eq!(
+            sub(|abyss| {
+                abyss.blow_double([10, 20]).unwrap();
+                abyss.blow_double([1, 2, 3]).unwrap();
+            }),
+            "[-17, -8]\n"
+        );
+        assert_eq!(
+            sub(|abyss| {
+                abyss.blow(10).unwrap();
+                abyss.blow_double([1, 2]).unwrap();
+                abyss.surround(2).unwrap();
+                abyss.blow(100).unwrap();
+            }),
+            "[[98, 99], 90]\n"
+        );
+    }
+
+    #[test]
+    fn combine_double_quotient_on_top() {
+        let div = |setup: fn(&mut Abyss<isize>)| {
+            let mut abyss = Abyss::default();
+            setup(&mut abyss);
+            abyss
+                .combine_double(|top, second| top / second, |top, second| top % second)
+                .unwrap();
+            abyss.to_string()
+        };
+        // 7 div 3 with another bubble below, which has to stay linked
+        assert_eq!(
+            div(|abyss| {
+                abyss.blow(5).unwrap();
+                abyss.blow(3).unwrap();
+                abyss.blow(7).unwrap();
+            }),
+            "[2, 1]\n5\n"
+        );
+        assert_eq!(
+            div(|abyss| {
+                abyss.blow(3).unwrap();
+                abyss.blow_double([8, 7]).unwrap();
+            }),
+            "[[2, 1], [2, 2]]\n"
+        );
+    }
+
+    #[test]
+    fn footprint_of_string() {
+        let text = "awa awa aw"
+            .bytes()
+            .map(|byte| awa_core::AwaSCII::from_ascii(byte).unwrap());
+        let mut abyss = Abyss::<isize>::new();
+        abyss.blow_awascii(text.collect::<Vec<_>>()).unwrap();
+        let stats = abyss.arena_footprint();
+        assert_eq!(
+            stats,
+            FootprintStats {
+                singles: 10,
+                doubles: 1,
+                free: 0
+            }
+        );
+        // NOTE: popping a double bubble releases the bubbles inside of it
+        abyss.blow(1).unwrap();
+        abyss.submerge(0).unwrap();
+        abyss.pop().unwrap();
+        assert_eq!(
+            abyss.arena_footprint(),
+            FootprintStats {
+                singles: 11,
+                doubles: 0,
+                free: 1
+            }
+        );
+    }
+}
@@ -0,0 +1,9 @@
+#![deny(unused_must_use)]
+
+use awa_abyss::linked::Abyss;
+use awa_core::Abyss as _;
+
+fn main() {
+    let mut abyss = Abyss::<isize>::new();
+    abyss.blow(1);
+}
@@ -2,6 +2,7 @@ use core::str;
 use std::{
     collections::HashMap,
     fmt::{Display, Write},
+    io::BufRead,
     ops::Deref,
     path::Path,
     rc::Rc,
@@ -128,8 +129,10 @@ impl<'i> Spanned<&'i [u8]> {
         self.trim_start();
         self.trim_end();
     }
+    /// Split into two parts at `middle`, clamping to the length of the item.
     #[inline]
     pub fn split_at(&self, middle: usize) -> (Self, Self) {
+        let middle = middle.min(self.item.len());
         (
             Self {
                 item: &self.item[..middle],
@@ -146,6 +149,31 @@ impl<'i> Spanned<&'i [u8]> {
         let (before, after) = self.split_at(middle);
         (before, after.split_at(1).1)
     }
+    /// Like [`Spanned::split_at_char`] but ignores `char` when enclosed in single or double quotes.
+    pub fn split_at_unquoted(&self, char: u8) -> (Self, Self) {
+        let mut quote = None;
+        let middle = self
+            .item
+            .iter()
+            .take_while(|c| match quote {
+                Some(q) => {
+                    if **c == q {
+                        quote = None;
+                    }
+                    true
+                }
+                None if **c == char => false,
+                None => {
+                    if matches!(**c, b'\'' | b'"') {
+                        quote = Some(**c);
+                    }
+                    true
+                }
+            })
+            .count();
+        let (before, after) = self.split_at(middle);
+        (before, after.split_at(1).1)
+    }
     #[inline]
     pub fn split_at_whitespace(&self) -> (Self, Self) {
         let middle = self
@@ -185,15 +213,23 @@ impl<'i> Spanned<&'i [u8]> {
     }
     #[inline]
     pub fn take_awascii(&mut self) -> Result<Option<AwaSCII>> {
-        let len = self.len();
-        match self.item.get(len.saturating_sub(1)) {
-            Some(b'n') if self.item.get(len.saturating_sub(2)) == Some(&b'\\') => {
-                *self = self.split_at(len - 2).1;
+        let Some(last) = self.item.len().checked_sub(1) else {
+            return Ok(None);
+        };
+        match self.item.get(last) {
+            Some(b'n') if last > 0 && self.item.get(last - 1) == Some(&b'\\') => {
+                *self = self.split_at(last - 1).0;
+                // SAFETY: 63 is a valid AwaSCII character
+                Ok(Some(unsafe { AwaSCII::new_unchecked(63) }))
+            }
+            // NOTE: a string spanning multiple lines of a file with CRLF line endings
+            Some(b'\n') if last > 0 && self.item.get(last - 1) == Some(&b'\r') => {
+                *self = self.split_at(last - 1).0;
                 // SAFETY: 63 is a valid AwaSCII character
                 Ok(Some(unsafe { AwaSCII::new_unchecked(63) }))
             }
             Some(ascii) => {
-                let (rest, last) = self.split_at(len - 1);
+                let (rest, last) = self.split_at(last);
                 let awascii = AwaSCII::from_ascii(*ascii).ok_or_else(|| Error::ParseError {
                     span: last.span,
                     msg: "invalid AwaSCII".to_string(),
@@ -224,7 +260,28 @@ pub enum Error {
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
-pub type Macro = Box<dyn Fn(Spanned<&[u8]>, &MacroTable) -> Result<Vec<AwaTism>>>;
+/// Settings that change how source code is interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ParserOptions {
+    /// Starts a comment extending to the end of the line.
+    pub comment: u8,
+    /// Turn a panic inside of a macro into a [`Error::SyntaxError`] instead of unwinding through the parser.
+    ///
+    /// This relies on [`std::panic::catch_unwind`], so it has no effect when compiled with `panic = "abort"`
+    /// and the panic message is still reported by the panic hook.
+    pub catch_panics: bool,
+}
+impl Default for ParserOptions {
+    #[inline(always)]
+    fn default() -> Self {
+        Self {
+            comment: b';',
+            catch_panics: false,
+        }
+    }
+}
+
+pub type Macro = Box<dyn Fn(Spanned<&[u8]>, &MacroTable, &ParserOptions) -> Result<Vec<AwaTism>>>;
 pub struct MacroTable(HashMap<String, Macro>);
 impl Deref for MacroTable {
     type Target = HashMap<String, Macro>;
@@ -233,8 +290,202 @@ impl Deref for MacroTable {
         &self.0
     }
 }
+impl MacroTable {
+    /// Add a macro that can be used as `!name`, returning the macro that was previously registered with that name.
+    #[inline]
+    pub fn register(&mut self, name: impl Into<String>, r#macro: Macro) -> Option<Macro> {
+        self.0.insert(name.into(), r#macro)
+    }
+}
 #[inline]
-pub fn load_program(file: &Path, src: &[u8], macros: &MacroTable) -> Result<Program> {
-    let awatisms = parser::lines(file.to_str().unwrap().into(), src, macros)?;
+pub fn load_program(
+    file: &Path,
+    src: &[u8],
+    macros: &MacroTable,
+    options: &ParserOptions,
+) -> Result<Program> {
+    let awatisms = parser::lines(file.to_str().unwrap().into(), src, macros, options)?;
     Ok(Program::from_vec(awatisms))
 }
+/// Parse a single instruction from a string, this is the inverse of formatting an [`AwaTism`].
+/// A trailing comment is ignored, like in [`parse_program`].
+/// Errors will point to a file named `<str>`.
+#[inline]
+pub fn parse_awatism(src: &str) -> Result<AwaTism> {
+    let line = Spanned::from_line("<str>".into(), 1, src.as_bytes());
+    let (mut line, _comment) = line.split_at_unquoted(ParserOptions::default().comment);
+    line.trim();
+    parser::awatism(line)
+}
+/// Assemble a whole program from a string using the default macros and options.
+/// Errors will point to a file named `<str>`.
+///
+/// This never touches the filesystem, so `!include` is not available.
+#[inline]
+pub fn parse_program(src: &str) -> Result<Program> {
+    let macros = MacroTable::in_memory();
+    let awatisms = parser::lines(
+        "<str>".into(),
+        src.as_bytes(),
+        &macros,
+        &ParserOptions::default(),
+    )?;
+    Ok(Program::from_vec(awatisms))
+}
+/// Same as [`load_program`], but reads the source one line at a time instead of keeping all of it in memory.
+#[inline]
+pub fn assemble_reader(
+    file: &Path,
+    reader: impl BufRead,
+    macros: &MacroTable,
+    options: &ParserOptions,
+) -> Result<Program> {
+    let awatisms = parser::lines(file.to_str().unwrap().into(), reader, macros, options)?;
+    Ok(Program::from_vec(awatisms))
+}
+
+#[cfg(test)]
+mod tests {
+    use awa_core::u5;
+
+    use super::*;
+
+    fn spanned(line: &[u8]) -> Spanned<&[u8]> {
+        Spanned::from_line(Rc::from("test"), 1, line)
+    }
+
+    #[test]
+    fn empty_spanned() {
+        let mut empty = spanned(b"");
+        let (before, after) = empty.split_at(5);
+        assert!(before.item.is_empty() && after.item.is_empty());
+        assert!(before.is_empty() && after.is_empty());
+        assert_eq!(empty.take_awascii().unwrap(), None);
+    }
+
+    #[test]
+    fn one_byte_spanned() {
+        let (before, after) = spanned(b"n").split_at(5);
+        assert_eq!((before.item, after.item), (&b"n"[..], &b""[..]));
+        assert_eq!((before.len(), after.len()), (1, 0));
+
+        let mut one = spanned(b"n");
+        assert_eq!(one.take_awascii().unwrap(), AwaSCII::from_ascii(b'n'));
+        assert_eq!(one.take_awascii().unwrap(), None);
+        assert!(spanned(b"\\").take_awascii().is_err());
+    }
+
+    #[test]
+    fn disassemble_reassembles() {
+        let program = parse_program(
+            "nop\nprn\npr1\nred\nr3d\nblo -1\nsbm 31\npop\ndpl\nsrn 2\nmrg\n4dd\nsub\nmul\ndiv\ncnt\n\
+             lbl 2\njmp 2\neql\nlss\ngr8\np0p\n!str \"awa\"\ntrm\n",
+        )
+        .unwrap();
+        let reassembled = parse_program(&program.disassemble()).unwrap();
+        assert_eq!(reassembled.instructions(), program.instructions());
+        assert_eq!(reassembled.labels(), program.labels());
+    }
+
+    #[test]
+    fn stray_arguments() {
+        let error = parse_awatism("pop 5").unwrap_err();
+        assert!(matches!(error, Error::SyntaxError { ref span, .. } if span.start == 4));
+        assert_eq!(parse_awatism("pop ; note").unwrap(), AwaTism::Pop);
+        let program = parse_program("pop ; note\nblo 1 ; note").unwrap();
+        assert_eq!(program.instructions(), [AwaTism::Pop, AwaTism::Blow(1)]);
+        assert!(parse_program("pop 5").is_err());
+    }
+
+    #[test]
+    fn assemble_reader_matches_load_program() {
+        let src = include_bytes!("../../../tests/corpus/fibs.awasm");
+        let (file, macros, options) = (
+            Path::new("fibs.awasm"),
+            MacroTable::default(),
+            ParserOptions::default(),
+        );
+        let expected = load_program(file, src, &macros, &options).unwrap();
+        let reader = std::io::BufReader::with_capacity(4, &src[..]);
+        let program = assemble_reader(file, reader, &macros, &options).unwrap();
+        assert_eq!(program.instructions(), expected.instructions());
+    }
+
+    #[test]
+    fn parse_every_mnemonic() {
+        let cases = [
+            ("nop", AwaTism::NoOp),
+            ("prn", AwaTism::Print),
+            ("pr1", AwaTism::PrintNum),
+            ("red", AwaTism::Read),
+            ("r3d", AwaTism::ReadNum),
+            ("blo -128", AwaTism::Blow(-128)),
+            ("sbm 31", AwaTism::Submerge(u5::MAX)),
+            ("pop", AwaTism::Pop),
+            ("dpl", AwaTism::Duplicate),
+            ("srn 2", AwaTism::Surround(u5::TWO)),
+            ("mrg", AwaTism::Merge),
+            ("4dd", AwaTism::Add),
+            ("sub", AwaTism::Subtract),
+            ("mul", AwaTism::Multiply),
+            ("div", AwaTism::Divide),
+            ("cnt", AwaTism::Count),
+            ("lbl 2", AwaTism::Label(u5::TWO)),
+            ("jmp 0x1f", AwaTism::Jump(u5::MAX)),
+            ("eql", AwaTism::EqualTo),
+            ("lss", AwaTism::LessThan),
+            ("gr8", AwaTism::GreaterThan),
+            ("p0p", AwaTism::DoublePop),
+            ("trm", AwaTism::Terminate),
+        ];
+        for (src, expected) in cases {
+            assert_eq!(parse_awatism(src).unwrap(), expected, "{src}");
+            assert_eq!(parse_awatism(&expected.to_string()).unwrap(), expected);
+        }
+        assert!(parse_awatism("  blo\t1  ").is_ok());
+        assert!(parse_awatism("blo 128").is_err());
+        assert!(parse_awatism("sbm").is_err());
+    }
+
+    #[test]
+    fn parse_program_from_str() {
+        let program = parse_program("blo 1 ; one\nblo 2\n\n4dd\npr1\ntrm").unwrap();
+        assert_eq!(
+            program.instructions(),
+            [
+                AwaTism::Blow(1),
+                AwaTism::Blow(2),
+                AwaTism::Add,
+                AwaTism::PrintNum,
+                AwaTism::Terminate
+            ]
+        );
+        let Err(Error::UnknownIdentifier { span, .. }) = parse_program("trm\nxyz") else {
+            panic!("expected unknown identifier");
+        };
+        assert_eq!((&*span.file, span.line), ("<str>", 2));
+    }
+
+    #[test]
+    fn panicking_macro() {
+        let mut macros = MacroTable::default();
+        macros.register("boom", Box::new(|_, _, _| panic!("boom")));
+        let src = b"blo 1\n!boom\ntrm\n";
+        let mut options = ParserOptions {
+            catch_panics: true,
+            ..Default::default()
+        };
+        match load_program(Path::new("test"), src, &macros, &options) {
+            Err(Error::SyntaxError { span, msg }) => {
+                assert_eq!(span.line, 2);
+                assert_eq!(msg, "macro !boom panicked: boom");
+            }
+            other => panic!("expected a syntax error, got {other:?}"),
+        }
+        options.catch_panics = false;
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            load_program(Path::new("test"), src, &macros, &options)
+        }));
+        assert!(result.is_err());
+    }
+}
@@ -1,11 +1,19 @@
 use core::str;
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::HashMap,
+    path::{absolute, Path, PathBuf},
+};
 
 use awa_core::{u5, AwaTism};
+use num_traits::{ConstOne, ConstZero};
 
-use crate::{parser::file, Error, MacroTable, Result, Spanned};
+use crate::{parser::file, Error, MacroTable, ParserOptions, Result, Spanned};
 
-pub fn chr(mut input: Spanned<&[u8]>, _macros: &MacroTable) -> Result<Vec<AwaTism>> {
+pub fn chr(
+    mut input: Spanned<&[u8]>,
+    _macros: &MacroTable,
+    _options: &ParserOptions,
+) -> Result<Vec<AwaTism>> {
     input.trim();
     let (begin, rest) = input.split_at_char(b'\'');
     if !begin.is_empty() {
@@ -27,7 +35,11 @@ pub fn chr(mut input: Spanned<&[u8]>, _macros: &MacroTable) -> Result<Vec<AwaTis
     })?;
     Ok(vec![AwaTism::Blow(*awascii as i8)])
 }
-pub fn str(mut input: Spanned<&[u8]>, _macros: &MacroTable) -> Result<Vec<AwaTism>> {
+pub fn str(
+    mut input: Spanned<&[u8]>,
+    _macros: &MacroTable,
+    _options: &ParserOptions,
+) -> Result<Vec<AwaTism>> {
     input.trim();
     let (begin, rest) = input.split_at_char(b'"');
     if !begin.is_empty() {
@@ -37,6 +49,12 @@ pub fn str(mut input: Spanned<&[u8]>, _macros: &MacroTable) -> Result<Vec<AwaTis
         });
     }
     let (mut inner, end) = rest.split_at_char(b'"');
+    if inner.item.len() == rest.item.len() {
+        return Err(Error::SyntaxError {
+            span: rest.span,
+            msg: "unterminated string".to_string(),
+        });
+    }
     if !end.is_empty() {
         return Err(Error::SyntaxError {
             span: end.span,
@@ -44,16 +62,14 @@ pub fn str(mut input: Spanned<&[u8]>, _macros: &MacroTable) -> Result<Vec<AwaTis
         });
     }
     let mut buffer = Vec::new();
-    let mut count = 0;
+    let mut count = u5::ZERO;
     let mut first_chunk = true;
-    // SAFETY: 31 is a valid u5
-    let chunk_size = unsafe { u5::new_unchecked(31) };
     while let Some(awascii) = inner.take_awascii()? {
         buffer.push(AwaTism::Blow(*awascii as i8));
-        count += 1;
-        if count == 31 {
-            buffer.push(AwaTism::Surround(chunk_size));
-            count = 0;
+        count = count.saturating_add(u5::ONE);
+        if count == u5::MAX {
+            buffer.push(AwaTism::Surround(u5::MAX));
+            count = u5::ZERO;
             if first_chunk {
                 first_chunk = false;
             } else {
@@ -61,16 +77,44 @@ pub fn str(mut input: Spanned<&[u8]>, _macros: &MacroTable) -> Result<Vec<AwaTis
             }
         }
     }
-    if count > 1 {
-        // SAFETY: count is always a valid u5
-        buffer.push(AwaTism::Surround(unsafe { u5::new_unchecked(count) }));
+    if *count > 1 {
+        buffer.push(AwaTism::Surround(count));
     }
-    if count != 0 && !first_chunk {
+    if *count != 0 && !first_chunk {
         buffer.push(AwaTism::Merge);
     }
+    // NOTE: same as reading an empty line, an empty string is a single zero
+    if buffer.is_empty() {
+        buffer.push(AwaTism::Blow(0));
+    }
     Ok(buffer)
 }
-pub fn include(mut input: Spanned<&[u8]>, macros: &MacroTable) -> Result<Vec<AwaTism>> {
+#[inline(always)]
+pub fn include(
+    input: Spanned<&[u8]>,
+    macros: &MacroTable,
+    options: &ParserOptions,
+) -> Result<Vec<AwaTism>> {
+    include_from(input, macros, options, &[])
+}
+/// Replaces [`include`] for sources that are not read from a file.
+fn no_include(
+    input: Spanned<&[u8]>,
+    _macros: &MacroTable,
+    _options: &ParserOptions,
+) -> Result<Vec<AwaTism>> {
+    Err(Error::SyntaxError {
+        span: input.span,
+        msg: "`!include` is only available when assembling from a file".to_string(),
+    })
+}
+/// Same as [`include`], but relative paths that don't exist next to the current file are also looked up in `search`.
+pub fn include_from(
+    mut input: Spanned<&[u8]>,
+    macros: &MacroTable,
+    options: &ParserOptions,
+    search: &[PathBuf],
+) -> Result<Vec<AwaTism>> {
     input.trim();
     let (begin, rest) = input.split_at_char(b'<');
     if !begin.is_empty() {
@@ -91,7 +135,16 @@ pub fn include(mut input: Spanned<&[u8]>, macros: &MacroTable) -> Result<Vec<Awa
         span: span.clone(),
         inner: e,
     })?);
-    file(Spanned { item: path, span }, macros)
+    let path = if path.is_relative() && !path.exists() {
+        search
+            .iter()
+            .map(|root| root.join(path))
+            .find(|path| path.exists())
+            .unwrap_or_else(|| path.to_path_buf())
+    } else {
+        path.to_path_buf()
+    };
+    file(Spanned { item: &path, span }, macros, options)
 }
 
 impl Default for MacroTable {
@@ -103,3 +156,82 @@ impl Default for MacroTable {
         MacroTable(result)
     }
 }
+impl MacroTable {
+    /// Default macros without the ones accessing the filesystem,
+    /// `!include` reports an error instead since there is no file to resolve paths against.
+    #[inline]
+    pub fn in_memory() -> Self {
+        let mut table = Self::default();
+        table.register("include", Box::new(no_include));
+        table
+    }
+    /// Default macros where `!include` also searches the directories in `paths` in order,
+    /// after looking next to the file containing the include.
+    #[inline]
+    pub fn with_include_paths(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        // NOTE: the current directory changes while assembling included files
+        let paths = paths
+            .into_iter()
+            .map(|path| absolute(&path).unwrap_or(path))
+            .collect::<Vec<_>>();
+        let mut table = Self::default();
+        table.register(
+            "include",
+            Box::new(move |input, macros, options| include_from(input, macros, options, &paths)),
+        );
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_program;
+
+    use super::*;
+
+    #[test]
+    fn empty_str_blows_zero() {
+        let program = parse_program("!str \"\"").unwrap();
+        assert_eq!(program.instructions(), [AwaTism::Blow(0)]);
+        let program = parse_program("!str \"a\"").unwrap();
+        assert_ne!(program.instructions(), [AwaTism::Blow(0)]);
+    }
+
+    #[test]
+    fn include_from_search_path() {
+        let root = std::env::temp_dir().join(format!("awa-asm-include-{}", std::process::id()));
+        let (src, lib) = (root.join("src"), root.join("lib"));
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::create_dir_all(lib.join("std")).unwrap();
+        std::fs::write(lib.join("std/five.awasm"), "blo 5\n").unwrap();
+        let main = src.join("main.awasm");
+        std::fs::write(&main, "!include <std/five.awasm>\ntrm\n").unwrap();
+
+        let span = crate::Span::new("test".into(), 1, 0, 0);
+        let options = ParserOptions::default();
+        let macros = MacroTable::with_include_paths([lib]);
+        let program = file(
+            Spanned {
+                item: &main,
+                span: span.clone(),
+            },
+            &macros,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(program, [AwaTism::Blow(5), AwaTism::Terminate]);
+        // NOTE: without the search path the include is only looked up next to main.awasm
+        let result = file(
+            Spanned { item: &main, span },
+            &MacroTable::default(),
+            &options,
+        );
+        assert!(matches!(result, Err(Error::IOError { .. })));
+        std::fs::remove_dir_all(root).unwrap();
+
+        let error = parse_program("!include <std/five.awasm>").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("only available when assembling from a file"));
+    }
+}
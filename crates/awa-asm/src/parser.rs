@@ -1,15 +1,71 @@
 use core::str;
 use std::{
+    collections::HashSet,
     env::{current_dir, set_current_dir},
+    fmt::Display,
     fs::File,
-    io::Read,
+    io::{BufRead, Read},
+    panic::{catch_unwind, AssertUnwindSafe},
     path::Path,
     rc::Rc,
 };
 
 use awa_core::{u5, AwaTism};
+use num_traits::{cast, Bounded, Num, NumCast};
 
-use crate::{Error, MacroTable, Result, Spanned};
+use crate::{Error, MacroTable, ParserOptions, Result, Span, Spanned};
+
+/// Parse an integer with optional sign, accepting `0x`, `0b` and `0o` prefixes besides plain decimal.
+#[inline]
+pub fn parse_int<T: Num>(arg: &Spanned<&[u8]>) -> Result<T>
+where
+    <T as Num>::FromStrRadixErr: Display,
+{
+    let text = str::from_utf8(arg.item).map_err(|e| Error::EncodingError {
+        span: arg.span.clone(),
+        inner: e,
+    })?;
+    let (sign, digits) = match text.as_bytes().first() {
+        Some(b'-') => ("-", &text[1..]),
+        Some(b'+') => ("", &text[1..]),
+        _ => ("", text),
+    };
+    let (radix, digits) = match digits.get(..2) {
+        Some("0x" | "0X") => (16, &digits[2..]),
+        Some("0b" | "0B") => (2, &digits[2..]),
+        Some("0o" | "0O") => (8, &digits[2..]),
+        _ => (10, digits),
+    };
+    if digits.starts_with(['+', '-']) {
+        return Err(Error::ParseError {
+            span: arg.span.clone(),
+            msg: "invalid digit found in string".to_string(),
+        });
+    }
+    T::from_str_radix(&format!("{}{}", sign, digits), radix).map_err(|e| Error::ParseError {
+        span: arg.span.clone(),
+        msg: e.to_string(),
+    })
+}
+
+/// Parse the argument of the instruction `ident`, reporting values outside of the range of `T` with the valid range.
+#[inline]
+pub fn parse_operand<T: Bounded + NumCast + Display>(
+    ident: &str,
+    arg: &Spanned<&[u8]>,
+) -> Result<T> {
+    let value = parse_int::<i64>(arg)?;
+    cast(value).ok_or_else(|| Error::ParseError {
+        span: arg.span.clone(),
+        msg: format!(
+            "`{}` argument {} out of range {}..={}",
+            ident,
+            value,
+            T::min_value(),
+            T::max_value()
+        ),
+    })
+}
 
 #[inline]
 pub fn awatism(line: Spanned<&[u8]>) -> Result<AwaTism> {
@@ -26,19 +82,19 @@ pub fn awatism(line: Spanned<&[u8]>) -> Result<AwaTism> {
         "red" => AwaTism::Read,
         "r3d" => AwaTism::ReadNum,
         "trm" => AwaTism::Terminate,
-        "blo" => AwaTism::Blow(arg.parse::<i8>()?),
-        "sbm" => AwaTism::Submerge(arg.parse::<u5>()?),
+        "blo" => AwaTism::Blow(parse_operand::<i8>(ident, &arg)?),
+        "sbm" => AwaTism::Submerge(parse_operand::<u5>(ident, &arg)?),
         "pop" => AwaTism::Pop,
         "dpl" => AwaTism::Duplicate,
-        "srn" => AwaTism::Surround(arg.parse::<u5>()?),
+        "srn" => AwaTism::Surround(parse_operand::<u5>(ident, &arg)?),
         "mrg" => AwaTism::Merge,
         "4dd" => AwaTism::Add,
         "sub" => AwaTism::Subtract,
         "mul" => AwaTism::Multiply,
         "div" => AwaTism::Divide,
         "cnt" => AwaTism::Count,
-        "lbl" => AwaTism::Label(arg.parse::<u5>()?),
-        "jmp" => AwaTism::Jump(arg.parse::<u5>()?),
+        "lbl" => AwaTism::Label(parse_operand::<u5>(ident, &arg)?),
+        "jmp" => AwaTism::Jump(parse_operand::<u5>(ident, &arg)?),
         "eql" => AwaTism::EqualTo,
         "lss" => AwaTism::LessThan,
         "gr8" => AwaTism::GreaterThan,
@@ -50,10 +106,28 @@ pub fn awatism(line: Spanned<&[u8]>) -> Result<AwaTism> {
             })
         }
     };
+    let has_arg = matches!(
+        awatism,
+        AwaTism::Blow(_)
+            | AwaTism::Submerge(_)
+            | AwaTism::Surround(_)
+            | AwaTism::Label(_)
+            | AwaTism::Jump(_)
+    );
+    if !has_arg && !arg.is_empty() {
+        return Err(Error::SyntaxError {
+            span: arg.span,
+            msg: format!("{} takes no argument", ident),
+        });
+    }
     Ok(awatism)
 }
 #[inline]
-pub fn _macro(line: Spanned<&[u8]>, macros: &MacroTable) -> Result<Vec<AwaTism>> {
+pub fn _macro(
+    line: Spanned<&[u8]>,
+    macros: &MacroTable,
+    options: &ParserOptions,
+) -> Result<Vec<AwaTism>> {
     let (_exclaim, rest) = line.split_at(1);
     let (name, mut rest) = rest.split_at_whitespace();
     let ident = str::from_utf8(name.item).map_err(|e| Error::EncodingError {
@@ -61,42 +135,232 @@ pub fn _macro(line: Spanned<&[u8]>, macros: &MacroTable) -> Result<Vec<AwaTism>>
         inner: e,
     })?;
     rest.trim();
-    macros
-        .get(ident)
-        .map(|f| f(rest, macros))
-        .transpose()?
-        .ok_or_else(|| Error::UnknownIdentifier {
+    let Some(f) = macros.get(ident) else {
+        return Err(Error::UnknownIdentifier {
             span: name.span,
             identifier: format!("!{}", ident),
+        });
+    };
+    if !options.catch_panics {
+        return f(rest, macros, options);
+    }
+    // NOTE: macros only receive shared references, so a panic can't leave any of them half modified
+    catch_unwind(AssertUnwindSafe(|| f(rest, macros, options))).unwrap_or_else(|payload| {
+        let reason = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown reason");
+        Err(Error::SyntaxError {
+            span: name.span,
+            msg: format!("macro !{} panicked: {}", ident, reason),
         })
+    })
 }
+/// Separates multiple instructions on a single line.
+pub const SEPARATOR: u8 = b'|';
+
+/// Parse all instructions in a line, instructions can be separated by [`SEPARATOR`].
+/// A comment starting with [`ParserOptions::comment`] will extend to the end of the line.
 #[inline]
 pub fn push_line(
     buffer: &mut Vec<AwaTism>,
-    mut line: Spanned<&[u8]>,
+    line: Spanned<&[u8]>,
     macros: &MacroTable,
+    options: &ParserOptions,
 ) -> Result<()> {
-    line.trim_start();
-    match line.first() {
-        Some(b'!') => buffer.append(&mut _macro(line, macros)?),
-        Some(b';') | None => (),
-        Some(_) => buffer.push(awatism(line)?),
+    let (line, _comment) = line.split_at_unquoted(options.comment);
+    let (mut current, mut rest) = line.split_at_unquoted(SEPARATOR);
+    loop {
+        current.trim();
+        match current.first() {
+            Some(b'!') => buffer.append(&mut _macro(current, macros, options)?),
+            None => (),
+            Some(_) => buffer.push(awatism(current)?),
+        }
+        if rest.is_empty() {
+            return Ok(());
+        }
+        (current, rest) = rest.split_at_unquoted(SEPARATOR);
     }
-    Ok(())
 }
+/// Check if a line ends inside of a double-quoted string, meaning the string continues on the next line.
 #[inline]
-pub fn lines(file: Rc<str>, src: &[u8], macros: &MacroTable) -> Result<Vec<AwaTism>> {
-    let mut buffer = Vec::new();
-    for (i, line) in src.split(|c| *c == b'\n').enumerate() {
-        push_line(
+pub fn continues_string(line: &[u8], options: &ParserOptions) -> bool {
+    let mut quote = None;
+    for char in line {
+        match quote {
+            Some(q) if *char == q => quote = None,
+            Some(_) => (),
+            None if *char == options.comment => break,
+            None if matches!(char, b'\'' | b'"') => quote = Some(*char),
+            None => (),
+        }
+    }
+    quote == Some(b'"')
+}
+/// Symbols set with `!define NAME` and the `!if NAME`/`!else`/`!endif` blocks currently open in a file.
+/// Lines inside of a block whose condition does not hold are skipped entirely.
+///
+/// These directives have to be on a line of their own and only apply to the file they appear in.
+#[derive(Debug, Clone, Default)]
+pub struct Conditions {
+    symbols: HashSet<String>,
+    // NOTE: span of the `!if`, whether its symbol was defined and whether its `!else` was reached
+    blocks: Vec<(Span, bool, bool)>,
+}
+impl Conditions {
+    #[inline(always)]
+    pub fn is_defined(&self, symbol: &str) -> bool {
+        self.symbols.contains(symbol)
+    }
+    /// Returns `true` when lines at the current position should be assembled.
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        self.blocks
+            .iter()
+            .all(|(_, defined, otherwise)| defined != otherwise)
+    }
+    /// Handle `line` if it is a conditional directive, returns `false` when it is not one.
+    pub fn directive(&mut self, line: &Spanned<&[u8]>, options: &ParserOptions) -> Result<bool> {
+        let (mut line, _comment) = line.split_at_unquoted(options.comment);
+        line.trim();
+        if line.first() != Some(b'!') {
+            return Ok(false);
+        }
+        let (name, mut rest) = line.split_at(1).1.split_at_whitespace();
+        rest.trim();
+        let takes_symbol = match name.item {
+            b"define" | b"if" => true,
+            b"else" | b"endif" => false,
+            _ => return Ok(false),
+        };
+        let symbol = if takes_symbol {
+            if rest.is_empty() || rest.item.iter().any(u8::is_ascii_whitespace) {
+                return Err(Error::SyntaxError {
+                    span: rest.span,
+                    msg: format!("`!{}` expects a single symbol", name.parse::<String>()?),
+                });
+            }
+            rest.parse::<String>()?
+        } else if !rest.is_empty() {
+            return Err(Error::SyntaxError {
+                span: rest.span,
+                msg: "extra content at end of line".to_string(),
+            });
+        } else {
+            String::new()
+        };
+        match name.item {
+            b"define" => {
+                if self.is_active() {
+                    self.symbols.insert(symbol);
+                }
+            }
+            b"if" => {
+                let defined = self.is_defined(&symbol);
+                self.blocks.push((line.span, defined, false));
+            }
+            b"else" => match self.blocks.last_mut() {
+                Some((_, _, otherwise @ false)) => *otherwise = true,
+                Some(_) => {
+                    return Err(Error::SyntaxError {
+                        span: line.span,
+                        msg: "duplicate `!else`".to_string(),
+                    })
+                }
+                None => {
+                    return Err(Error::SyntaxError {
+                        span: line.span,
+                        msg: "`!else` without `!if`".to_string(),
+                    })
+                }
+            },
+            _ => {
+                if self.blocks.pop().is_none() {
+                    return Err(Error::SyntaxError {
+                        span: line.span,
+                        msg: "`!endif` without `!if`".to_string(),
+                    });
+                }
+            }
+        }
+        Ok(true)
+    }
+    /// Report the innermost `!if` that was never closed.
+    #[inline]
+    pub fn finish(self) -> Result<()> {
+        match self.blocks.into_iter().last() {
+            Some((span, ..)) => Err(Error::SyntaxError {
+                span,
+                msg: "`!if` without `!endif`".to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+    /// Same as [`push_line`], but handles conditional directives and skips inactive lines.
+    #[inline]
+    pub fn push_line(
+        &mut self,
+        buffer: &mut Vec<AwaTism>,
+        line: Spanned<&[u8]>,
+        macros: &MacroTable,
+        options: &ParserOptions,
+    ) -> Result<()> {
+        if self.directive(&line, options)? || !self.is_active() {
+            return Ok(());
+        }
+        push_line(buffer, line, macros, options)
+    }
+}
+/// Parse all lines in `src`, reading one line at a time.
+/// A line ending inside of a double-quoted string is joined with the following lines until the string is closed.
+#[inline]
+pub fn lines(
+    file: Rc<str>,
+    mut src: impl BufRead,
+    macros: &MacroTable,
+    options: &ParserOptions,
+) -> Result<Vec<AwaTism>> {
+    let (mut buffer, mut line, mut number) = (Vec::new(), Vec::new(), 1);
+    let mut conditions = Conditions::default();
+    let mut read_line = |line: &mut Vec<u8>, number| {
+        src.read_until(b'\n', line).map_err(|e| Error::IOError {
+            span: Span::new(file.clone(), number, 0, 0),
+            inner: e,
+        })
+    };
+    loop {
+        line.clear();
+        if read_line(&mut line, number)? == 0 {
+            break;
+        }
+        let mut count = 1;
+        while line.last() == Some(&b'\n') && continues_string(&line, options) {
+            if read_line(&mut line, number + count)? == 0 {
+                break;
+            }
+            count += 1;
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        conditions.push_line(
             &mut buffer,
-            Spanned::from_line(file.clone(), i + 1, line),
+            Spanned::from_line(file.clone(), number, &line),
             macros,
+            options,
         )?;
+        number += count;
     }
+    conditions.finish()?;
     Ok(buffer)
 }
-pub fn file(file: Spanned<&Path>, macros: &MacroTable) -> Result<Vec<AwaTism>> {
+pub fn file(
+    file: Spanned<&Path>,
+    macros: &MacroTable,
+    options: &ParserOptions,
+) -> Result<Vec<AwaTism>> {
     let mut handle = File::open(file.item).map_err(|e| Error::IOError {
         span: file.span.clone(),
         inner: e,
@@ -116,10 +380,149 @@ pub fn file(file: Spanned<&Path>, macros: &MacroTable) -> Result<Vec<AwaTism>> {
         span: file.span.clone(),
         inner: e,
     })?;
-    let result = lines(file.item.to_str().unwrap().into(), &buffer, macros);
+    let result = lines(
+        file.item.to_str().unwrap().into(),
+        &buffer[..],
+        macros,
+        options,
+    );
     set_current_dir(cwd).map_err(|e| Error::IOError {
         span: file.span,
         inner: e,
     })?;
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use awa_core::AwaSCII;
+
+    use crate::{assemble_reader, load_program, parse_program, MacroTable, ParserOptions};
+
+    use super::*;
+
+    #[test]
+    fn conditional_assembly() {
+        let body = "!if DEBUG\nblo 1\npr1\n!else\nblo 2\n!endif\ntrm";
+        let without = parse_program(body).unwrap();
+        assert_eq!(
+            without.instructions(),
+            [AwaTism::Blow(2), AwaTism::Terminate]
+        );
+        let with = parse_program(&format!("!define DEBUG\n{}", body)).unwrap();
+        assert_eq!(
+            with.instructions(),
+            [AwaTism::Blow(1), AwaTism::PrintNum, AwaTism::Terminate]
+        );
+    }
+
+    #[test]
+    fn unbalanced_conditions() {
+        for src in ["!if DEBUG\ntrm", "!else\ntrm", "trm\n!endif"] {
+            assert!(matches!(parse_program(src), Err(Error::SyntaxError { .. })));
+        }
+    }
+
+    #[test]
+    fn multi_instruction_lines() {
+        let one_per_line = parse_program("blo 1\nblo 2\n4dd\npr1").unwrap();
+        let piped = parse_program("blo 1 | blo 2|4dd |pr1").unwrap();
+        assert_eq!(piped.instructions(), one_per_line.instructions());
+
+        let quoted = parse_program("!str \"a b\" | prn").unwrap();
+        let separate = parse_program("!str \"a b\"\nprn").unwrap();
+        assert_eq!(quoted.instructions(), separate.instructions());
+        // NOTE: `|` is not AwaSCII, but the string must not be split at it
+        let Err(Error::ParseError { span, msg }) = parse_program("!str \"a|b\" | prn") else {
+            panic!("expected a parse error");
+        };
+        assert_eq!((span.start, msg.as_str()), (7, "invalid AwaSCII"));
+
+        let Err(Error::UnknownIdentifier { span, .. }) = parse_program("blo 1 | nope") else {
+            panic!("expected an unknown identifier");
+        };
+        assert_eq!((span.line, span.start, span.end), (1, 8, 12));
+    }
+
+    #[test]
+    fn numeric_operand_bases() {
+        let program = parse_program("sbm 0b101\nlbl 0x1f\nsrn 0o7\nblo -0x80\nblo +12").unwrap();
+        assert_eq!(
+            program.instructions(),
+            [
+                AwaTism::Submerge(u5::try_from(5u8).unwrap()),
+                AwaTism::Label(u5::try_from(31u8).unwrap()),
+                AwaTism::Surround(u5::try_from(7u8).unwrap()),
+                AwaTism::Blow(-128),
+                AwaTism::Blow(12),
+            ]
+        );
+        let message = |src| match parse_program(src) {
+            Err(Error::ParseError { msg, .. }) => msg,
+            result => panic!("expected a parse error, got {:?}", result),
+        };
+        assert_eq!(message("srn 0x20"), "`srn` argument 32 out of range 0..=31");
+        assert_eq!(
+            message("blo 0xff"),
+            "`blo` argument 255 out of range -128..=127"
+        );
+        assert_eq!(message("blo 0x-5"), "invalid digit found in string");
+        assert_eq!(message("blo 0x"), "cannot parse integer from empty string");
+    }
+
+    #[test]
+    fn alternate_comment_character() {
+        let options = ParserOptions {
+            comment: b'#',
+            ..Default::default()
+        };
+        let macros = MacroTable::in_memory();
+        let load = |src: &str| load_program(Path::new("<str>"), src.as_bytes(), &macros, &options);
+        let program = load("blo 1 # one\n# nothing here\nblo 2#two\n").unwrap();
+        assert_eq!(
+            program.instructions(),
+            parse_program("blo 1 ; one\n; nothing here\nblo 2;two\n")
+                .unwrap()
+                .instructions()
+        );
+        // NOTE: `;` is no longer special
+        assert!(load("blo 1 ; one").is_err());
+        assert!(parse_program("blo 1 # one").is_err());
+    }
+
+    #[test]
+    fn two_line_string() {
+        let (file, macros, options) = (
+            Path::new("<test>"),
+            MacroTable::default(),
+            ParserOptions::default(),
+        );
+        let src = b"!str \"a\nb\"\nprn";
+        let blow = |char| AwaTism::Blow(*AwaSCII::from_ascii(char).unwrap() as i8);
+        // NOTE: the last character is blown first, so the string reads front to back
+        let expected = [
+            blow(b'b'),
+            blow(b'\n'),
+            blow(b'a'),
+            AwaTism::Surround(u5::try_from(3).unwrap()),
+            AwaTism::Print,
+        ];
+        let loaded = load_program(file, src, &macros, &options).unwrap();
+        assert_eq!(loaded.instructions(), expected);
+        let read = assemble_reader(file, &src[..], &macros, &options).unwrap();
+        assert_eq!(read.instructions(), expected);
+        // NOTE: the joined lines still count for the line numbers after them
+        let src = b"!str \"a\nb\"\nxyz";
+        for result in [
+            load_program(file, src, &macros, &options),
+            assemble_reader(file, &src[..], &macros, &options),
+        ] {
+            let Err(Error::UnknownIdentifier { span, .. }) = result else {
+                panic!("expected unknown identifier: {result:?}");
+            };
+            assert_eq!(span.line, 3);
+        }
+    }
+}
@@ -1,13 +1,14 @@
+#[cfg(feature = "std")]
 use bitbuffer::{BitError, BitRead, BitReadStream, BitWrite, BitWriteStream, Endianness};
-use num_traits::{
-    Bounded, ConstOne, ConstZero, FromPrimitive, Num, NumCast, One, ToPrimitive, Unsigned, Zero,
-};
-use std::{
+use core::{
     fmt::Display,
     num::IntErrorKind,
     ops::{Add, Deref, Div, Mul, Rem, Sub},
     str::FromStr,
 };
+use num_traits::{
+    Bounded, ConstOne, ConstZero, FromPrimitive, Num, NumCast, One, ToPrimitive, Unsigned, Zero,
+};
 
 use crate::Error;
 
@@ -19,12 +20,32 @@ pub struct u5(u8);
 impl u5 {
     // SAFETY: 2 is a valid 5 bit number
     pub const TWO: u5 = unsafe { u5(2) };
+    // SAFETY: 31 is a valid 5 bit number
+    pub const MAX: u5 = unsafe { u5(0b11111) };
     /// # Safety
     /// `value` has to be a valid 5 bit number
     #[inline(always)]
     pub const unsafe fn new_unchecked(value: u8) -> Self {
         u5(value)
     }
+    /// Add without wrapping, results above [`u5::MAX`] are clamped to it.
+    #[inline]
+    pub const fn saturating_add(self, rhs: Self) -> Self {
+        // NOTE: the sum of two 5 bit numbers always fits into a u8
+        let result = self.0 + rhs.0;
+        if result > Self::MAX.0 {
+            Self::MAX
+        } else {
+            // SAFETY: result fits into 5 bits here
+            unsafe { u5(result) }
+        }
+    }
+    /// Subtract without wrapping, results below zero are clamped to zero.
+    #[inline]
+    pub const fn saturating_sub(self, rhs: Self) -> Self {
+        // SAFETY: the difference is never bigger than self
+        unsafe { u5(self.0.saturating_sub(rhs.0)) }
+    }
 }
 impl TryFrom<u8> for u5 {
     type Error = Error;
@@ -51,6 +72,7 @@ impl Deref for u5 {
         &self.0
     }
 }
+#[cfg(feature = "std")]
 impl<'a, E: Endianness> BitRead<'a, E> for u5 {
     #[inline]
     fn read(stream: &mut BitReadStream<'a, E>) -> Result<Self, BitError> {
@@ -61,6 +83,7 @@ impl<'a, E: Endianness> BitRead<'a, E> for u5 {
         Some(5)
     }
 }
+#[cfg(feature = "std")]
 impl<E: Endianness> BitWrite<E> for u5 {
     #[inline(always)]
     fn write(&self, stream: &mut BitWriteStream<E>) -> Result<(), BitError> {
@@ -69,7 +92,7 @@ impl<E: Endianness> BitWrite<E> for u5 {
 }
 impl Display for u5 {
     #[inline(always)]
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         self.0.fmt(f)
     }
 }
@@ -205,3 +228,21 @@ impl NumCast for u5 {
         Some(unsafe { u5(num) })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saturating_at_both_ends() {
+        let n = |value: u8| u5::try_from(value).unwrap();
+        assert_eq!(n(30).saturating_add(n(1)), u5::MAX);
+        assert_eq!(n(30).saturating_add(n(2)), u5::MAX);
+        assert_eq!(u5::MAX.saturating_add(u5::MAX), u5::MAX);
+        assert_eq!(n(3).saturating_add(n(4)), n(7));
+        assert_eq!(n(1).saturating_sub(n(1)), n(0));
+        assert_eq!(n(1).saturating_sub(n(2)), n(0));
+        assert_eq!(n(0).saturating_sub(u5::MAX), n(0));
+        assert_eq!(u5::MAX.saturating_sub(u5::TWO), n(29));
+    }
+}
@@ -1,10 +1,33 @@
-use std::fmt::Display;
+use core::fmt::Display;
 
-use num_traits::{cast, Num, NumCast};
+use num_traits::{
+    cast, Bounded, CheckedAdd, CheckedDiv, CheckedMul, CheckedRem, CheckedSub, Num, NumCast,
+    SaturatingAdd, SaturatingMul, SaturatingSub, WrappingAdd, WrappingMul, WrappingSub,
+};
 
 use crate::AwaSCII;
 
-pub trait Value = Num + NumCast + PartialOrd + Copy + Display;
+/// Number type stored in bubbles.
+/// Values have to be totally ordered, so comparisons always have a defined result (this rules out floats).
+/// Checked, wrapping and saturating arithmetic is needed for the interpreter's arithmetic modes,
+/// every primitive integer type qualifies.
+pub trait Value = Num
+    + NumCast
+    + Ord
+    + Copy
+    + Display
+    + Bounded
+    + CheckedAdd
+    + CheckedSub
+    + CheckedMul
+    + CheckedDiv
+    + CheckedRem
+    + WrappingAdd
+    + WrappingSub
+    + WrappingMul
+    + SaturatingAdd
+    + SaturatingSub
+    + SaturatingMul;
 
 macro_rules! impl_copied {
     ($single:ident, $batched:ident) => {
@@ -34,60 +57,108 @@ macro_rules! impl_buffered {
     };
 }
 
+/// Part of a bubble visited by [`Abyss::consume_nested`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Nested<T> {
+    /// Start of a double bubble.
+    Begin,
+    Value(T),
+    /// End of the most recently started double bubble.
+    End,
+}
+
 /// Minimal functionallity for an Abyss data structure that is required to run an AWA program.
 pub trait Abyss {
     type Value: Value;
     fn is_empty(&self) -> bool;
+    /// Number of bubbles on the abyss, not counting bubbles inside of double bubbles.
+    fn depth(&self) -> usize;
     /// Push AwaSCII string as a double bubble, empty string will push a single bubble with value zero.
     /// Returns `None` if the abyss is full.
+    #[must_use]
     fn blow_awascii<B>(&mut self, awascii: B) -> Option<()>
     where
         B: AsRef<[AwaSCII]>;
     /// Push number as a new bubble.
     /// Returns `None` if the abyss is full.
+    #[must_use]
     fn blow(&mut self, value: Self::Value) -> Option<()>;
-    /// Move top bubble down, pass `0` to move to bottom.
+    /// Move top bubble down below the next `distance` bubbles, pass `0` to move to bottom.
+    /// Distances reaching past the bottom also move it to the bottom.
     /// Returns `None` if there is no top bubble.
+    #[must_use]
     fn submerge(&mut self, distance: usize) -> Option<()>;
     /// Remove the top bubble.
     /// Returns `None` if there is no top bubble.
+    #[must_use]
     fn pop(&mut self) -> Option<()>;
+    /// Remove the top bubble and return its value.
+    /// Returns `None` and leaves the abyss unchanged if there is no top bubble or it is a double bubble.
+    #[must_use]
+    fn pop_value(&mut self) -> Option<Self::Value>;
     /// Remove the top bubble and in case of a double bubble will also remove all inner bubbles
     /// Returns `None` if there is no top bubble.
+    #[must_use]
     fn double_pop(&mut self) -> Option<()>;
     /// Duplicates the top bubble.
     /// Returns `None` if there is no top bubble.
+    #[must_use]
     fn duplicate(&mut self) -> Option<()>;
     /// Create a double bubble from the top bubbles.
     /// Returns `None` if there not enough bubbles.
+    #[must_use]
     fn surround(&mut self, count: usize) -> Option<()>;
     /// Merges the top two bubbles into a single double bubble.
     /// Returns `None` if there are less then two bubbles on top.
+    #[must_use]
     fn merge(&mut self) -> Option<()>;
     /// Pushes the size of the top bubble on top (single bubble will push zero).
-    /// Return `None` if there is no top bubble.
+    /// Return `None` if there is no top bubble or its size does not fit into [`Abyss::Value`].
+    /// With the count cached inside of double bubbles, building a bubble that large will already overflow.
+    #[must_use]
     fn count(&mut self) -> Option<()>;
     /// Map the top two bubbles into one bubble.
+    /// The top bubble is always passed as the first argument to `op`.
+    ///
+    /// - single and single: a single bubble holding `op(top, second)`.
+    /// - single and double (either order): the single bubble is applied to every bubble inside the double.
+    /// - double and double: bubbles at the same position are combined,
+    ///   bubbles without partner in the larger one are dropped.
+    ///
+    /// Nested double bubbles are handled by applying the same rules recursively.
     /// Returns `None` if there are less then two bubbles on top.
+    #[must_use]
     fn combine_single<F>(&mut self, op: F) -> Option<()>
     where
         F: Fn(Self::Value, Self::Value) -> Self::Value;
     /// Map the top two bubbles into one bubble, creates a double bubble for each single bubble.
+    /// Operands are paired up following the same rules as [`Abyss::combine_single`],
+    /// each pair of single bubbles is replaced by a double bubble holding `op1(top, second)`
+    /// on top of `op2(top, second)`, so `div` results in the quotient on top of the remainder.
     /// Returns `None` if there are less then two bubbles on top.
+    #[must_use]
     fn combine_double<F1, F2>(&mut self, op1: F1, op2: F2) -> Option<()>
     where
         F1: Fn(Self::Value, Self::Value) -> Self::Value,
         F2: Fn(Self::Value, Self::Value) -> Self::Value;
-    /// Tests the top two bubbles and removes them, returning the result of the test.
+    /// Tests the top two bubbles and leaves them on the abyss, returning the result of the test.
     /// Returns `None` if there are less then two bubbles on top.
+    #[must_use]
     fn test<F>(&mut self, test: F) -> Option<bool>
     where
         F: Fn(&Self::Value, &Self::Value) -> bool;
     /// Iterate over all values in the top bubble and removing it after, returning a possible error during iteration.
-    /// Returns `None` if there is no top bubble.
+    /// Values are visited starting from the front, nested double bubbles are flattened in place,
+    /// so `[[0, 1], 2, [3]]` yields `0, 1, 2, 3` (matching the AWA5.0 reference `prn`/`pr1`).
+    /// Returns `None` if there is no top bubble, the top bubble is left unchanged when `fun` fails.
     fn consume<F, E>(&mut self, fun: F) -> Result<Option<()>, E>
     where
         F: FnMut(Self::Value) -> Result<(), E>;
+    /// Same as [`Abyss::consume`], but keeps the structure of double bubbles,
+    /// so `[[0, 1], 2]` yields `Begin, Begin, 0, 1, End, 2, End`.
+    fn consume_nested<F, E>(&mut self, fun: F) -> Result<Option<()>, E>
+    where
+        F: FnMut(Nested<Self::Value>) -> Result<(), E>;
 
     impl_buffered!(blow, blow_many, values: Self::Value);
     impl_buffered!(submerge, submerge_many, distances: usize);
@@ -96,16 +167,79 @@ pub trait Abyss {
     impl_copied!(duplicate, duplicate_many);
     impl_buffered!(surround, surround_many, counts: usize);
     impl_copied!(merge, merge_many);
+    /// Size of the top bubble as [`Abyss::count`] would push it, without modifying the abyss.
+    /// Returns `None` if there is no top bubble or the size can't be looked up without modifying the abyss.
+    #[inline]
+    fn top_size(&self) -> Option<usize> {
+        None
+    }
+    /// Iterate over all values in the top bubble in the same order as [`Abyss::consume`], without removing it.
+    /// Returns `None` if there is no top bubble or the values can't be visited without modifying the abyss.
+    #[inline]
+    #[must_use]
+    fn peek<F>(&self, _fun: F) -> Option<()>
+    where
+        F: FnMut(Self::Value),
+    {
+        None
+    }
+    /// Reverse the order of the bubbles inside of the top double bubble.
+    /// Returns `None` if there is no top bubble or it is a single bubble.
+    #[inline]
+    #[must_use]
+    fn reverse_top(&mut self) -> Option<()> {
+        self.count()?;
+        // SAFETY: unwrap: count pushes a single bubble
+        let count = self.pop_value().unwrap();
+        let count = cast::<_, usize>(count).filter(|count| *count != 0)?;
+        self.pop()?;
+        for distance in (1..count).rev() {
+            self.submerge(distance)?;
+        }
+        self.surround(count)
+    }
+    /// Merge the top `count` bubbles into a single double bubble,
+    /// this has the same result as merging `count - 1` times.
+    /// Returns `None` if there are less then `count` bubbles.
+    #[inline]
+    #[must_use]
+    fn merge_top_n(&mut self, count: usize) -> Option<()> {
+        self.merge_many(count.saturating_sub(1))
+    }
     /// Push new double bubble with the given elements.
     /// The last element will end up as the front.
-    /// Will return `None` when the abyss is full or the double is too big.
+    /// Unlike [`AwaTism::Surround`](crate::AwaTism::Surround) this is not limited to 31 elements.
+    /// Will return `None` when the abyss is full.
     #[inline]
+    #[must_use]
     fn blow_double<B>(&mut self, inner: B) -> Option<()>
     where
         B: AsRef<[Self::Value]>,
     {
-        let count = cast(inner.as_ref().len())?;
+        let count = inner.as_ref().len();
         self.blow_many(inner)?;
         self.surround(count)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::cmp::Ordering;
+
+    use super::*;
+
+    #[test]
+    fn integers_are_totally_ordered_values() {
+        fn ordered<T: Value>() -> bool {
+            let (min, zero, max) = (T::min_value(), T::zero(), T::max_value());
+            min.cmp(&max) == Ordering::Less
+                && max.cmp(&min) == Ordering::Greater
+                && zero.cmp(&zero) == Ordering::Equal
+                && zero.clamp(min, max) == zero
+        }
+        assert!(ordered::<i8>() && ordered::<i16>() && ordered::<i32>() && ordered::<i64>());
+        assert!(ordered::<i128>() && ordered::<isize>());
+        assert!(ordered::<u8>() && ordered::<u16>() && ordered::<u32>() && ordered::<u64>());
+        assert!(ordered::<u128>() && ordered::<usize>());
+    }
+}
@@ -0,0 +1,603 @@
+use alloc::{vec, vec::Vec};
+use core::fmt::Display;
+
+use crate::{u5, AwaTism, Program};
+
+/// Represents a problem found by static analysis of a [`Program`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Diagnostic {
+    /// Execution can continue past the last instruction without reaching [`AwaTism::Terminate`].
+    MissingTerminate,
+    /// No [`AwaTism::Terminate`] can be reached and execution can not continue past the last instruction,
+    /// so the program only stops on errors.
+    NeverTerminates,
+    /// Comparison at `pc` is the last instruction, so there is no instruction to skip.
+    SkipPastEnd { pc: usize },
+    /// Comparison at `pc` is the second to last instruction, so failing it ends the program.
+    SkipToEnd { pc: usize },
+    /// Comparison at `pc` skips a [`AwaTism::Label`], which does nothing either way.
+    SkipsLabel { pc: usize },
+    /// Instruction at `pc` will always fail, because there are never enough bubbles.
+    StackUnderflow {
+        pc: usize,
+        required: usize,
+        available: usize,
+    },
+    /// Constant arithmetic at `pc` produces a value that does not fit into a `bits` wide signed integer.
+    IntOverflow { pc: usize, bits: u32 },
+    /// Label at `pc` is not the target of any [`AwaTism::Jump`], it can still be reached by falling through.
+    UnusedLabel { pc: usize, label: u5 },
+}
+impl Diagnostic {
+    /// Location of the instruction causing the problem, `None` for program-level problems.
+    #[inline]
+    pub const fn pc(&self) -> Option<usize> {
+        match self {
+            Self::MissingTerminate | Self::NeverTerminates => None,
+            Self::SkipPastEnd { pc } => Some(*pc),
+            Self::SkipToEnd { pc } => Some(*pc),
+            Self::SkipsLabel { pc } => Some(*pc),
+            Self::StackUnderflow { pc, .. } => Some(*pc),
+            Self::IntOverflow { pc, .. } => Some(*pc),
+            Self::UnusedLabel { pc, .. } => Some(*pc),
+        }
+    }
+}
+impl Display for Diagnostic {
+    #[inline]
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::MissingTerminate => f.write_str("program can end without terminate"),
+            Self::NeverTerminates => f.write_str("program may never terminate"),
+            Self::SkipPastEnd { .. } => {
+                f.write_str("comparison at the end of the program has nothing to skip")
+            }
+            Self::SkipToEnd { .. } => {
+                f.write_str("comparison skips the last instruction and ends the program")
+            }
+            Self::SkipsLabel { .. } => f.write_str("comparison skips a label, so it has no effect"),
+            Self::StackUnderflow {
+                required,
+                available,
+                ..
+            } => write!(
+                f,
+                "needs {} bubbles, but at most {} are available",
+                required, available
+            ),
+            Self::IntOverflow { bits, .. } => {
+                write!(f, "result does not fit into {}-bit integers", bits)
+            }
+            Self::UnusedLabel { label, .. } => write!(f, "label {} is never jumped to", label),
+        }
+    }
+}
+
+/// Number of bubbles the instruction needs to be on the abyss.
+#[inline]
+fn required_bubbles(awatism: &AwaTism) -> usize {
+    match awatism {
+        AwaTism::NoOp
+        | AwaTism::Read
+        | AwaTism::ReadNum
+        | AwaTism::Terminate
+        | AwaTism::Blow(_)
+        | AwaTism::Label(_)
+        | AwaTism::Jump(_) => 0,
+        AwaTism::Surround(count) => **count as usize,
+        AwaTism::Merge
+        | AwaTism::Add
+        | AwaTism::Subtract
+        | AwaTism::Multiply
+        | AwaTism::Divide
+        | AwaTism::EqualTo
+        | AwaTism::LessThan
+        | AwaTism::GreaterThan => 2,
+        _ => 1,
+    }
+}
+/// Upper bound of the abyss depth after executing the instruction, `None` when unknown.
+#[inline]
+fn max_depth_after(awatism: &AwaTism, depth: usize) -> Option<usize> {
+    match awatism {
+        AwaTism::NoOp | AwaTism::Submerge(_) | AwaTism::Label(_) => Some(depth),
+        // NOTE: comparisons leave both bubbles on the abyss
+        AwaTism::EqualTo | AwaTism::LessThan | AwaTism::GreaterThan => Some(depth),
+        AwaTism::Read
+        | AwaTism::ReadNum
+        | AwaTism::Blow(_)
+        | AwaTism::Duplicate
+        | AwaTism::Count => Some(depth + 1),
+        AwaTism::Print
+        | AwaTism::PrintNum
+        | AwaTism::DoublePop
+        | AwaTism::Merge
+        | AwaTism::Add
+        | AwaTism::Subtract
+        | AwaTism::Multiply
+        | AwaTism::Divide => Some(depth - 1),
+        AwaTism::Surround(count) if **count == 0 => Some(depth),
+        AwaTism::Surround(count) => Some(depth + 1 - **count as usize),
+        // NOTE: popping a double bubble releases all inner bubbles
+        AwaTism::Pop => None,
+        AwaTism::Terminate | AwaTism::Jump(_) => None,
+    }
+}
+
+impl Program {
+    /// Returns the locations that can be executed after the instruction at `pc`.
+    /// Locations past the last instruction mean that the program ends there.
+    /// Jumps to unknown labels have no successor.
+    #[inline]
+    pub fn successors(&self, pc: usize) -> [Option<usize>; 2] {
+        match self.get(pc) {
+            None | Some(AwaTism::Terminate) => [None, None],
+            Some(AwaTism::Jump(label)) => {
+                [self.labels()[**label as usize].map(|pc| pc.get()), None]
+            }
+            Some(AwaTism::EqualTo | AwaTism::LessThan | AwaTism::GreaterThan) => {
+                [Some(pc + 1), Some(pc + 2)]
+            }
+            Some(_) => [Some(pc + 1), None],
+        }
+    }
+    /// Returns the sorted locations where execution can start other than by falling through,
+    /// the first instruction and the targets of all defined labels.
+    /// These are the starts of the basic blocks, a label at the end of the program results in [`Program::len`].
+    pub fn entry_points(&self) -> Vec<usize> {
+        let mut result = vec![0];
+        result.extend(self.defined_labels().map(|(_, pc)| pc));
+        result.sort_unstable();
+        result.dedup();
+        result
+    }
+    /// Returns which instructions can be executed when starting at the first instruction.
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut result = vec![false; self.len()];
+        let mut stack = vec![0];
+        while let Some(pc) = stack.pop() {
+            let Some(visited) = result.get_mut(pc) else {
+                continue;
+            };
+            if *visited {
+                continue;
+            }
+            *visited = true;
+            stack.extend(self.successors(pc).into_iter().flatten());
+        }
+        result
+    }
+    /// Returns `true` when the program contains [`AwaTism::Read`] or [`AwaTism::ReadNum`].
+    #[inline]
+    pub fn reads_input(&self) -> bool {
+        self.iter()
+            .any(|awatism| matches!(awatism, AwaTism::Read | AwaTism::ReadNum))
+    }
+    /// Returns `true` when the program contains [`AwaTism::Print`] or [`AwaTism::PrintNum`].
+    #[inline]
+    pub fn writes_output(&self) -> bool {
+        self.iter()
+            .any(|awatism| matches!(awatism, AwaTism::Print | AwaTism::PrintNum))
+    }
+    /// Returns `true` when the program contains no I/O instructions,
+    /// so running it only depends on the initial abyss.
+    #[inline]
+    pub fn is_pure(&self) -> bool {
+        !self.iter().any(AwaTism::is_io)
+    }
+    /// Returns `true` when execution can continue past the last instruction.
+    pub fn can_fall_off_end(&self) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        self.reachable()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, reachable)| *reachable)
+            .any(|(pc, _)| {
+                self.successors(pc)
+                    .into_iter()
+                    .flatten()
+                    .any(|next| next >= self.len())
+            })
+    }
+    /// Returns `true` when a [`AwaTism::Terminate`] can be reached from the first instruction.
+    pub fn can_terminate(&self) -> bool {
+        self.iter()
+            .zip(self.reachable())
+            .any(|(awatism, reachable)| reachable && *awatism == AwaTism::Terminate)
+    }
+    /// Returns an upper bound of the abyss depth before each instruction, `None` when unknown.
+    /// This only follows straight-line code from the start of the program,
+    /// the bound is dropped at every label and after every jump or skip.
+    pub fn max_depths(&self) -> Vec<Option<usize>> {
+        let mut result = Vec::with_capacity(self.len());
+        let (mut depth, mut skip_targets) = (Some(0), vec![false; self.len() + 2]);
+        for (pc, awatism) in self.iter().enumerate() {
+            if matches!(awatism, AwaTism::Label(_)) || skip_targets[pc] {
+                depth = None;
+            }
+            result.push(depth);
+            if matches!(
+                awatism,
+                AwaTism::EqualTo | AwaTism::LessThan | AwaTism::GreaterThan
+            ) {
+                skip_targets[pc + 2] = true;
+            }
+            depth = depth.and_then(|depth| {
+                if depth < required_bubbles(awatism) {
+                    return None;
+                }
+                max_depth_after(awatism, depth)
+            });
+        }
+        result
+    }
+    /// Run static analysis and return all problems found.
+    pub fn check(&self) -> Vec<Diagnostic> {
+        let mut result = Vec::new();
+        if self.can_fall_off_end() {
+            result.push(Diagnostic::MissingTerminate);
+        } else if !self.can_terminate() {
+            result.push(Diagnostic::NeverTerminates);
+        }
+        for (pc, awatism) in self.iter().enumerate() {
+            if !matches!(
+                awatism,
+                AwaTism::EqualTo | AwaTism::LessThan | AwaTism::GreaterThan
+            ) {
+                continue;
+            }
+            match self.get(pc + 1) {
+                None => result.push(Diagnostic::SkipPastEnd { pc }),
+                Some(AwaTism::Label(_)) => result.push(Diagnostic::SkipsLabel { pc }),
+                // NOTE: skipping a jump is how conditional jumps are written
+                Some(_) if pc + 2 == self.len() => result.push(Diagnostic::SkipToEnd { pc }),
+                Some(_) => (),
+            }
+        }
+        for (pc, (awatism, depth)) in self.iter().zip(self.max_depths()).enumerate() {
+            let required = required_bubbles(awatism);
+            match depth {
+                Some(available) if available < required => {
+                    result.push(Diagnostic::StackUnderflow {
+                        pc,
+                        required,
+                        available,
+                    })
+                }
+                _ => (),
+            }
+        }
+        let mut jumped = [false; 32];
+        for awatism in self.iter() {
+            if let AwaTism::Jump(label) = awatism {
+                jumped[**label as usize] = true;
+            }
+        }
+        for (pc, awatism) in self.iter().enumerate() {
+            match awatism {
+                AwaTism::Label(label) if !jumped[**label as usize] => {
+                    result.push(Diagnostic::UnusedLabel { pc, label: *label })
+                }
+                _ => (),
+            }
+        }
+        result
+    }
+    /// Find literals and constant arithmetic that produce values outside of the range of a `bits` wide signed integer.
+    /// This only tracks values from [`AwaTism::Blow`] through straight-line code,
+    /// everything else is assumed to be unknown.
+    /// Widths of zero or more than 128 bits can't be checked and result in no diagnostics.
+    pub fn check_int_width(&self, bits: u32) -> Vec<Diagnostic> {
+        if !(1..=i128::BITS).contains(&bits) {
+            return Vec::new();
+        }
+        let max = i128::MAX >> (i128::BITS - bits);
+        let min = -max - 1;
+        let (mut result, mut skip_targets) = (Vec::new(), vec![false; self.len() + 2]);
+        // NOTE: only the top of the abyss is tracked, missing bubbles are unknown
+        let mut known: Vec<Option<i128>> = Vec::new();
+        for (pc, awatism) in self.iter().enumerate() {
+            if matches!(awatism, AwaTism::Label(_)) || skip_targets[pc] {
+                known.clear();
+            }
+            let op: fn(i128, i128) -> Option<i128> = match awatism {
+                AwaTism::NoOp | AwaTism::Label(_) => continue,
+                AwaTism::Blow(value) => {
+                    let value = *value as i128;
+                    if !(min..=max).contains(&value) {
+                        result.push(Diagnostic::IntOverflow { pc, bits });
+                    }
+                    known.push(Some(value));
+                    continue;
+                }
+                AwaTism::Duplicate => {
+                    known.push(known.last().copied().flatten());
+                    continue;
+                }
+                AwaTism::Add => i128::checked_add,
+                AwaTism::Subtract => i128::checked_sub,
+                AwaTism::Multiply => i128::checked_mul,
+                AwaTism::EqualTo | AwaTism::LessThan | AwaTism::GreaterThan => {
+                    skip_targets[pc + 2] = true;
+                    known.clear();
+                    continue;
+                }
+                _ => {
+                    known.clear();
+                    continue;
+                }
+            };
+            let (lhs, rhs) = (known.pop().flatten(), known.pop().flatten());
+            let value = match (lhs, rhs) {
+                (Some(lhs), Some(rhs)) => match op(lhs, rhs) {
+                    Some(value) if (min..=max).contains(&value) => Some(value),
+                    _ => {
+                        result.push(Diagnostic::IntOverflow { pc, bits });
+                        None
+                    }
+                },
+                _ => None,
+            };
+            known.push(value);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use num_traits::{ConstOne, ConstZero};
+
+    use super::*;
+
+    #[test]
+    fn leading_add_underflows() {
+        let program = Program::from_vec(vec![AwaTism::Add, AwaTism::Terminate]);
+        assert!(program.check().contains(&Diagnostic::StackUnderflow {
+            pc: 0,
+            required: 2,
+            available: 0,
+        }));
+    }
+
+    #[test]
+    fn comparison_keeps_bubbles() {
+        let program = Program::from_vec(vec![
+            AwaTism::Blow(2),
+            AwaTism::Blow(2),
+            AwaTism::EqualTo,
+            AwaTism::Add,
+            AwaTism::PrintNum,
+            AwaTism::Terminate,
+        ]);
+        assert!(!program
+            .check()
+            .iter()
+            .any(|diagnostic| matches!(diagnostic, Diagnostic::StackUnderflow { .. })));
+    }
+
+    #[test]
+    fn entry_points_of_two_labels() {
+        let program = Program::from_vec(vec![
+            AwaTism::Blow(1),
+            AwaTism::Label(u5::ZERO),
+            AwaTism::Pop,
+            AwaTism::Label(u5::ONE),
+            AwaTism::Jump(u5::ZERO),
+            AwaTism::Jump(u5::ONE),
+        ]);
+        assert_eq!(program.entry_points(), [0, 2, 4]);
+    }
+
+    #[test]
+    fn missing_terminate() {
+        let missing = |awatisms| {
+            Program::from_vec(awatisms)
+                .check()
+                .contains(&Diagnostic::MissingTerminate)
+        };
+        assert!(missing(vec![AwaTism::Blow(1), AwaTism::Print]));
+        assert!(!missing(vec![
+            AwaTism::Blow(1),
+            AwaTism::Print,
+            AwaTism::Terminate
+        ]));
+        // NOTE: a failed comparison skips the final terminate
+        assert!(missing(vec![
+            AwaTism::Blow(1),
+            AwaTism::Blow(2),
+            AwaTism::EqualTo,
+            AwaTism::Terminate,
+        ]));
+        assert!(!missing(vec![
+            AwaTism::Label(u5::ZERO),
+            AwaTism::Blow(1),
+            AwaTism::Print,
+            AwaTism::Jump(u5::ZERO),
+        ]));
+    }
+
+    #[test]
+    fn trailing_comparison_skips_past_end() {
+        let trailing =
+            Program::from_vec(vec![AwaTism::Blow(1), AwaTism::Blow(2), AwaTism::EqualTo]);
+        assert!(trailing
+            .check()
+            .contains(&Diagnostic::SkipPastEnd { pc: 2 }));
+        let guarded = Program::from_vec(vec![
+            AwaTism::Blow(1),
+            AwaTism::Blow(2),
+            AwaTism::EqualTo,
+            AwaTism::Terminate,
+        ]);
+        assert!(!guarded
+            .check()
+            .iter()
+            .any(|diagnostic| matches!(diagnostic, Diagnostic::SkipPastEnd { .. })));
+    }
+
+    #[test]
+    fn int_width_overflow() {
+        let program = Program::from_vec(vec![
+            AwaTism::Blow(100),
+            AwaTism::Blow(100),
+            AwaTism::Add,
+            AwaTism::Terminate,
+        ]);
+        assert!(matches!(
+            program.check_int_width(8)[..],
+            [Diagnostic::IntOverflow { pc: 2, bits: 8 }]
+        ));
+        assert!(program.check_int_width(9).is_empty());
+        let single = Program::from_vec(vec![AwaTism::Blow(100), AwaTism::Terminate]);
+        assert!(single.check_int_width(8).is_empty());
+        assert!(matches!(
+            single.check_int_width(7)[..],
+            [Diagnostic::IntOverflow { pc: 0, bits: 7 }]
+        ));
+        for bits in [0, 1, 128, 129, u32::MAX] {
+            program.check_int_width(bits);
+        }
+        assert!(program.check_int_width(0).is_empty());
+        assert!(program.check_int_width(129).is_empty());
+    }
+
+    #[test]
+    fn unjumped_label_is_reported() {
+        let program = Program::from_vec(vec![
+            AwaTism::Label(u5::ONE),
+            AwaTism::Blow(1),
+            AwaTism::Label(u5::TWO),
+            AwaTism::PrintNum,
+            AwaTism::Jump(u5::ONE),
+        ]);
+        let unused = program
+            .check()
+            .into_iter()
+            .filter(|diagnostic| matches!(diagnostic, Diagnostic::UnusedLabel { .. }))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            unused,
+            [Diagnostic::UnusedLabel {
+                pc: 2,
+                label: u5::TWO
+            }]
+        );
+        assert_eq!(unused[0].to_string(), "label 2 is never jumped to");
+    }
+
+    #[test]
+    fn io_predicates() {
+        let silent = Program::from_vec(vec![AwaTism::Blow(1), AwaTism::Pop, AwaTism::Terminate]);
+        assert!(!silent.reads_input() && !silent.writes_output());
+        let echo = Program::from_vec(vec![AwaTism::ReadNum, AwaTism::PrintNum]);
+        assert!(echo.reads_input() && echo.writes_output());
+        let read = Program::from_vec(vec![AwaTism::Read, AwaTism::Pop]);
+        assert!(read.reads_input() && !read.writes_output());
+        let print = Program::from_vec(vec![AwaTism::Blow(1), AwaTism::Print]);
+        assert!(!print.reads_input() && print.writes_output());
+        assert!(!Program::new().reads_input() && !Program::new().writes_output());
+    }
+
+    #[test]
+    fn arithmetic_is_pure() {
+        let arithmetic = Program::from_vec(vec![
+            AwaTism::Blow(3),
+            AwaTism::Blow(4),
+            AwaTism::Add,
+            AwaTism::Duplicate,
+            AwaTism::Multiply,
+            AwaTism::Terminate,
+        ]);
+        assert!(arithmetic.is_pure());
+        assert!(Program::new().is_pure());
+        for io in [
+            AwaTism::Read,
+            AwaTism::ReadNum,
+            AwaTism::Print,
+            AwaTism::PrintNum,
+        ] {
+            let program = Program::from_vec(vec![AwaTism::Blow(1), io, AwaTism::Terminate]);
+            assert!(!program.is_pure());
+        }
+    }
+
+    #[test]
+    fn comparison_skipping_label_or_last() {
+        let zero = u5::ZERO;
+        let skips_label = Program::from_vec(vec![
+            AwaTism::Blow(1),
+            AwaTism::Blow(2),
+            AwaTism::GreaterThan,
+            AwaTism::Label(zero),
+            AwaTism::PrintNum,
+            AwaTism::Terminate,
+        ]);
+        assert!(skips_label
+            .check()
+            .contains(&Diagnostic::SkipsLabel { pc: 2 }));
+        let skips_to_end = Program::from_vec(vec![
+            AwaTism::Blow(1),
+            AwaTism::Blow(2),
+            AwaTism::EqualTo,
+            AwaTism::Terminate,
+        ]);
+        assert!(skips_to_end
+            .check()
+            .contains(&Diagnostic::SkipToEnd { pc: 2 }));
+        let conditional_jump = Program::from_vec(vec![
+            AwaTism::Label(zero),
+            AwaTism::Blow(1),
+            AwaTism::Blow(2),
+            AwaTism::LessThan,
+            AwaTism::Jump(zero),
+            AwaTism::PrintNum,
+            AwaTism::Terminate,
+        ]);
+        assert!(!conditional_jump.check().iter().any(|diagnostic| matches!(
+            diagnostic,
+            Diagnostic::SkipPastEnd { .. }
+                | Diagnostic::SkipToEnd { .. }
+                | Diagnostic::SkipsLabel { .. }
+        )));
+    }
+
+    #[test]
+    fn infinite_loop_never_terminates() {
+        let zero = u5::ZERO;
+        let looping = Program::from_vec(vec![
+            AwaTism::Label(zero),
+            AwaTism::Blow(1),
+            AwaTism::PrintNum,
+            AwaTism::Jump(zero),
+        ]);
+        assert!(!looping.can_terminate());
+        let diagnostics = looping.check();
+        assert!(diagnostics.contains(&Diagnostic::NeverTerminates));
+        assert!(!diagnostics.contains(&Diagnostic::MissingTerminate));
+        // NOTE: a terminate after the loop can't be reached either
+        let unreachable = Program::from_vec(vec![
+            AwaTism::Label(zero),
+            AwaTism::Blow(1),
+            AwaTism::Jump(zero),
+            AwaTism::Terminate,
+        ]);
+        assert!(unreachable.check().contains(&Diagnostic::NeverTerminates));
+        let exiting = Program::from_vec(vec![
+            AwaTism::Label(zero),
+            AwaTism::ReadNum,
+            AwaTism::Blow(0),
+            AwaTism::EqualTo,
+            AwaTism::Terminate,
+            AwaTism::Pop,
+            AwaTism::Pop,
+            AwaTism::Jump(zero),
+        ]);
+        assert!(exiting.can_terminate());
+        assert!(!exiting.check().contains(&Diagnostic::NeverTerminates));
+    }
+}
@@ -1,5 +1,8 @@
+#[cfg(feature = "std")]
 use bitbuffer::{BitError, BitRead, BitReadStream, BitWrite, BitWriteStream, Endianness};
-use std::{cell::LazyCell, fmt::Display, ops::Deref};
+use core::{fmt::Display, ops::Deref};
+#[cfg(feature = "std")]
+use std::io::{Result as IOResult, Write};
 
 use crate::Error;
 
@@ -15,14 +18,6 @@ impl AwaSCII {
         b'3', b'4', b'5', b'6', b'7', b'8', b'9', b' ', b'.', b',', b'!', b'`', b'(', b')', b'~',
         b'_', b'/', b';', b'\n',
     ];
-    #[allow(clippy::declare_interior_mutable_const)]
-    const FROM_ASCII: LazyCell<[u8; 128]> = LazyCell::new(|| {
-        let mut t = [255; 128];
-        for (awascii, ascii) in Self::TO_ASCII.iter().enumerate() {
-            t[*ascii as usize] = awascii as u8;
-        }
-        t
-    });
     /// Create a new character from its character code.
     /// # Safety
     /// `awascii` has to be a valid 6 bit number
@@ -32,22 +27,26 @@ impl AwaSCII {
     }
     #[inline]
     pub fn new(awascii: u8) -> Option<Self> {
-        if awascii >= 32 {
+        if !Self::is_valid_code(awascii) {
             return None;
         }
         // SAFETY: awascii is a valid 6 bit number here
         Some(unsafe { AwaSCII(awascii) })
     }
+    /// Check if `code` is a valid AwaSCII character code.
+    #[inline(always)]
+    pub const fn is_valid_code(code: u8) -> bool {
+        code < 64
+    }
+    /// Check if `byte` is an ASCII character that can be represented in AwaSCII.
+    #[inline(always)]
+    pub const fn is_valid_ascii(byte: u8) -> bool {
+        AwaSCIITable::DEFAULT.from_ascii(byte).is_some()
+    }
     /// Create a new chracter from an ASCII character, when a chatacter cannot be represented in AwaSCII `None` will be returned.
     #[inline]
     pub fn from_ascii(ascii: u8) -> Option<Self> {
-        #[allow(clippy::borrow_interior_mutable_const)]
-        let awascii = (*Self::FROM_ASCII)[ascii as usize];
-        if awascii == 255 {
-            return None;
-        }
-        // SAFETY: FROM_ASCII only contains valid AwaSCII characters
-        Some(unsafe { Self(awascii) })
+        AwaSCIITable::DEFAULT.from_ascii(ascii)
     }
     /// Return the matching ASCII chatacter.
     #[inline]
@@ -55,6 +54,63 @@ impl AwaSCII {
         Self::TO_ASCII[self.0 as usize]
     }
 }
+
+/// Mapping between AwaSCII and ASCII characters, allows using dialects with a different character set.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AwaSCIITable {
+    to_ascii: [u8; 64],
+    from_ascii: [u8; 128],
+}
+impl AwaSCIITable {
+    /// Character set defined by AWA5.0.
+    pub const DEFAULT: Self = match Self::new(AwaSCII::TO_ASCII) {
+        Some(table) => table,
+        None => panic!("default AwaSCII table is invalid"),
+    };
+    /// Create a table from the ASCII characters for each AwaSCII character code.
+    /// Returns `None` when a character is not ASCII or appears more than once.
+    #[inline]
+    pub const fn new(to_ascii: [u8; 64]) -> Option<Self> {
+        let mut from_ascii = [255; 128];
+        let mut awascii = 0;
+        while awascii < 64 {
+            let ascii = to_ascii[awascii];
+            if ascii >= 128 || from_ascii[ascii as usize] != 255 {
+                return None;
+            }
+            from_ascii[ascii as usize] = awascii as u8;
+            awascii += 1;
+        }
+        Some(Self {
+            to_ascii,
+            from_ascii,
+        })
+    }
+    /// Return the matching AwaSCII character, `None` when the character is not part of the table.
+    #[inline]
+    pub const fn from_ascii(&self, ascii: u8) -> Option<AwaSCII> {
+        if ascii >= 128 {
+            return None;
+        }
+        let awascii = self.from_ascii[ascii as usize];
+        if awascii == 255 {
+            return None;
+        }
+        // SAFETY: from_ascii only contains valid AwaSCII characters
+        Some(unsafe { AwaSCII(awascii) })
+    }
+    /// Return the matching ASCII character.
+    #[inline]
+    pub const fn to_ascii(&self, awascii: AwaSCII) -> u8 {
+        self.to_ascii[awascii.0 as usize]
+    }
+}
+impl Default for AwaSCIITable {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
 impl Deref for AwaSCII {
     type Target = u8;
     #[inline(always)]
@@ -73,6 +129,20 @@ impl TryFrom<u8> for AwaSCII {
         Ok(unsafe { Self(value) })
     }
 }
+/// Write the ASCII representation of `awascii` to `out` without allocating.
+#[cfg(feature = "std")]
+#[inline]
+pub fn write_awascii(out: &mut impl Write, awascii: &[AwaSCII]) -> IOResult<()> {
+    let mut buffer = [0; 256];
+    for chunk in awascii.chunks(buffer.len()) {
+        for (byte, awascii) in buffer.iter_mut().zip(chunk) {
+            *byte = awascii.to_ascii();
+        }
+        out.write_all(&buffer[..chunk.len()])?;
+    }
+    Ok(())
+}
+#[cfg(feature = "std")]
 impl<'a, E: Endianness> BitRead<'a, E> for AwaSCII {
     #[inline]
     fn read(stream: &mut BitReadStream<'a, E>) -> Result<Self, BitError> {
@@ -83,6 +153,7 @@ impl<'a, E: Endianness> BitRead<'a, E> for AwaSCII {
         Some(6)
     }
 }
+#[cfg(feature = "std")]
 impl<E: Endianness> BitWrite<E> for AwaSCII {
     #[inline(always)]
     fn write(&self, stream: &mut BitWriteStream<E>) -> Result<(), BitError> {
@@ -91,7 +162,45 @@ impl<E: Endianness> BitWrite<E> for AwaSCII {
 }
 impl Display for AwaSCII {
     #[inline(always)]
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         (self.to_ascii() as char).fmt(f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validity_boundaries() {
+        const VALID: [bool; 2] = [AwaSCII::is_valid_code(63), AwaSCII::is_valid_ascii(b'\n')];
+        assert_eq!(VALID, [true, true]);
+        assert!(!AwaSCII::is_valid_code(64));
+        assert!(!AwaSCII::is_valid_code(u8::MAX));
+        assert_eq!(AwaSCII::new(63).map(|c| c.to_ascii()), Some(b'\n'));
+        assert_eq!(AwaSCII::new(64), None);
+        for byte in [b'A', b'w', b'0', b' ', b'`', b';'] {
+            assert!(AwaSCII::is_valid_ascii(byte), "{}", byte as char);
+        }
+        for byte in [b'Q', b'z', b'?', b'|', b'\r', 0, 0x80] {
+            assert!(!AwaSCII::is_valid_ascii(byte), "{byte:#04x}");
+            assert_eq!(AwaSCII::from_ascii(byte), None);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn write_awascii_bytes() {
+        let text = b"Awa awa! 0123\n".repeat(40);
+        let awascii = text
+            .iter()
+            .map(|byte| AwaSCII::from_ascii(*byte).unwrap())
+            .collect::<Vec<_>>();
+        let mut out = Vec::new();
+        write_awascii(&mut out, &awascii).unwrap();
+        assert_eq!(out, text);
+        out.clear();
+        write_awascii(&mut out, &[]).unwrap();
+        assert!(out.is_empty());
+    }
+}
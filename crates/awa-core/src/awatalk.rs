@@ -1,6 +1,8 @@
 use bitbuffer::{BitError, BitReadBuffer, BitWriteStream, Endianness};
 use thiserror::Error;
 
+use crate::Program;
+
 /// Represents an error that can occure during interpretation of AwaTalk source code.
 #[derive(Debug, Error)]
 pub enum ParseError {
@@ -8,6 +10,9 @@ pub enum ParseError {
     NoHeader,
     #[error(transparent)]
     BitError(#[from] BitError),
+    /// Decoding the instruction starting at `bit` failed.
+    #[error("failed to decode instruction at bit {bit}")]
+    DecodeAt { bit: usize, source: BitError },
 }
 
 #[derive(Debug)]
@@ -55,9 +60,9 @@ pub fn load_awatalk<E: Endianness>(
     else {
         return Err(ParseError::NoHeader);
     };
-    // SAFETY: buffer: src only containing ones will take 16 bits per bit
-    let mut buffer = vec![0; src.len() >> 4];
-    let mut writer = BitWriteStream::from_slice(&mut buffer, E::endianness());
+    // NOTE: a body of only ones takes 2 characters per bit, so this will not need to grow
+    let mut buffer = Vec::with_capacity((src.len() >> 4) + 1);
+    let mut writer = BitWriteStream::new(&mut buffer, E::endianness());
     let [mut zero, mut one] = [AWATALK_ZERO, AWATALK_ONE].map(StringMatcher::new);
     while let Some((char, rest)) = src.split_first() {
         src = rest;
@@ -75,3 +80,189 @@ pub fn load_awatalk<E: Endianness>(
     buffer.truncate(len);
     Ok((BitReadBuffer::new_owned(buffer, E::endianness()), bits))
 }
+/// Convert AwaTalk source code into a [`Program`], see [`load_awatalk`].
+#[inline]
+pub fn program_from_awatalk<E: Endianness>(src: impl AsRef<[u8]>) -> Result<Program, ParseError> {
+    let (buffer, bits) = load_awatalk::<E>(src)?;
+    Program::from_bitbuffer_with_length(buffer, bits)
+}
+/// Convert a sequence of bits into AwaTalk source code on a single line.
+/// This is the inverse of [`load_awatalk`].
+#[inline]
+pub fn encode_awatalk(bits: impl IntoIterator<Item = bool>) -> String {
+    let bits = bits.into_iter();
+    let mut result =
+        String::with_capacity(AWATALK_HEAD.len() + bits.size_hint().0 * AWATALK_ZERO.len());
+    result.push_str("awa");
+    for bit in bits {
+        result.push_str(if bit { AWATALK_ONE } else { AWATALK_ZERO });
+    }
+    result
+}
+/// Convert the first `length` bits of a binary into AwaTalk source code, see [`encode_awatalk`].
+/// A newline is inserted before every bit position in `breaks` (which has to be sorted),
+/// this will never split a token and can be loaded again.
+/// A break at `length` ends the source with a newline, positions after that are ignored.
+#[inline]
+pub fn save_awatalk<E: Endianness>(
+    buffer: BitReadBuffer<E>,
+    length: usize,
+    breaks: impl IntoIterator<Item = usize>,
+) -> Result<String, BitError> {
+    let mut result = String::with_capacity(AWATALK_HEAD.len() + length * AWATALK_ZERO.len());
+    result.push_str("awa");
+    let mut breaks = breaks.into_iter().peekable();
+    for i in 0..=length {
+        while let Some(pos) = breaks.next_if(|pos| *pos <= i) {
+            if pos == i {
+                result.push('\n');
+            }
+        }
+        if i == length {
+            break;
+        }
+        if buffer.read_bool(i)? {
+            result.push_str(AWATALK_ONE);
+        } else {
+            result.push_str(AWATALK_ZERO);
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitbuffer::BigEndian;
+    use num_traits::ConstOne;
+
+    use super::*;
+    use crate::{u5, AwaTism};
+
+    #[test]
+    fn program_from_known_awatalk() {
+        let src = concat!(
+            "awa",
+            // blo 1
+            " awa awawa awawa",
+            " awa awa awa awa awa awa awawa",
+            // pr1
+            " awa awa awawa awa",
+            // trm
+            "wawawawawa",
+        );
+        let program = program_from_awatalk::<BigEndian>(src).unwrap();
+        assert_eq!(
+            program.instructions(),
+            [AwaTism::Blow(1), AwaTism::PrintNum, AwaTism::Terminate]
+        );
+        assert!(matches!(
+            program_from_awatalk::<BigEndian>("wa"),
+            Err(ParseError::NoHeader)
+        ));
+    }
+
+    #[test]
+    fn short_awatalk_bodies() {
+        let bits = |src: &str| {
+            let (buffer, len) = load_awatalk::<BigEndian>(src).unwrap();
+            (0..len)
+                .map(|i| buffer.read_bool(i).unwrap())
+                .collect::<alloc::vec::Vec<_>>()
+        };
+        assert_eq!(bits("awa"), []);
+        assert_eq!(bits("awawa"), [true]);
+        assert_eq!(bits("awa awa"), [false]);
+        assert_eq!(bits(&format!("awa{}", "wa".repeat(8))), [true; 8]);
+        assert_eq!(bits(&format!("awa{}", "wa".repeat(9))), [true; 9]);
+    }
+
+    /// Encode `program` and convert it into AwaTalk with newlines at `breaks`.
+    fn to_awatalk(
+        program: &Program,
+        breaks: impl IntoIterator<Item = usize>,
+    ) -> alloc::string::String {
+        let mut buffer = alloc::vec::Vec::new();
+        let mut writer = BitWriteStream::new(&mut buffer, BigEndian);
+        for awatism in program {
+            writer.write(awatism).unwrap();
+        }
+        let length = writer.bit_len();
+        save_awatalk(BitReadBuffer::new(&buffer, BigEndian), length, breaks).unwrap()
+    }
+
+    #[test]
+    fn wrapped_awatalk_reloads() {
+        let program = Program::from_vec(alloc::vec![
+            AwaTism::Blow(-3),
+            AwaTism::Surround(u5::ONE),
+            AwaTism::PrintNum,
+            AwaTism::Terminate,
+        ]);
+        let ends = program
+            .iter()
+            .scan(0, |pos, awatism| {
+                *pos += awatism.bit_width();
+                Some(*pos)
+            })
+            .collect::<alloc::vec::Vec<_>>();
+        let wrapped = to_awatalk(&program, ends.iter().copied());
+        assert_eq!(wrapped.lines().count(), program.len());
+        let reloaded = program_from_awatalk::<BigEndian>(&wrapped).unwrap();
+        assert_eq!(reloaded.instructions(), program.instructions());
+    }
+    #[test]
+    fn encoded_bits_reload() {
+        let bits = [true, false, false, true, true, false, true, false, true];
+        let (buffer, len) = load_awatalk::<BigEndian>(encode_awatalk(bits)).unwrap();
+        let reloaded = (0..len)
+            .map(|i| buffer.read_bool(i).unwrap())
+            .collect::<alloc::vec::Vec<_>>();
+        assert_eq!(reloaded, bits);
+    }
+
+    #[test]
+    fn saved_awatalk_round_trips() {
+        let src = "awa awawa awa\nwa awa awa awawawa awa";
+        let (buffer, len) = load_awatalk::<BigEndian>(src).unwrap();
+        let saved = save_awatalk(buffer.clone(), len, [3]).unwrap();
+        assert_eq!(saved, src);
+        let (reloaded, reloaded_len) = load_awatalk::<BigEndian>(&saved).unwrap();
+        assert_eq!(reloaded_len, len);
+        assert_eq!(
+            reloaded.read_int::<u16>(0, len).unwrap(),
+            buffer.read_int::<u16>(0, len).unwrap()
+        );
+    }
+
+    #[test]
+    fn breaks_at_both_ends() {
+        let buffer = BitReadBuffer::new(&[0b1010_0000], BigEndian);
+        assert_eq!(
+            save_awatalk(buffer.clone(), 3, [0]).unwrap(),
+            "awa\nwa awawa"
+        );
+        assert_eq!(
+            save_awatalk(buffer.clone(), 3, [3]).unwrap(),
+            "awawa awawa\n"
+        );
+        assert_eq!(
+            save_awatalk(buffer.clone(), 3, [0, 3, 4]).unwrap(),
+            "awa\nwa awawa\n"
+        );
+        // NOTE: a break that was already passed must not swallow the next one
+        assert_eq!(
+            save_awatalk(buffer.clone(), 3, [2, 1, 2]).unwrap(),
+            "awawa awa\n\nwa"
+        );
+        let (_, len) = load_awatalk::<BigEndian>("awa\nwa awawa\n").unwrap();
+        assert_eq!(len, 3);
+    }
+    #[test]
+    fn exported_from_crate_root() {
+        // NOTE: this only compiles if the root exports are the items of this module
+        let missing: Result<_, ParseError> = crate::load_awatalk::<BigEndian>("wa");
+        assert!(matches!(missing, Err(crate::ParseError::NoHeader)));
+        let (_, len) = crate::load_awatalk::<BigEndian>("awawa").unwrap();
+        assert_eq!(len, 1);
+    }
+}
@@ -1,60 +1,112 @@
 use crate::u5;
+#[cfg(feature = "std")]
 use bitbuffer::{BitRead, BitWrite};
-use std::fmt::Display;
+use core::fmt::Display;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, BitRead, BitWrite)]
-#[discriminant_bits = 5]
+// NOTE: the BitRead derive rejects doc comments on the enum itself
+// NOTE: opcodes 0x15 and 0x17..=0x1E are unused, decoding them fails with BitError::UnmatchedDiscriminant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "std", derive(BitRead, BitWrite))]
+#[cfg_attr(feature = "std", discriminant_bits = 5)]
 pub enum AwaTism {
-    #[discriminant = 0x00]
+    #[cfg_attr(feature = "std", discriminant = 0x00)]
     NoOp,
-    #[discriminant = 0x01]
+    #[cfg_attr(feature = "std", discriminant = 0x01)]
     Print,
-    #[discriminant = 0x02]
+    #[cfg_attr(feature = "std", discriminant = 0x02)]
     PrintNum,
-    #[discriminant = 0x03]
+    #[cfg_attr(feature = "std", discriminant = 0x03)]
     Read,
-    #[discriminant = 0x04]
+    #[cfg_attr(feature = "std", discriminant = 0x04)]
     ReadNum,
-    #[discriminant = 0x1F]
+    #[cfg_attr(feature = "std", discriminant = 0x1F)]
     Terminate,
-    #[discriminant = 0x05]
+    #[cfg_attr(feature = "std", discriminant = 0x05)]
     Blow(i8),
-    #[discriminant = 0x06]
+    #[cfg_attr(feature = "std", discriminant = 0x06)]
     Submerge(u5),
-    #[discriminant = 0x07]
+    #[cfg_attr(feature = "std", discriminant = 0x07)]
     Pop,
-    #[discriminant = 0x08]
+    #[cfg_attr(feature = "std", discriminant = 0x08)]
     Duplicate,
-    #[discriminant = 0x09]
+    #[cfg_attr(feature = "std", discriminant = 0x09)]
     Surround(u5),
-    #[discriminant = 0x0A]
+    #[cfg_attr(feature = "std", discriminant = 0x0A)]
     Merge,
-    #[discriminant = 0x0B]
+    #[cfg_attr(feature = "std", discriminant = 0x0B)]
     Add,
-    #[discriminant = 0x0C]
+    #[cfg_attr(feature = "std", discriminant = 0x0C)]
     Subtract,
-    #[discriminant = 0x0D]
+    #[cfg_attr(feature = "std", discriminant = 0x0D)]
     Multiply,
-    #[discriminant = 0x0E]
+    #[cfg_attr(feature = "std", discriminant = 0x0E)]
     Divide,
-    #[discriminant = 0x0F]
+    #[cfg_attr(feature = "std", discriminant = 0x0F)]
     Count,
-    #[discriminant = 0x10]
+    #[cfg_attr(feature = "std", discriminant = 0x10)]
     Label(u5),
-    #[discriminant = 0x11]
+    #[cfg_attr(feature = "std", discriminant = 0x11)]
     Jump(u5),
-    #[discriminant = 0x12]
+    #[cfg_attr(feature = "std", discriminant = 0x12)]
     EqualTo,
-    #[discriminant = 0x13]
+    #[cfg_attr(feature = "std", discriminant = 0x13)]
     LessThan,
-    #[discriminant = 0x14]
+    #[cfg_attr(feature = "std", discriminant = 0x14)]
     GreaterThan,
-    #[discriminant = 0x16]
+    #[cfg_attr(feature = "std", discriminant = 0x16)]
     DoublePop,
 }
+impl AwaTism {
+    /// Instructions that change or end the flow of execution.
+    #[inline]
+    pub const fn is_control_flow(&self) -> bool {
+        matches!(
+            self,
+            Self::Jump(_)
+                | Self::Label(_)
+                | Self::EqualTo
+                | Self::LessThan
+                | Self::GreaterThan
+                | Self::Terminate
+        )
+    }
+    /// Instructions that read from input or write to output.
+    #[inline]
+    pub const fn is_io(&self) -> bool {
+        matches!(
+            self,
+            Self::Print | Self::PrintNum | Self::Read | Self::ReadNum
+        )
+    }
+    /// Instructions that compute new values from existing bubbles.
+    #[inline]
+    pub const fn is_arithmetic(&self) -> bool {
+        matches!(
+            self,
+            Self::Add | Self::Subtract | Self::Multiply | Self::Divide | Self::Count
+        )
+    }
+    /// Instructions that only rearrange bubbles in the abyss (including [`AwaTism::NoOp`]).
+    #[inline]
+    pub const fn is_stack(&self) -> bool {
+        !(self.is_control_flow() || self.is_io() || self.is_arithmetic())
+    }
+    /// Number of bits used by the binary representation of the biggest instruction.
+    pub const MAX_BIT_WIDTH: usize = 13;
+    /// Number of bits used by the binary representation,
+    /// this is 5 bits for the discriminant plus the size of the argument.
+    #[inline]
+    pub const fn bit_width(&self) -> usize {
+        match self {
+            Self::Blow(_) => 5 + 8,
+            Self::Submerge(_) | Self::Surround(_) | Self::Label(_) | Self::Jump(_) => 5 + 5,
+            _ => 5,
+        }
+    }
+}
 impl Display for AwaTism {
     #[inline]
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::NoOp => f.write_str("nop"),
             Self::Print => f.write_str("prn"),
@@ -82,3 +134,73 @@ impl Display for AwaTism {
         }
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// One instance of every variant, in the order of their discriminants.
+    pub(crate) const EVERY_VARIANT: [AwaTism; 23] = [
+        AwaTism::NoOp,
+        AwaTism::Print,
+        AwaTism::PrintNum,
+        AwaTism::Read,
+        AwaTism::ReadNum,
+        AwaTism::Blow(-1),
+        AwaTism::Submerge(u5::MAX),
+        AwaTism::Pop,
+        AwaTism::Duplicate,
+        AwaTism::Surround(u5::TWO),
+        AwaTism::Merge,
+        AwaTism::Add,
+        AwaTism::Subtract,
+        AwaTism::Multiply,
+        AwaTism::Divide,
+        AwaTism::Count,
+        AwaTism::Label(u5::TWO),
+        AwaTism::Jump(u5::TWO),
+        AwaTism::EqualTo,
+        AwaTism::LessThan,
+        AwaTism::GreaterThan,
+        AwaTism::DoublePop,
+        AwaTism::Terminate,
+    ];
+
+    #[test]
+    fn one_category_each() {
+        for awatism in EVERY_VARIANT {
+            let categories = [
+                awatism.is_control_flow(),
+                awatism.is_io(),
+                awatism.is_arithmetic(),
+                awatism.is_stack(),
+            ];
+            assert_eq!(
+                categories.iter().filter(|is| **is).count(),
+                1,
+                "{awatism} has to be in exactly one category"
+            );
+        }
+        assert!(AwaTism::NoOp.is_stack());
+        assert!(AwaTism::Count.is_arithmetic());
+        assert!(AwaTism::Terminate.is_control_flow());
+        assert!(AwaTism::ReadNum.is_io());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn bit_width_matches_encoding() {
+        use bitbuffer::{BigEndian, BitWriteStream, LittleEndian};
+
+        for awatism in EVERY_VARIANT {
+            let mut buffer = std::vec::Vec::new();
+            let mut writer = BitWriteStream::new(&mut buffer, BigEndian);
+            writer.write(&awatism).unwrap();
+            assert_eq!(writer.bit_len(), awatism.bit_width(), "{awatism}");
+            buffer.clear();
+            let mut writer = BitWriteStream::new(&mut buffer, LittleEndian);
+            writer.write(&awatism).unwrap();
+            assert_eq!(writer.bit_len(), awatism.bit_width(), "{awatism}");
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use alloc::{vec, vec::Vec};
+use core::ops::Range;
+
+use crate::{AwaTism, Program};
+
+/// Sequence of instructions that is always executed from start to end, see [`Program::basic_blocks`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Block {
+    /// Location of the first instruction.
+    pub start: usize,
+    /// Location after the last instruction.
+    pub end: usize,
+    /// Sorted locations that can be executed after the last instruction, each one is the start of a block.
+    /// [`Program::len`] means that the program ends there.
+    pub successors: Vec<usize>,
+}
+impl Block {
+    #[inline(always)]
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+    /// Returns the location of the last instruction.
+    #[inline(always)]
+    pub fn last(&self) -> usize {
+        self.end - 1
+    }
+}
+
+impl Program {
+    /// Split the program into basic blocks, ordered by their location.
+    /// Blocks start at the [`Program::entry_points`] and after jumps, terminates and comparisons,
+    /// so only the last instruction of a block can change where execution continues.
+    pub fn basic_blocks(&self) -> Vec<Block> {
+        let mut leaders = vec![false; self.len() + 1];
+        for pc in self.entry_points() {
+            leaders[pc] = true;
+        }
+        for (pc, awatism) in self.iter().enumerate() {
+            match awatism {
+                AwaTism::Jump(_) | AwaTism::Terminate => leaders[pc + 1] = true,
+                AwaTism::EqualTo | AwaTism::LessThan | AwaTism::GreaterThan => {
+                    leaders[pc + 1] = true;
+                    // NOTE: a comparison skipping past the end also ends the program
+                    if let Some(leader) = leaders.get_mut(pc + 2) {
+                        *leader = true;
+                    }
+                }
+                _ => (),
+            }
+        }
+        leaders[self.len()] = true;
+        let starts = leaders
+            .iter()
+            .enumerate()
+            .filter_map(|(pc, leader)| leader.then_some(pc))
+            .collect::<Vec<_>>();
+        starts
+            .windows(2)
+            .map(|range| {
+                let (start, end) = (range[0], range[1]);
+                let mut successors = self
+                    .successors(end - 1)
+                    .into_iter()
+                    .flatten()
+                    .map(|pc| pc.min(self.len()))
+                    .collect::<Vec<_>>();
+                successors.sort_unstable();
+                successors.dedup();
+                Block {
+                    start,
+                    end,
+                    successors,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_traits::ConstZero;
+
+    use super::*;
+    use crate::u5;
+
+    #[test]
+    fn loop_with_condition() {
+        let program = Program::from_vec(vec![
+            AwaTism::Blow(5),
+            AwaTism::Label(u5::ZERO),
+            AwaTism::Blow(1),
+            AwaTism::Subtract,
+            AwaTism::Duplicate,
+            AwaTism::Blow(0),
+            AwaTism::EqualTo,
+            AwaTism::Terminate,
+            AwaTism::Pop,
+            AwaTism::Jump(u5::ZERO),
+        ]);
+        let block = |start, end, successors: &[usize]| Block {
+            start,
+            end,
+            successors: successors.to_vec(),
+        };
+        assert_eq!(
+            program.basic_blocks(),
+            [
+                block(0, 2, &[2]),
+                block(2, 7, &[7, 8]),
+                block(7, 8, &[]),
+                block(8, 10, &[2]),
+            ]
+        );
+    }
+
+    #[test]
+    fn skip_past_end() {
+        let program = Program::from_vec(vec![AwaTism::Blow(1), AwaTism::EqualTo, AwaTism::Print]);
+        let blocks = program.basic_blocks();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(
+            (blocks[0].range(), &blocks[0].successors[..]),
+            (0..2, &[2, 3][..])
+        );
+        assert_eq!(
+            (blocks[1].range(), &blocks[1].successors[..]),
+            (2..3, &[3][..])
+        );
+    }
+}
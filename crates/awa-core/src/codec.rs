@@ -0,0 +1,287 @@
+use alloc::vec::Vec;
+
+use crate::{u5, AwaTism, Program};
+
+/// Represents an error that can occur while decoding a binary with [`Program::from_bytes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "std", derive(thiserror::Error))]
+pub enum DecodeError {
+    /// Instruction starting at `bit` uses an opcode that is not assigned to any instruction.
+    #[cfg_attr(feature = "std", error("unknown opcode {opcode:#04x} at bit {bit}"))]
+    UnknownOpcode { bit: usize, opcode: u8 },
+    /// Instruction starting at `bit` is cut off by the end of the binary.
+    #[cfg_attr(feature = "std", error("instruction at bit {bit} is cut off"))]
+    Truncated { bit: usize },
+}
+// NOTE: thiserror needs std, so Display has to be spelled out here
+#[cfg(not(feature = "std"))]
+impl core::fmt::Display for DecodeError {
+    #[inline]
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::UnknownOpcode { bit, opcode } => {
+                write!(f, "unknown opcode {:#04x} at bit {}", opcode, bit)
+            }
+            Self::Truncated { bit } => write!(f, "instruction at bit {} is cut off", bit),
+        }
+    }
+}
+
+/// Reads bits starting from the most significant bit of each byte.
+#[derive(Debug)]
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+impl<'a> BitReader<'a> {
+    #[inline(always)]
+    fn bits_left(&self) -> usize {
+        self.bytes.len() * 8 - self.pos
+    }
+    /// Returns `None` without moving when there are less than `count` bits left.
+    #[inline]
+    fn read(&mut self, count: usize) -> Option<u16> {
+        if self.bits_left() < count {
+            return None;
+        }
+        let mut value = 0;
+        for _ in 0..count {
+            let bit = self.bytes[self.pos / 8] >> (7 - self.pos % 8) & 1;
+            (value, self.pos) = (value << 1 | bit as u16, self.pos + 1);
+        }
+        Some(value)
+    }
+}
+
+/// Writes bits starting from the most significant bit of each byte.
+#[derive(Debug, Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    len: usize,
+}
+impl BitWriter {
+    #[inline]
+    fn write(&mut self, value: u16, count: usize) {
+        for i in (0..count).rev() {
+            if self.len % 8 == 0 {
+                self.bytes.push(0);
+            }
+            // SAFETY: unwrap: a byte was pushed above when the last one was full
+            *self.bytes.last_mut().unwrap() |= ((value >> i & 1) as u8) << (7 - self.len % 8);
+            self.len += 1;
+        }
+    }
+}
+
+impl AwaTism {
+    /// Number stored in the first 5 bits of the binary representation.
+    #[inline]
+    pub const fn opcode(&self) -> u8 {
+        match self {
+            Self::NoOp => 0x00,
+            Self::Print => 0x01,
+            Self::PrintNum => 0x02,
+            Self::Read => 0x03,
+            Self::ReadNum => 0x04,
+            Self::Blow(_) => 0x05,
+            Self::Submerge(_) => 0x06,
+            Self::Pop => 0x07,
+            Self::Duplicate => 0x08,
+            Self::Surround(_) => 0x09,
+            Self::Merge => 0x0A,
+            Self::Add => 0x0B,
+            Self::Subtract => 0x0C,
+            Self::Multiply => 0x0D,
+            Self::Divide => 0x0E,
+            Self::Count => 0x0F,
+            Self::Label(_) => 0x10,
+            Self::Jump(_) => 0x11,
+            Self::EqualTo => 0x12,
+            Self::LessThan => 0x13,
+            Self::GreaterThan => 0x14,
+            Self::DoublePop => 0x16,
+            Self::Terminate => 0x1F,
+        }
+    }
+    /// Returns `None` when the instruction is cut off, unknown opcodes are reported as `Some(Err(opcode))`.
+    #[inline]
+    fn decode(reader: &mut BitReader) -> Option<Result<Self, u8>> {
+        let opcode = reader.read(5)? as u8;
+        // SAFETY: 5 bits always fit into u5
+        let mut operand = || {
+            reader
+                .read(5)
+                .map(|value| unsafe { u5::new_unchecked(value as u8) })
+        };
+        let awatism = match opcode {
+            0x00 => Self::NoOp,
+            0x01 => Self::Print,
+            0x02 => Self::PrintNum,
+            0x03 => Self::Read,
+            0x04 => Self::ReadNum,
+            0x05 => Self::Blow(reader.read(8)? as u8 as i8),
+            0x06 => Self::Submerge(operand()?),
+            0x07 => Self::Pop,
+            0x08 => Self::Duplicate,
+            0x09 => Self::Surround(operand()?),
+            0x0A => Self::Merge,
+            0x0B => Self::Add,
+            0x0C => Self::Subtract,
+            0x0D => Self::Multiply,
+            0x0E => Self::Divide,
+            0x0F => Self::Count,
+            0x10 => Self::Label(operand()?),
+            0x11 => Self::Jump(operand()?),
+            0x12 => Self::EqualTo,
+            0x13 => Self::LessThan,
+            0x14 => Self::GreaterThan,
+            0x16 => Self::DoublePop,
+            0x1F => Self::Terminate,
+            opcode => return Some(Err(opcode)),
+        };
+        Some(Ok(awatism))
+    }
+}
+
+impl Program {
+    /// Encode the program as a big-endian binary, the last byte is padded with zero bits.
+    /// This produces the same binary as writing every instruction to a big-endian `BitWriteStream`,
+    /// but does not need `std`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = BitWriter {
+            bytes: Vec::with_capacity(self.bit_len().div_ceil(8)),
+            len: 0,
+        };
+        for awatism in self {
+            writer.write(awatism.opcode() as u16, 5);
+            match awatism {
+                AwaTism::Blow(value) => writer.write(*value as u8 as u16, 8),
+                AwaTism::Submerge(operand)
+                | AwaTism::Surround(operand)
+                | AwaTism::Label(operand)
+                | AwaTism::Jump(operand) => writer.write(**operand as u16, 5),
+                _ => (),
+            }
+        }
+        writer.bytes
+    }
+    /// Decode a big-endian binary, the inverse of [`Program::to_bytes`].
+    /// Trailing zero bits that don't form a whole instruction are treated as padding.
+    /// This accepts the same binaries as `Program::from_bitbuffer`, but does not need `std`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let (mut reader, mut program) = (BitReader { bytes, pos: 0 }, Self::new());
+        while reader.bits_left() != 0 {
+            let bit = reader.pos;
+            match AwaTism::decode(&mut reader) {
+                Some(Ok(awatism)) => program.push(awatism),
+                Some(Err(opcode)) => return Err(DecodeError::UnknownOpcode { bit, opcode }),
+                None => {
+                    reader.pos = bit;
+                    // NOTE: an instruction is only cut off with less than 13 bits left, so the rest fits into u16
+                    if reader.read(reader.bits_left()) == Some(0) {
+                        break;
+                    }
+                    return Err(DecodeError::Truncated { bit });
+                }
+            }
+        }
+        Ok(program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use num_traits::ConstOne;
+
+    use super::*;
+
+    fn sample() -> Program {
+        let mut instructions = vec![
+            AwaTism::Print,
+            AwaTism::Submerge(u5::MAX),
+            AwaTism::Label(u5::ONE),
+            AwaTism::Jump(u5::ONE),
+            AwaTism::DoublePop,
+            AwaTism::Terminate,
+        ];
+        instructions.extend((i8::MIN..=i8::MAX).map(AwaTism::Blow));
+        Program::from_vec(instructions)
+    }
+
+    #[test]
+    fn round_trip() {
+        let program = sample();
+        let decoded = Program::from_bytes(&program.to_bytes()).unwrap();
+        assert_eq!(decoded.instructions(), program.instructions());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn matches_bitbuffer() {
+        use bitbuffer::{BigEndian, BitReadBuffer, BitWriteStream};
+
+        let program = sample();
+        let mut buffer = Vec::new();
+        let mut writer = BitWriteStream::new(&mut buffer, BigEndian);
+        for awatism in &program {
+            writer.write(awatism).unwrap();
+        }
+        assert_eq!(program.to_bytes(), buffer);
+        let decoded = Program::from_bitbuffer(BitReadBuffer::new(&buffer, BigEndian)).unwrap();
+        assert_eq!(decoded.instructions(), program.instructions());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn every_opcode_matches_bitbuffer() {
+        use bitbuffer::{BigEndian, BitWriteStream};
+
+        use crate::awatism::tests::EVERY_VARIANT;
+
+        for awatism in EVERY_VARIANT {
+            let program = Program::from_vec(vec![awatism]);
+            let mut buffer = Vec::new();
+            let mut writer = BitWriteStream::new(&mut buffer, BigEndian);
+            writer.write(&awatism).unwrap();
+            assert_eq!(program.to_bytes(), buffer, "{awatism}");
+            // NOTE: padding of 5 or more bits decodes as trailing NoOp
+            let decoded = Program::from_bytes(&buffer).unwrap();
+            assert_eq!(decoded.get(0), Some(&awatism));
+            assert!(decoded.iter().skip(1).all(|rest| *rest == AwaTism::NoOp));
+        }
+        // NOTE: every opcode that is not used by a variant has to be rejected
+        for opcode in 0..32u8 {
+            let used = EVERY_VARIANT
+                .iter()
+                .any(|awatism| awatism.opcode() == opcode);
+            let result = Program::from_bytes(&[opcode << 3, 0, 0]);
+            assert_eq!(
+                result.is_err_and(|error| error == DecodeError::UnknownOpcode { bit: 0, opcode }),
+                !used,
+                "{opcode:#04x}"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_opcode() {
+        assert_eq!(
+            Program::from_bytes(&[0x00, 0x6A]).unwrap_err(),
+            DecodeError::UnknownOpcode {
+                bit: 10,
+                opcode: 0x15
+            }
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        // NOTE: blo followed by only 3 bits of its operand
+        assert_eq!(
+            Program::from_bytes(&[0x2F]).unwrap_err(),
+            DecodeError::Truncated { bit: 0 }
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use alloc::{vec, vec::Vec};
+use core::fmt::Display;
+
+use crate::{AwaTism, Program};
+
+/// Represents a difference between two [`Program`]s, see [`Program::diff`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Diff {
+    /// Instruction at `pc` of the old program is missing from the new one.
+    Removed { pc: usize, awatism: AwaTism },
+    /// Instruction at `pc` of the new program is missing from the old one.
+    Added { pc: usize, awatism: AwaTism },
+    /// Instruction at `pc` of the old program was replaced by the one at `new_pc` of the new program.
+    Changed {
+        pc: usize,
+        new_pc: usize,
+        from: AwaTism,
+        to: AwaTism,
+    },
+}
+impl Display for Diff {
+    #[inline]
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Removed { pc, awatism } => write!(f, "-{} {}", pc + 1, awatism),
+            Self::Added { pc, awatism } => write!(f, "+{} {}", pc + 1, awatism),
+            Self::Changed { pc, from, to, .. } => write!(f, "~{} {} -> {}", pc + 1, from, to),
+        }
+    }
+}
+
+/// Turn the positions collected for a block of differences into [`Diff`]s,
+/// pairing up removed and added instructions as changes.
+#[inline]
+fn flush(
+    result: &mut Vec<Diff>,
+    (old, new): (&[AwaTism], &[AwaTism]),
+    removed: &mut Vec<usize>,
+    added: &mut Vec<usize>,
+) {
+    let paired = removed.len().min(added.len());
+    for (&pc, &new_pc) in removed.iter().zip(added.iter()) {
+        result.push(Diff::Changed {
+            pc,
+            new_pc,
+            from: old[pc],
+            to: new[new_pc],
+        });
+    }
+    for &pc in &removed[paired..] {
+        result.push(Diff::Removed {
+            pc,
+            awatism: old[pc],
+        });
+    }
+    for &pc in &added[paired..] {
+        result.push(Diff::Added {
+            pc,
+            awatism: new[pc],
+        });
+    }
+    removed.clear();
+    added.clear();
+}
+
+impl Program {
+    /// Find the instructions that have to change to turn this program into `other`.
+    /// Differences are ordered by their position and based on the longest common subsequence of instructions.
+    pub fn diff(&self, other: &Program) -> Vec<Diff> {
+        let (old, new) = (self.instructions(), other.instructions());
+        // NOTE: most differences are local, so only the middle part needs the quadratic table
+        let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let (n, m) = (old.len() - prefix - suffix, new.len() - prefix - suffix);
+        let at = |i: usize, j: usize| i * (m + 1) + j;
+        // NOTE: common[at(i, j)] is the length of the longest common subsequence of old[i..] and new[j..]
+        let mut common = vec![0usize; (n + 1) * (m + 1)];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                common[at(i, j)] = if old[prefix + i] == new[prefix + j] {
+                    common[at(i + 1, j + 1)] + 1
+                } else {
+                    common[at(i + 1, j)].max(common[at(i, j + 1)])
+                };
+            }
+        }
+        let (mut result, mut removed, mut added) = (Vec::new(), Vec::new(), Vec::new());
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old[prefix + i] == new[prefix + j] {
+                flush(&mut result, (old, new), &mut removed, &mut added);
+                (i, j) = (i + 1, j + 1);
+            } else if j == m || (i < n && common[at(i + 1, j)] >= common[at(i, j + 1)]) {
+                removed.push(prefix + i);
+                i += 1;
+            } else {
+                added.push(prefix + j);
+                j += 1;
+            }
+        }
+        flush(&mut result, (old, new), &mut removed, &mut added);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+    use crate::remove_noops;
+
+    #[test]
+    fn removed_noops() {
+        let program = Program::from_vec(vec![
+            AwaTism::Blow(1),
+            AwaTism::NoOp,
+            AwaTism::Blow(2),
+            AwaTism::EqualTo,
+            AwaTism::NoOp,
+            AwaTism::NoOp,
+            AwaTism::PrintNum,
+            AwaTism::NoOp,
+            AwaTism::Terminate,
+        ]);
+        let removed = [1, 5, 7].map(|pc| Diff::Removed {
+            pc,
+            awatism: AwaTism::NoOp,
+        });
+        assert_eq!(program.diff(&remove_noops(&program)), removed);
+        assert!(program.diff(&program).is_empty());
+    }
+
+    #[test]
+    fn changed_and_added() {
+        let old = Program::from_vec(vec![AwaTism::Blow(1), AwaTism::Print]);
+        let new = Program::from_vec(vec![AwaTism::Blow(2), AwaTism::Print, AwaTism::Terminate]);
+        let diff = old.diff(&new);
+        assert_eq!(
+            diff,
+            [
+                Diff::Changed {
+                    pc: 0,
+                    new_pc: 0,
+                    from: AwaTism::Blow(1),
+                    to: AwaTism::Blow(2),
+                },
+                Diff::Added {
+                    pc: 2,
+                    awatism: AwaTism::Terminate,
+                },
+            ]
+        );
+        assert_eq!(diff[0].to_string(), "~1 blo 1 -> blo 2");
+        assert_eq!(diff[1].to_string(), "+3 trm");
+    }
+}
@@ -2,9 +2,13 @@
 #![feature(rustc_attrs)]
 #![feature(nonzero_internals)]
 #![feature(trait_alias)]
+#![cfg_attr(not(feature = "std"), no_std)]
 
-use std::num::ParseIntError;
+extern crate alloc;
 
+use core::num::ParseIntError;
+
+#[cfg(feature = "std")]
 pub use bitbuffer::{
     BigEndian, BitError, BitReadBuffer, BitReadStream, BitWriteStream, Endianness, LittleEndian,
 };
@@ -17,19 +21,50 @@ mod awascii;
 pub use awascii::*;
 mod abyss;
 pub use abyss::*;
+#[cfg(feature = "std")]
 mod awatalk;
+#[cfg(feature = "std")]
 pub use awatalk::*;
 mod program;
 pub use program::*;
+mod analysis;
+pub use analysis::*;
+mod optimize;
+pub use optimize::*;
+mod diff;
+pub use diff::*;
+mod cfg;
+pub use cfg::*;
+mod codec;
+pub use codec::*;
 
-use thiserror::Error;
-
-#[derive(Debug, Clone, Error)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "std", derive(thiserror::Error))]
 pub enum Error {
-    #[error("Value is too big to fit in {0} bits")]
+    #[cfg_attr(feature = "std", error("Value is too big to fit in {0} bits"))]
     OutOfBounds(u8),
-    #[error("ASCII char {0} has no equivalent AwaSCII char")]
+    #[cfg_attr(
+        feature = "std",
+        error("ASCII char {0} has no equivalent AwaSCII char")
+    )]
     InvalidAwaSCII(u8),
-    #[error(transparent)]
-    ParseError(#[from] ParseIntError),
+    #[cfg_attr(
+        feature = "std",
+        error("label {label} is defined at instruction {first} and again at {second}")
+    )]
+    DuplicateLabel {
+        label: u8,
+        first: usize,
+        second: usize,
+    },
+    #[cfg_attr(feature = "std", error(transparent))]
+    ParseError(#[cfg_attr(feature = "std", from)] ParseIntError),
+}
+// NOTE: thiserror needs std, so the conversion has to be spelled out here
+#[cfg(not(feature = "std"))]
+impl From<ParseIntError> for Error {
+    #[inline(always)]
+    fn from(value: ParseIntError) -> Self {
+        Self::ParseError(value)
+    }
 }
@@ -0,0 +1,95 @@
+use alloc::vec::Vec;
+
+use crate::{AwaTism, Program};
+
+/// Apply behavior-preserving rewrites that make `program` shorter.
+///
+/// Currently this is the same as [`remove_noops`].
+#[inline]
+pub fn optimize(program: &Program) -> Program {
+    remove_noops(program)
+}
+/// Remove every [`AwaTism::NoOp`] that is not the instruction skipped by a comparison.
+///
+/// Jumps refer to labels instead of instruction positions, so they stay valid with the rebuilt label table,
+/// only the positions of instructions (like the ones reported by diagnostics) change.
+pub fn remove_noops(program: &Program) -> Program {
+    let mut instructions = Vec::with_capacity(program.len());
+    let mut previous = None;
+    for awatism in program {
+        // NOTE: a failed comparison skips the next instruction, removing it would skip another one instead
+        let skippable = matches!(
+            previous,
+            Some(AwaTism::EqualTo | AwaTism::LessThan | AwaTism::GreaterThan)
+        );
+        if skippable || *awatism != AwaTism::NoOp {
+            instructions.push(*awatism);
+        }
+        previous = Some(*awatism);
+    }
+    Program::from_vec(instructions)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::u5;
+
+    #[test]
+    fn noops_are_removed() {
+        let program = Program::from_vec(vec![
+            AwaTism::NoOp,
+            AwaTism::Label(u5::TWO),
+            AwaTism::NoOp,
+            AwaTism::Blow(1),
+            AwaTism::NoOp,
+            AwaTism::Jump(u5::TWO),
+        ]);
+        let optimized = optimize(&program);
+        assert!(optimized.len() < program.len());
+        assert_eq!(
+            optimized.instructions(),
+            [
+                AwaTism::Label(u5::TWO),
+                AwaTism::Blow(1),
+                AwaTism::Jump(u5::TWO)
+            ]
+        );
+        assert_eq!(
+            optimized.defined_labels().collect::<Vec<_>>(),
+            [(u5::TWO, 1)]
+        );
+    }
+
+    #[test]
+    fn skipped_noops_are_kept() {
+        let program = Program::from_vec(vec![
+            AwaTism::Blow(1),
+            AwaTism::Blow(2),
+            AwaTism::EqualTo,
+            AwaTism::NoOp,
+            AwaTism::NoOp,
+            AwaTism::LessThan,
+            AwaTism::NoOp,
+            AwaTism::GreaterThan,
+            AwaTism::NoOp,
+            AwaTism::PrintNum,
+        ]);
+        assert_eq!(
+            remove_noops(&program).instructions(),
+            [
+                AwaTism::Blow(1),
+                AwaTism::Blow(2),
+                AwaTism::EqualTo,
+                AwaTism::NoOp,
+                AwaTism::LessThan,
+                AwaTism::NoOp,
+                AwaTism::GreaterThan,
+                AwaTism::NoOp,
+                AwaTism::PrintNum,
+            ]
+        );
+    }
+}
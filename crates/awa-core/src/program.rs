@@ -1,77 +1,115 @@
-use std::{num::NonZero, ops::Index, slice::SliceIndex};
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::{fmt::Write, num::NonZero, ops::Index, slice::SliceIndex};
 
+#[cfg(feature = "std")]
 use bitbuffer::{BitError, BitReadBuffer, BitReadStream, Endianness};
-use num_traits::cast;
 
-use crate::AwaTism;
+#[cfg(feature = "std")]
+use crate::ParseError;
+use crate::{u5, AwaSCII, AwaTism, Error};
 
+/// Instructions together with the positions of their labels.
+///
+/// A program is never modified while running, so it can be shared between threads by reference,
+/// with every thread running its own interpreter.
 #[derive(Debug, Clone)]
 pub struct Program {
     instructions: Vec<AwaTism>,
     labels: Box<[Option<NonZero<usize>>; 32]>,
 }
+// NOTE: make sure sharing a program between threads keeps working
+const _: fn() = || {
+    fn shareable<T: Send + Sync>() {}
+    shareable::<Program>();
+};
 impl Program {
     #[inline]
     pub fn new() -> Self {
-        Program {
-            instructions: Vec::new(),
-            labels: [None; 32].into(),
-        }
+        Self::with_capacity(0)
     }
     #[inline]
     pub fn with_capacity(capacity: usize) -> Self {
-        Program {
-            instructions: Vec::with_capacity(capacity),
+        Self::from_vec(Vec::with_capacity(capacity))
+    }
+    #[inline]
+    pub fn from_vec(instructions: Vec<AwaTism>) -> Self {
+        let mut program = Self {
+            instructions,
             labels: [None; 32].into(),
+        };
+        for pc in 0..program.instructions.len() {
+            if let AwaTism::Label(label) = program.instructions[pc] {
+                program.record_label(label, pc);
+            }
         }
+        program
+    }
+    /// Make jumps to `label` continue after the instruction at `pc`.
+    #[inline(always)]
+    fn record_label(&mut self, label: u5, pc: usize) {
+        // SAFETY: pc + 1 can never be zero
+        self.labels[*label as usize] = Some(unsafe { NonZero::new_unchecked(pc + 1) });
     }
+    /// Same as [`Program::from_vec`], but fails when a label is defined more than once.
+    /// Otherwise jumps would silently go to the last definition.
     #[inline]
-    pub fn from_vec(instructions: Vec<AwaTism>) -> Self {
-        let mut labels = Box::new([None; 32]);
+    pub fn from_vec_checked(instructions: Vec<AwaTism>) -> Result<Self, Error> {
+        let mut first = [None; 32];
         for (pc, awatism) in instructions.iter().enumerate() {
             if let AwaTism::Label(label) = awatism {
-                // SAFETY: pc + 1 can never be zero
-                labels[**label as usize] = Some(unsafe { NonZero::new_unchecked(pc + 1) });
+                if let Some(previous) = first[**label as usize].replace(pc) {
+                    return Err(Error::DuplicateLabel {
+                        label: **label,
+                        first: previous,
+                        second: pc,
+                    });
+                }
             }
         }
-        Self {
-            instructions,
-            labels,
-        }
+        Ok(Self::from_vec(instructions))
     }
+    #[cfg(feature = "std")]
     #[inline]
-    pub fn from_bitbuffer(buffer: BitReadBuffer<impl Endianness>) -> Result<Self, BitError> {
+    pub fn from_bitbuffer(buffer: BitReadBuffer<impl Endianness>) -> Result<Self, ParseError> {
         let (mut stream, mut program) = (BitReadStream::new(buffer), Self::new());
         loop {
+            let bit = stream.pos();
             match stream.read() {
                 Ok(awatism) => program.push(awatism),
-                Err(error @ BitError::NotEnoughData { bits_left, .. }) => {
+                Err(source @ BitError::NotEnoughData { .. }) => {
+                    // NOTE: trailing zero bits are padding to fill the last byte
+                    stream.set_pos(bit)?;
+                    let bits_left = stream.bits_left();
                     // SAFETY: unwrap: no AwaTism needs more than 16 bits
-                    if stream.read_int::<u16>(bits_left).unwrap() == 0 {
+                    if bits_left == 0 || stream.read_int::<u16>(bits_left).unwrap() == 0 {
                         return Ok(program);
                     }
-                    return Err(error);
+                    return Err(ParseError::DecodeAt { bit, source });
                 }
                 Err(BitError::IndexOutOfBounds { .. }) => return Ok(program),
-                Err(error) => return Err(error),
+                Err(source) => return Err(ParseError::DecodeAt { bit, source }),
             }
         }
     }
+    #[cfg(feature = "std")]
     #[inline]
     pub fn from_bitbuffer_with_length(
         buffer: BitReadBuffer<impl Endianness>,
         length: usize,
-    ) -> Result<Self, BitError> {
+    ) -> Result<Self, ParseError> {
         if length == 0 {
             return Ok(Self::new());
         }
-        // NOTE: biggest instruction is 13 bits, so this is the minimum size required
-        let (mut stream, mut program) =
-            (BitReadStream::new(buffer), Self::with_capacity(length / 13));
+        // NOTE: every instruction takes at most this many bits, so this is the minimum size required
+        let (mut stream, mut program) = (
+            BitReadStream::new(buffer),
+            Self::with_capacity(length / AwaTism::MAX_BIT_WIDTH),
+        );
         while stream.pos() < length {
+            let bit = stream.pos();
             match stream.read() {
                 Ok(awatism) => program.push(awatism),
-                Err(error) => return Err(error),
+                Err(source) => return Err(ParseError::DecodeAt { bit, source }),
             }
         }
         Ok(program)
@@ -84,6 +122,11 @@ impl Program {
     pub fn is_empty(&self) -> bool {
         self.instructions.is_empty()
     }
+    /// Number of bits used by the binary representation, excluding padding.
+    #[inline]
+    pub fn bit_len(&self) -> usize {
+        self.instructions.iter().map(AwaTism::bit_width).sum()
+    }
     #[inline(always)]
     pub fn iter(&self) -> impl Iterator<Item = &AwaTism> {
         self.instructions.iter()
@@ -92,6 +135,53 @@ impl Program {
     pub fn get<I: SliceIndex<[AwaTism]>>(&self, index: I) -> Option<&I::Output> {
         self.instructions.get(index)
     }
+    /// Returns all instructions as a slice.
+    #[inline(always)]
+    pub fn instructions(&self) -> &[AwaTism] {
+        self.instructions.as_slice()
+    }
+    /// Copies all instructions into a new [`Vec`], see [`Program::from_vec`] for the reverse.
+    #[inline(always)]
+    pub fn to_vec(&self) -> Vec<AwaTism> {
+        self.instructions.clone()
+    }
+    /// Replace every instruction with the result of `f`.
+    /// The label table is rebuilt, since `f` can add, move or remove labels.
+    #[inline]
+    pub fn map(self, f: impl FnMut(AwaTism) -> AwaTism) -> Self {
+        Self::from_vec(self.instructions.into_iter().map(f).collect())
+    }
+    /// Same as [`Program::map`], but instructions are removed when `f` returns `None`.
+    /// Jumps are not retargeted, so removing a label or an instruction skipped by a comparison changes behaviour.
+    #[inline]
+    pub fn filter_map(self, f: impl FnMut(AwaTism) -> Option<AwaTism>) -> Self {
+        Self::from_vec(self.instructions.into_iter().filter_map(f).collect())
+    }
+    /// Iterate over all instructions with their location.
+    /// [`AwaTism::Blow`] of a valid character code is annotated with the matching [`AwaSCII`] character.
+    #[inline]
+    pub fn annotated(&self) -> impl Iterator<Item = (usize, &AwaTism, Option<AwaSCII>)> + '_ {
+        self.instructions
+            .iter()
+            .enumerate()
+            .map(|(pc, awatism)| match awatism {
+                AwaTism::Blow(value) => {
+                    let awascii = u8::try_from(*value).ok().and_then(|v| v.try_into().ok());
+                    (pc, awatism, awascii)
+                }
+                _ => (pc, awatism, None),
+            })
+    }
+    /// Format the program as assembly, with one instruction per line.
+    #[inline]
+    pub fn disassemble(&self) -> String {
+        let mut result = String::new();
+        for awatism in &self.instructions {
+            // SAFETY: unwrap: writing to a String cannot fail
+            writeln!(result, "{}", awatism).unwrap();
+        }
+        result
+    }
     /// Returns label table.
     /// Numbers represent the first instruction to execute after jumping to a label, not the label itself.
     /// Will be `None` when no matching label was found.
@@ -99,16 +189,24 @@ impl Program {
     pub fn labels(&self) -> &[Option<NonZero<usize>>] {
         self.labels.as_slice()
     }
+    /// Iterate over all labels that are defined, together with the first instruction to execute after jumping to it.
+    #[inline]
+    pub fn defined_labels(&self) -> impl Iterator<Item = (u5, usize)> + '_ {
+        self.labels.iter().enumerate().filter_map(|(label, pc)| {
+            // SAFETY: the label table has 32 entries, so every index is a valid 5 bit number
+            Some((
+                unsafe { u5::new_unchecked(label as u8) },
+                pc.as_ref()?.get(),
+            ))
+        })
+    }
     /// Push instruction to the end of the program and update the label table.
     #[inline]
     pub fn push(&mut self, awatism: AwaTism) {
-        self.instructions.push(awatism);
         if let AwaTism::Label(label) = awatism {
-            // SAFETY: unwrap: usize is wider than u5
-            // SAFETY: the index limit will not reasonably be reached
-            self.labels[cast::<_, usize>(label).unwrap()] =
-                Some(unsafe { NonZero::new_unchecked(self.instructions.len()) });
+            self.record_label(label, self.instructions.len());
         }
+        self.instructions.push(awatism);
     }
 }
 impl<I: SliceIndex<[AwaTism]>> Index<I> for Program {
@@ -140,3 +238,248 @@ impl<'a> IntoIterator for &'a Program {
         self.instructions.iter()
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use bitbuffer::{BigEndian, BitReadBuffer, BitWriteStream};
+
+    use super::*;
+
+    #[test]
+    fn negative_blow_round_trip() {
+        let mut buffer = Vec::new();
+        let mut writer = BitWriteStream::new(&mut buffer, BigEndian);
+        for value in i8::MIN..=i8::MAX {
+            writer.write(&AwaTism::Blow(value)).unwrap();
+        }
+        let program = Program::from_bitbuffer(BitReadBuffer::new(&buffer, BigEndian)).unwrap();
+        let expected = (i8::MIN..=i8::MAX).map(AwaTism::Blow).collect::<Vec<_>>();
+        assert_eq!(program.instructions(), expected);
+    }
+
+    #[test]
+    fn every_opcode_decodes_cleanly() {
+        for opcode in 0u8..32 {
+            // NOTE: the zero bits after the opcode are read as operand, NoOps or padding
+            let buffer = [opcode << 3, 0];
+            let result = Program::from_bitbuffer(BitReadBuffer::new(&buffer, BigEndian));
+            if opcode == 0x15 || (0x17..=0x1E).contains(&opcode) {
+                let Err(ParseError::DecodeAt { bit: 0, source }) = result else {
+                    panic!("opcode {opcode:#04x} should not decode: {result:?}");
+                };
+                assert!(matches!(source, BitError::UnmatchedDiscriminant { .. }));
+                assert!(source.to_string().contains("Unmatched discriminant"));
+            } else {
+                assert_eq!(result.unwrap()[0].opcode(), opcode);
+            }
+        }
+        let buffer = [0x1F << 3];
+        let program = Program::from_bitbuffer(BitReadBuffer::new(&buffer, BigEndian)).unwrap();
+        assert_eq!(program.instructions(), [AwaTism::Terminate]);
+    }
+
+    #[test]
+    fn adjacent_pairs_through_slice() {
+        let program = Program::from_vec(vec![
+            AwaTism::Blow(1),
+            AwaTism::Pop,
+            AwaTism::Blow(2),
+            AwaTism::Pop,
+            AwaTism::Terminate,
+        ]);
+        let pairs = program
+            .instructions()
+            .windows(2)
+            .enumerate()
+            .filter(|(_, pair)| matches!(pair, [AwaTism::Blow(_), AwaTism::Pop]))
+            .map(|(pc, _)| pc)
+            .collect::<Vec<_>>();
+        assert_eq!(pairs, [0, 2]);
+        assert_eq!(program.instructions().len(), program.len());
+    }
+
+    #[test]
+    fn duplicate_labels_are_detected() {
+        let three = u5::try_from(3).unwrap();
+        let instructions = vec![
+            AwaTism::Label(three),
+            AwaTism::Pop,
+            AwaTism::Label(u5::TWO),
+            AwaTism::Label(three),
+            AwaTism::Jump(three),
+        ];
+        let result = Program::from_vec_checked(instructions.clone());
+        assert!(matches!(
+            result,
+            Err(Error::DuplicateLabel {
+                label: 3,
+                first: 0,
+                second: 3
+            })
+        ));
+        // NOTE: the unchecked version jumps to the last definition
+        assert_eq!(Program::from_vec(instructions).labels()[3], NonZero::new(4));
+        let unique = Program::from_vec_checked(vec![AwaTism::Label(three), AwaTism::Jump(three)]);
+        assert_eq!(unique.unwrap().labels()[3], NonZero::new(1));
+    }
+
+    #[test]
+    fn truncated_binary_reports_bit() {
+        let mut buffer = Vec::new();
+        let mut writer = BitWriteStream::new(&mut buffer, BigEndian);
+        writer.write(&AwaTism::Terminate).unwrap();
+        writer.write(&AwaTism::Blow(1)).unwrap();
+        // NOTE: blo needs 13 bits, only 11 of them are left after trm
+        buffer.truncate(2);
+        let result = Program::from_bitbuffer(BitReadBuffer::new(&buffer, BigEndian));
+        let Err(ParseError::DecodeAt { bit: 5, source }) = result else {
+            panic!("expected decode error at bit 5: {result:?}");
+        };
+        assert!(matches!(source, BitError::NotEnoughData { .. }));
+        let result =
+            Program::from_bitbuffer_with_length(BitReadBuffer::new(&buffer, BigEndian), 18);
+        assert!(matches!(result, Err(ParseError::DecodeAt { bit: 5, .. })));
+    }
+
+    #[test]
+    fn annotated_only_valid_blow() {
+        let program = Program::from_vec(vec![
+            AwaTism::Blow(0),
+            AwaTism::Blow(63),
+            AwaTism::Blow(64),
+            AwaTism::Blow(i8::MAX),
+            AwaTism::Print,
+            AwaTism::Terminate,
+        ]);
+        let annotations = program
+            .annotated()
+            .map(|(_, _, awascii)| awascii.map(|awascii| awascii.to_ascii()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            annotations,
+            [Some(b'A'), Some(b'\n'), None, None, None, None]
+        );
+        let pcs = program.annotated().map(|(pc, _, _)| pc);
+        assert!(pcs.eq(0..program.len()));
+    }
+
+    #[test]
+    fn from_vec_matches_push() {
+        let instructions = vec![
+            AwaTism::Label(u5::TWO),
+            AwaTism::Blow(1),
+            AwaTism::Label(u5::MAX),
+            AwaTism::Jump(u5::TWO),
+            AwaTism::Label(u5::TWO),
+            AwaTism::Terminate,
+        ];
+        let mut pushed = Program::new();
+        for awatism in instructions.iter().copied() {
+            pushed.push(awatism);
+        }
+        let collected = Program::from_vec(instructions);
+        assert_eq!(pushed.labels(), collected.labels());
+        assert_eq!(pushed.instructions(), collected.instructions());
+        assert_eq!(collected.labels()[2], NonZero::new(5));
+        assert_eq!(
+            Program::default().labels(),
+            Program::with_capacity(8).labels()
+        );
+    }
+
+    #[test]
+    fn to_vec_round_trips() {
+        let program = Program::from_vec(vec![
+            AwaTism::Label(u5::TWO),
+            AwaTism::Blow(-3),
+            AwaTism::EqualTo,
+            AwaTism::Jump(u5::TWO),
+            AwaTism::Terminate,
+        ]);
+        let instructions = program.to_vec();
+        assert_eq!(instructions, program.instructions());
+        let copy = Program::from_vec(instructions);
+        assert_eq!(copy.instructions(), program.instructions());
+        assert_eq!(copy.labels(), program.labels());
+    }
+
+    #[test]
+    fn defined_labels_skip_missing() {
+        let (zero, five) = (u5::try_from(0u8).unwrap(), u5::try_from(5u8).unwrap());
+        let program = Program::from_vec(vec![
+            AwaTism::Label(zero),
+            AwaTism::Blow(1),
+            AwaTism::Label(u5::MAX),
+            AwaTism::Label(five),
+            AwaTism::Terminate,
+        ]);
+        let labels = program.defined_labels().collect::<Vec<_>>();
+        assert_eq!(labels, [(zero, 1), (five, 4), (u5::MAX, 3)]);
+        assert_eq!(Program::new().defined_labels().count(), 0);
+    }
+
+    #[test]
+    fn negative_blow_is_not_annotated() {
+        let program = Program::from_vec(vec![
+            AwaTism::Blow(-1),
+            AwaTism::Blow(-64),
+            AwaTism::Blow(i8::MIN),
+            AwaTism::Blow(1),
+        ]);
+        let annotations = program
+            .annotated()
+            .map(|(_, _, awascii)| awascii)
+            .collect::<Vec<_>>();
+        assert_eq!(annotations, [None, None, None, AwaSCII::new(1)]);
+    }
+
+    #[test]
+    fn map_recomputes_labels() {
+        let (zero, five) = (u5::try_from(0u8).unwrap(), u5::try_from(5u8).unwrap());
+        let program = Program::from_vec(vec![
+            AwaTism::NoOp,
+            AwaTism::Label(zero),
+            AwaTism::NoOp,
+            AwaTism::Label(five),
+            AwaTism::Terminate,
+        ]);
+
+        let popped = program.clone().map(|awatism| match awatism {
+            AwaTism::NoOp => AwaTism::Pop,
+            other => other,
+        });
+        assert_eq!(
+            popped.instructions(),
+            [
+                AwaTism::Pop,
+                AwaTism::Label(zero),
+                AwaTism::Pop,
+                AwaTism::Label(five),
+                AwaTism::Terminate
+            ]
+        );
+        assert_eq!(
+            popped.defined_labels().collect::<Vec<_>>(),
+            [(zero, 2), (five, 4)]
+        );
+
+        let renumbered = program.clone().map(|awatism| match awatism {
+            AwaTism::Label(label) if label == zero => AwaTism::Label(u5::MAX),
+            other => other,
+        });
+        assert_eq!(
+            renumbered.defined_labels().collect::<Vec<_>>(),
+            [(five, 4), (u5::MAX, 2)]
+        );
+
+        let stripped = program.filter_map(|awatism| match awatism {
+            AwaTism::NoOp => None,
+            other => Some(other),
+        });
+        assert_eq!(stripped.len(), 3);
+        assert_eq!(
+            stripped.defined_labels().collect::<Vec<_>>(),
+            [(zero, 1), (five, 2)]
+        );
+    }
+}
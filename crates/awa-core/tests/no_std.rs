@@ -0,0 +1,21 @@
+use std::{path::Path, process::Command};
+
+/// Building the workspace always enables `std`, so check the `no_std` build separately.
+#[test]
+fn builds_without_std() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+    // NOTE: a separate target directory avoids waiting for the lock held by the running cargo
+    let target = Path::new(env!("CARGO_TARGET_TMPDIR")).join("no_std");
+    let output = Command::new(env!("CARGO"))
+        .args(["check", "--no-default-features", "--manifest-path"])
+        .arg(manifest)
+        .arg("--target-dir")
+        .arg(target)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
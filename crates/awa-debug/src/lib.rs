@@ -1,13 +1,14 @@
-#![feature(ptr_as_ref_unchecked)]
 use std::{
-    collections::HashSet,
-    fmt::Display,
-    io::{stdout, BufReader, Error as IOError, Read, Write},
+    collections::VecDeque,
+    fmt::{Display, Write as _},
+    io::{stdout, BufReader, Error as IOError, Write},
     num::ParseIntError,
+    time::{Duration, Instant},
 };
 
-use awa_core::{Abyss, AwaTism, Program};
-use awa_interpreter::{Cursor, Error as RuntimeError, Interpreter};
+use awa_core::{Abyss, AwaSCII, AwaTism, Program};
+use awa_interpreter::{BreakpointController, Cursor, Error as RuntimeError, Interpreter};
+use num_traits::{cast, NumCast};
 
 use ratatui::{
     crossterm::{event::*, terminal::*, *},
@@ -28,6 +29,10 @@ pub enum Error {
     UnknownCommand,
     #[error("line not found in program")]
     InvalidBreakpoint,
+    #[error("abyss is empty")]
+    EmptyAbyss,
+    #[error("value {0} does not fit into the abyss")]
+    InvalidValue(i64),
     #[error(transparent)]
     RuntimeError(#[from] RuntimeError),
     #[error(transparent)]
@@ -44,33 +49,178 @@ pub enum Mode {
     Close,
 }
 
+/// Appearance of the cursor in the command line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CursorStyle {
+    #[default]
+    RapidBlink,
+    SlowBlink,
+    Static,
+}
+impl CursorStyle {
+    #[inline]
+    pub fn style(self) -> Style {
+        match self {
+            Self::RapidBlink => Style::new().rapid_blink(),
+            Self::SlowBlink => Style::new().slow_blink(),
+            Self::Static => Style::new(),
+        }
+    }
+}
+
+/// Additional sink for program output.
+struct Tee<'a>(Box<dyn Write + 'a>);
+impl<'a> std::fmt::Debug for Tee<'a> {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Tee")
+    }
+}
+
 #[derive(Debug)]
 pub struct Debugger<'a, A: Abyss + Display> {
     cursor: Cursor<'a>,
     interpreter: Interpreter<A, BufReader<PipeReader>, PipeWriter>,
     inbuffer: Pipe,
     outbuffer: Pipe,
+    /// Output of the last step, kept to reuse its allocation.
+    outtext: String,
     cmdbuffer: Input,
-    breakpoints: HashSet<usize>,
+    breakpoints: BreakpointController<'a, A>,
+    queued: VecDeque<String>,
+    tee: Option<Tee<'a>>,
+    /// Steps left from the last `s N`, `n` or `r` command.
+    running: Option<usize>,
+    /// Location where the last `n` command stops.
+    until: Option<usize>,
     view: View<'a, A>,
     mode: Mode,
+    cursor_style: CursorStyle,
 }
 impl<'a, A: Abyss + Display + 'a> Debugger<'a, A> {
+    /// Maximum number of lines kept in the I/O panel.
+    pub const MAX_IO_LINES: usize = 10000;
+    /// Time spent running the program between redraws.
+    pub const FRAME_TIME: Duration = Duration::from_millis(50);
+    /// Number of steps between checking the time spent running.
+    const STEPS_PER_CHECK: usize = 256;
+    /// Maximum number of steps executed by a `n` command.
+    pub const STEP_OVER_LIMIT: usize = 1_000_000;
+    /// Height of the command line including its border.
+    const COMMAND_HEIGHT: u16 = 3;
     #[inline]
     pub fn new(program: &'a Program, abyss: A) -> Self {
         let (inbuffer, outbuffer) = (Pipe::new(), Pipe::new());
         let interpreter =
             Interpreter::new(abyss, BufReader::new(inbuffer.reader()), outbuffer.writer());
+        let mut view = View::new(program, Tab::IO, 1);
+        view.io.set_max_lines(Some(Self::MAX_IO_LINES));
         Self {
             cursor: Cursor::new(program),
             interpreter,
             inbuffer,
             outbuffer,
+            outtext: String::new(),
             cmdbuffer: Input::default(),
-            breakpoints: HashSet::new(),
-            view: View::new(program, Tab::IO, 1),
+            breakpoints: BreakpointController::new(),
+            queued: VecDeque::new(),
+            tee: None,
+            running: None,
+            until: None,
+            view,
             mode: Mode::Command,
+            cursor_style: CursorStyle::default(),
+        }
+    }
+    /// Use a different cursor in the command line, some terminals do not render blinking text.
+    #[inline(always)]
+    pub fn with_cursor_style(mut self, cursor_style: CursorStyle) -> Self {
+        self.cursor_style = cursor_style;
+        self
+    }
+    /// Keep `context` lines visible above the current instruction, see [`widgets::ProgramWindow::set_context`].
+    #[inline(always)]
+    pub fn with_program_context(mut self, context: usize) -> Self {
+        self.view.program.set_context(context);
+        self
+    }
+    #[inline(always)]
+    pub fn cursor_style(&self) -> CursorStyle {
+        self.cursor_style
+    }
+    #[inline(always)]
+    pub fn set_cursor_style(&mut self, cursor_style: CursorStyle) {
+        self.cursor_style = cursor_style;
+    }
+    #[inline(always)]
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+    #[inline(always)]
+    pub fn pc(&self) -> Option<usize> {
+        self.cursor.pc
+    }
+    /// Returns `true` while a `s N`, `n` or `r` command has steps left, see [`Debugger::advance`].
+    #[inline(always)]
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+    #[inline]
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.locations().iter().copied()
+    }
+    /// Breakpoints set with the `b` command, use this to add breakpoints or a callback when one is hit.
+    #[inline(always)]
+    pub fn breakpoints_mut(&mut self) -> &mut BreakpointController<'a, A> {
+        &mut self.breakpoints
+    }
+    /// Queue lines of input to be consumed by [`AwaTism::Read`] and [`AwaTism::ReadNum`]
+    /// before falling back to asking interactively.
+    #[inline]
+    pub fn queue_input(&mut self, input: impl AsRef<str>) {
+        self.queued
+            .extend(input.as_ref().lines().map(ToString::to_string));
+    }
+    /// Also write all program output to `sink` as it is printed.
+    #[inline]
+    pub fn tee_output(&mut self, sink: impl Write + 'a) {
+        self.tee = Some(Tee(Box::new(sink)));
+    }
+    /// Number of input lines that are queued and not yet consumed.
+    #[inline(always)]
+    pub fn queued_input(&self) -> usize {
+        self.queued.len()
+    }
+    /// Output of the current instruction if it is [`AwaTism::Print`] or [`AwaTism::PrintNum`], without changing the abyss.
+    /// Returns `None` for other instructions, when there is no top bubble or when it can't be printed.
+    pub fn preview_output(&self) -> Option<String> {
+        let print = match self.cursor.current()? {
+            (_, AwaTism::Print) => true,
+            (_, AwaTism::PrintNum) => false,
+            _ => return None,
+        };
+        let mut values = Vec::new();
+        self.interpreter.abyss().peek(|value| values.push(value))?;
+        let mut preview = String::new();
+        for value in values {
+            if print {
+                let awascii = AwaSCII::new(cast(value)?)?;
+                preview.push(self.interpreter.table().to_ascii(awascii) as char);
+            } else {
+                if !preview.is_empty() {
+                    preview.push(' ');
+                }
+                // SAFETY: unwrap: writing to a String cannot fail
+                write!(preview, "{}", value).unwrap();
+            }
         }
+        Some(preview)
+    }
+    #[inline]
+    fn write_input(&mut self, line: &str) {
+        // SAFETY: unwrap: writing to Pipe cannot fail
+        self.inbuffer.writer().write_all(line.as_bytes()).unwrap();
+        self.view.io.push_line(line);
     }
     #[allow(clippy::should_implement_trait)]
     #[inline]
@@ -84,6 +234,10 @@ impl<'a, A: Abyss + Display + 'a> Debugger<'a, A> {
             {
                 self.view.active_tab = Tab::IO;
                 self.mode = Mode::Input;
+                if let Some(line) = self.queued.pop_front() {
+                    self.write_input(&line);
+                    return self.next();
+                }
             }
             Mode::Command | Mode::Input => {
                 if !self.cursor.next(&mut self.interpreter)? {
@@ -92,11 +246,13 @@ impl<'a, A: Abyss + Display + 'a> Debugger<'a, A> {
                 }
                 if let Some(pc) = self.cursor.pc {
                     self.view.program.set_pc(pc);
-                    let mut buffer = String::new();
-                    // SAFETY: unwrap: reading from Pipe cannot fail
-                    self.outbuffer.reader().read_to_string(&mut buffer).unwrap();
-                    if !buffer.is_empty() {
-                        self.view.io.push(&buffer);
+                    self.outtext.clear();
+                    if self.outbuffer.reader().drain_to_string(&mut self.outtext) > 0 {
+                        if let Some(Tee(sink)) = &mut self.tee {
+                            sink.write_all(self.outtext.as_bytes())?;
+                            sink.flush()?;
+                        }
+                        self.view.io.push(&self.outtext);
                         self.view.active_tab = Tab::IO;
                     }
                     self.mode = Mode::Command;
@@ -106,31 +262,48 @@ impl<'a, A: Abyss + Display + 'a> Debugger<'a, A> {
         }
         Ok(())
     }
+    /// Run the debugger in the alternate screen of the terminal until it is closed.
     pub fn run(&mut self) -> Result<(), Error> {
         stdout().execute(EnterAlternateScreen)?;
         enable_raw_mode()?;
-        let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+        let result = Terminal::new(CrosstermBackend::new(stdout()))
+            .map_err(Error::from)
+            .and_then(|mut terminal| self.run_on(&mut terminal));
+        stdout().execute(LeaveAlternateScreen)?;
+        disable_raw_mode()?;
+        result
+    }
+    /// Run the debugger until it is closed, drawing to `terminal`.
+    /// Events are still read from the terminal the process is attached to.
+    pub fn run_on<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<(), Error> {
         terminal.clear()?;
         while self.mode != Mode::Close {
             terminal.draw(|frame| self.draw(frame))?;
-            self.handle_event(read()?)?;
+            if self.is_running() {
+                let start = Instant::now();
+                while self.is_running() && start.elapsed() < Self::FRAME_TIME {
+                    let result = self.advance(Self::STEPS_PER_CHECK);
+                    self.report(result);
+                }
+                while poll(Duration::ZERO)? {
+                    self.handle_event(read()?)?;
+                }
+            } else {
+                self.handle_event(read()?)?;
+            }
         }
-        stdout().execute(LeaveAlternateScreen)?;
-        disable_raw_mode()?;
         Ok(())
     }
-    /// # Safety
-    /// The result has interier mutable access to `self`.
-    pub unsafe fn state<'b>(&self) -> State<'a, 'b, A> {
-        let program = (&self.breakpoints as *const _ as *mut HashSet<usize>).as_mut_unchecked();
-        let abyss = (self.interpreter.abyss() as *const A as *mut A).as_mut_unchecked();
-        State { program, abyss }
-    }
     pub fn draw(&mut self, frame: &mut Frame) {
-        let outer =
-            Layout::vertical(vec![Constraint::Fill(1), Constraint::Length(3)]).split(frame.size());
-        // SAFETY: self is not modified before state is dropped
-        let mut state = unsafe { self.state() };
+        let outer = Layout::vertical(vec![
+            Constraint::Fill(1),
+            Constraint::Length(Self::COMMAND_HEIGHT),
+        ])
+        .split(frame.size());
+        let mut state = State {
+            program: self.breakpoints.locations_mut(),
+            abyss: self.interpreter.abyss_mut(),
+        };
         self.view
             .render_ref(outer[0], frame.buffer_mut(), &mut state);
         let title = match self.mode {
@@ -138,15 +311,32 @@ impl<'a, A: Abyss + Display + 'a> Debugger<'a, A> {
             Mode::Input => "Input",
             _ => return,
         };
+        let mut block = Block::bordered().title(title);
+        if self.mode == Mode::Command && !self.is_running() {
+            if let Some(preview) = self.preview_output() {
+                block = block.title(Line::from(format!(" prints {:?} ", preview)).right_aligned());
+            }
+        }
         Paragraph::new(Line::from(vec![
             " ".into(),
             self.cmdbuffer.value().into(),
-            "|".rapid_blink(),
+            Span::styled("|", self.cursor_style.style()),
         ]))
-        .block(Block::bordered().title(title))
+        .block(block)
         .render(outer[1], frame.buffer_mut());
     }
+    /// Update cached layout for a terminal of `height` lines.
+    #[inline]
+    pub fn resize(&mut self, height: u16) {
+        self.view
+            .resize(height.saturating_sub(Self::COMMAND_HEIGHT));
+    }
     pub fn handle_event(&mut self, event: Event) -> Result<(), Error> {
+        // NOTE: the next iteration of the event loop redraws using the new size
+        if let Event::Resize(_, height) = event {
+            self.resize(height);
+            return Ok(());
+        }
         if let Event::Key(
             event @ KeyEvent {
                 code,
@@ -160,21 +350,17 @@ impl<'a, A: Abyss + Display + 'a> Debugger<'a, A> {
                 return Ok(());
             }
             match code {
+                KeyCode::Esc if self.is_running() => self.running = None,
+                // NOTE: don't start another command before the current one is done
+                KeyCode::Enter if self.is_running() => (),
                 KeyCode::Enter => match self.mode {
                     Mode::Command => {
-                        if let Err(error) = self.execute() {
-                            self.view.diagnostics.push_line(error.to_string());
-                            self.cmdbuffer.reset();
-                            self.view.active_tab = Tab::Diagnostics;
-                        }
+                        let result = self.execute();
+                        self.report(result);
                     }
                     Mode::Input => {
-                        // SAFETY: unwrap: writing to Pipe cannot fail
-                        self.inbuffer
-                            .writer()
-                            .write_all(self.cmdbuffer.value().as_bytes())
-                            .unwrap();
-                        self.view.io.push_line(self.cmdbuffer.value());
+                        let line = self.cmdbuffer.value().to_string();
+                        self.write_input(&line);
                         self.cmdbuffer.reset();
                         self.next()?;
                     }
@@ -206,18 +392,47 @@ impl<'a, A: Abyss + Display + 'a> Debugger<'a, A> {
         }
         Ok(())
     }
-    pub fn execute(&mut self) -> Result<(), Error> {
-        fn should_break(this: &mut Debugger<impl Abyss + Display>) -> bool {
-            if this.mode != Mode::Command {
-                return true;
-            }
-            if let Some(pc) = this.cursor.pc {
-                this.breakpoints.contains(&pc)
-            } else {
-                this.mode = Mode::Done;
-                true
+    /// Show `result` in the diagnostics tab if it is an error.
+    #[inline]
+    fn report(&mut self, result: Result<(), Error>) {
+        if let Err(error) = result {
+            self.view.diagnostics.push_line(error.to_string());
+            self.cmdbuffer.reset();
+            self.view.active_tab = Tab::Diagnostics;
+        }
+    }
+    #[inline]
+    fn should_break(&mut self) -> bool {
+        if self.mode != Mode::Command {
+            return true;
+        }
+        if let Some(pc) = self.cursor.pc {
+            // NOTE: the breakpoint callback has to run even when stopping at the `u` target anyway
+            let hit = self.breakpoints.hit(pc, self.interpreter.abyss());
+            hit || self.until == Some(pc)
+        } else {
+            self.mode = Mode::Done;
+            true
+        }
+    }
+    /// Execute at most `steps` of the steps left by the last `s N`, `n` or `r` command.
+    /// Running stops early at breakpoints, when input is needed and on errors.
+    pub fn advance(&mut self, steps: usize) -> Result<(), Error> {
+        let Some(remaining) = self.running.take() else {
+            return Ok(());
+        };
+        for _ in 0..steps.min(remaining) {
+            self.next()?;
+            if self.should_break() {
+                return Ok(());
             }
         }
+        self.running = remaining.checked_sub(steps).filter(|left| *left != 0);
+        Ok(())
+    }
+    /// Execute the command in the command buffer.
+    /// `s N`, `n` and `r` only start running, the steps are executed by [`Debugger::advance`].
+    pub fn execute(&mut self) -> Result<(), Error> {
         let cmd = self.cmdbuffer.value();
         let len = cmd.len();
         if len == 0 {
@@ -228,25 +443,27 @@ impl<'a, A: Abyss + Display + 'a> Debugger<'a, A> {
             's' if len == 1 => self.next()?,
             's' => {
                 let count = cmd[1..].trim().parse::<usize>()?;
-                for _ in 0..count {
-                    self.next()?;
-                    if should_break(self) {
-                        break;
-                    }
-                }
+                self.running = (count != 0).then_some(count);
+                self.until = None;
+            }
+            // NOTE: there is no call and return, so a jump is assumed to return when the next instruction is reached
+            'n' if len == 1 => {
+                let program = self.cursor.program();
+                self.running = Some(Self::STEP_OVER_LIMIT);
+                // NOTE: jumps continue after the label, so labels themselves are never reached
+                self.until = self.cursor.pc.and_then(|pc| {
+                    (pc + 1..).find(|pc| !matches!(program.get(*pc), Some(AwaTism::Label(_))))
+                });
+            }
+            // NOTE: this will not run out of steps in practice
+            'r' if len == 1 => {
+                self.running = Some(usize::MAX);
+                self.until = None;
             }
-            'r' if len == 1 => loop {
-                self.next()?;
-                if should_break(self) {
-                    break;
-                }
-            },
             'b' if len == 1 => {
                 // SAFETY: unwrap: pc should always be valid by construction
                 let pc = self.cursor.pc.unwrap();
-                if !self.breakpoints.remove(&pc) {
-                    self.breakpoints.insert(pc);
-                }
+                self.breakpoints.toggle(pc);
             }
             'b' => {
                 let trimmed = cmd[1..].trim();
@@ -257,9 +474,7 @@ impl<'a, A: Abyss + Display + 'a> Debugger<'a, A> {
                     if pc >= self.cursor.len() {
                         return Err(Error::InvalidBreakpoint);
                     }
-                    if !self.breakpoints.remove(&pc) {
-                        self.breakpoints.insert(pc);
-                    }
+                    self.breakpoints.toggle(pc);
                 } else {
                     let Some(pc) = trimmed.parse::<usize>()?.checked_sub(1) else {
                         return Err(Error::InvalidBreakpoint);
@@ -267,11 +482,25 @@ impl<'a, A: Abyss + Display + 'a> Debugger<'a, A> {
                     if pc >= self.cursor.len() {
                         return Err(Error::InvalidBreakpoint);
                     }
-                    if !self.breakpoints.remove(&pc) {
-                        self.breakpoints.insert(pc);
-                    }
+                    self.breakpoints.toggle(pc);
                 }
             }
+            'p' if cmd == "pop" => {
+                if self.interpreter.abyss_mut().pop().is_none() {
+                    return Err(Error::EmptyAbyss);
+                }
+                self.view.active_tab = Tab::Abyss;
+            }
+            'p' if cmd.starts_with("push ") => {
+                let value = cmd[5..].trim().parse::<i64>()?;
+                let Some(bubble) = <A::Value as NumCast>::from(value) else {
+                    return Err(Error::InvalidValue(value));
+                };
+                if self.interpreter.abyss_mut().blow(bubble).is_none() {
+                    return Err(RuntimeError::NoSpace.into());
+                }
+                self.view.active_tab = Tab::Abyss;
+            }
             'q' if len == 1 => self.mode = Mode::Close,
             _ => return Err(Error::UnknownCommand),
         };
@@ -279,3 +508,272 @@ impl<'a, A: Abyss + Display + 'a> Debugger<'a, A> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use awa_abyss::linked;
+    use awa_asm::parse_program;
+    use awa_core::Abyss as _;
+    use ratatui::backend::TestBackend;
+
+    use super::*;
+
+    type Abyss = linked::Abyss<isize>;
+
+    /// Type `cmd` into the command line and press enter.
+    fn command(debugger: &mut Debugger<Abyss>, cmd: &str) {
+        let key = |code| Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+        for char in cmd.chars() {
+            debugger.handle_event(key(KeyCode::Char(char))).unwrap();
+        }
+        debugger.handle_event(key(KeyCode::Enter)).unwrap();
+    }
+
+    /// Draw a single frame and return its lines.
+    fn render(debugger: &mut Debugger<Abyss>) -> Vec<String> {
+        let mut terminal = Terminal::new(TestBackend::new(60, 20)).unwrap();
+        debugger.resize(20);
+        let frame = terminal.draw(|frame| debugger.draw(frame)).unwrap();
+        let (buffer, width) = (frame.buffer, frame.area.width as usize);
+        buffer
+            .content
+            .chunks(width)
+            .map(|line| line.iter().map(|cell| cell.symbol()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn scripted_render() {
+        let program = parse_program("blo 1\nblo 2\n4dd\npr1\ntrm").unwrap();
+        let mut debugger = Debugger::new(&program, Abyss::default());
+        command(&mut debugger, "s");
+        command(&mut debugger, "s");
+        let screen = render(&mut debugger);
+        assert!(screen.iter().any(|line| line.contains("4dd")));
+        assert!(screen.iter().any(|line| line.contains("Command")));
+        assert_eq!(debugger.pc(), Some(2));
+        assert_eq!(debugger.interpreter.abyss().depth(), 2);
+    }
+
+    #[test]
+    fn accessors_follow_steps() {
+        let program = parse_program("blo 1\nblo 2\ntrm").unwrap();
+        let mut debugger = Debugger::new(&program, Abyss::default());
+        assert_eq!((debugger.mode(), debugger.pc()), (Mode::Command, Some(0)));
+        command(&mut debugger, "s");
+        assert_eq!(debugger.pc(), Some(1));
+        command(&mut debugger, "b 3");
+        assert_eq!(debugger.breakpoints().collect::<Vec<_>>(), [2]);
+    }
+
+    #[test]
+    fn queued_input_before_prompting() {
+        let program = parse_program("r3d\nr3d\n4dd\nr3d\ntrm").unwrap();
+        let mut debugger = Debugger::new(&program, Abyss::default());
+        debugger.queue_input("5\n7\n");
+        assert_eq!(debugger.queued_input(), 2);
+        command(&mut debugger, "s 3");
+        debugger.advance(3).unwrap();
+        assert_eq!(debugger.queued_input(), 0);
+        assert_eq!(debugger.pc(), Some(3));
+        assert_eq!(debugger.interpreter.abyss().to_string(), "12\n");
+        assert_eq!(debugger.mode(), Mode::Command);
+        command(&mut debugger, "s");
+        assert_eq!(debugger.mode(), Mode::Input);
+    }
+
+    #[test]
+    fn push_and_pop_commands() {
+        let program = parse_program("pr1\ntrm").unwrap();
+        let mut debugger = Debugger::new(&program, Abyss::default());
+        command(&mut debugger, "push 5");
+        command(&mut debugger, "push -3");
+        assert_eq!(debugger.interpreter.abyss().depth(), 2);
+        assert_eq!(debugger.view.active_tab, Tab::Abyss);
+        command(&mut debugger, "pop");
+        command(&mut debugger, "pop");
+        assert!(debugger.interpreter.abyss().is_empty());
+        command(&mut debugger, "pop");
+        assert_eq!(debugger.view.active_tab, Tab::Diagnostics);
+        assert!(debugger
+            .view
+            .diagnostics
+            .lines()
+            .any(|line| line == Error::EmptyAbyss.to_string()));
+        assert_eq!(debugger.pc(), Some(0));
+    }
+
+    #[test]
+    fn tee_matches_io_panel() {
+        let program = parse_program("blo 7\npr1\n!str \" awa\"\nprn\ntrm").unwrap();
+        let mut tee = Vec::new();
+        let shown = {
+            let mut debugger = Debugger::new(&program, Abyss::default());
+            debugger.tee_output(&mut tee);
+            while debugger.mode() != Mode::Done {
+                command(&mut debugger, "s");
+            }
+            debugger.view.io.lines().collect::<String>()
+        };
+        assert_eq!(String::from_utf8(tee).unwrap(), shown);
+        assert_eq!(shown, "7 awa");
+    }
+
+    #[test]
+    fn preview_matches_output() {
+        let program =
+            parse_program("blo 7\npr1\n!str \"awa\"\nblo 3\nsrn 2\nprn\nblo 2\nprn\ntrm").unwrap();
+        let mut debugger = Debugger::new(&program, Abyss::default());
+        let mut previews = Vec::new();
+        while debugger.mode() != Mode::Done {
+            let Some(preview) = debugger.preview_output() else {
+                command(&mut debugger, "s");
+                continue;
+            };
+            let depth = debugger.interpreter.abyss().depth();
+            let before = debugger.view.io.lines().collect::<String>();
+            if preview == "wawa" {
+                let screen = render(&mut debugger);
+                assert!(screen.iter().any(|line| line.contains("prints \"wawa\"")));
+            }
+            assert_eq!(debugger.interpreter.abyss().depth(), depth);
+            command(&mut debugger, "s");
+            let after = debugger.view.io.lines().collect::<String>();
+            assert_eq!(after.strip_prefix(&before), Some(preview.as_str()));
+            previews.push(preview);
+        }
+        assert_eq!(previews, ["7", "wawa", "a"]);
+    }
+
+    #[test]
+    fn resize_keeps_current_line_visible() {
+        let src = format!("{}4dd\ntrm", "blo 1\n".repeat(25));
+        let program = parse_program(&src).unwrap();
+        let mut debugger = Debugger::new(&program, Abyss::default());
+        command(&mut debugger, "s 25");
+        debugger.advance(25).unwrap();
+        assert_eq!(debugger.pc(), Some(25));
+        let draw = |debugger: &mut Debugger<Abyss>, width, height| {
+            debugger.handle_event(Event::Resize(width, height)).unwrap();
+            let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+            let frame = terminal.draw(|frame| debugger.draw(frame)).unwrap();
+            frame
+                .buffer
+                .content
+                .chunks(width as usize)
+                .map(|line| line.iter().map(|cell| cell.symbol()).collect::<String>())
+                .collect::<Vec<_>>()
+        };
+        assert!(draw(&mut debugger, 60, 8)
+            .iter()
+            .any(|line| line.contains("4dd")));
+        // NOTE: too small to show anything useful, but must not panic
+        draw(&mut debugger, 4, 2);
+        assert!(draw(&mut debugger, 60, 40)
+            .iter()
+            .any(|line| line.contains("4dd")));
+        assert_eq!(debugger.mode(), Mode::Command);
+    }
+
+    #[test]
+    fn test_backend_cells() {
+        let program = parse_program("blo 1\nblo 2\n4dd\npr1\ntrm").unwrap();
+        let mut debugger = Debugger::new(&program, Abyss::default());
+        command(&mut debugger, "s");
+        let mut terminal = Terminal::new(TestBackend::new(60, 20)).unwrap();
+        debugger.resize(20);
+        terminal.draw(|frame| debugger.draw(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        assert_eq!(buffer.get(0, 17).symbol(), "┌");
+        assert_eq!(buffer.get(1, 17).symbol(), "C");
+        // NOTE: the next instruction is highlighted
+        assert_eq!(buffer.get(0, 1).symbol(), "2");
+        assert_ne!(buffer.get(2, 1).style(), buffer.get(2, 0).style());
+        assert_eq!(buffer.get(2, 2).style(), buffer.get(2, 0).style());
+        // NOTE: a closed debugger returns before waiting for terminal events
+        debugger.mode = Mode::Close;
+        debugger.run_on(&mut terminal).unwrap();
+        assert_eq!(terminal.backend().buffer().get(0, 17).symbol(), " ");
+    }
+
+    #[test]
+    fn cursor_cell_has_configured_style() {
+        use ratatui::style::Modifier;
+
+        let program = parse_program("trm").unwrap();
+        for (cursor_style, modifier) in [
+            (CursorStyle::RapidBlink, Modifier::RAPID_BLINK),
+            (CursorStyle::SlowBlink, Modifier::SLOW_BLINK),
+            (CursorStyle::Static, Modifier::empty()),
+        ] {
+            let mut debugger =
+                Debugger::new(&program, Abyss::default()).with_cursor_style(cursor_style);
+            let mut terminal = Terminal::new(TestBackend::new(40, 20)).unwrap();
+            debugger.resize(20);
+            terminal.draw(|frame| debugger.draw(frame)).unwrap();
+            let cursor = terminal.backend().buffer().get(2, 18);
+            assert_eq!(cursor.symbol(), "|");
+            assert_eq!(cursor.modifier, modifier);
+        }
+    }
+
+    #[test]
+    fn step_over_subroutine() {
+        // NOTE: the subroutine at `lbl 0` counts down to zero and returns to `lbl 1`
+        let program = parse_program(
+            "blo 3\njmp 0\nlbl 1\npr1\ntrm\n\
+             lbl 0\nblo -1\n4dd\nblo 0\neql\njmp 1\npop\njmp 0",
+        )
+        .unwrap();
+        let mut debugger = Debugger::new(&program, Abyss::default());
+        command(&mut debugger, "s");
+        assert_eq!(debugger.pc(), Some(1));
+        command(&mut debugger, "n");
+        assert!(debugger.is_running());
+        debugger
+            .advance(Debugger::<Abyss>::STEP_OVER_LIMIT)
+            .unwrap();
+        assert!(!debugger.is_running());
+        assert_eq!(debugger.pc(), Some(3));
+        assert_eq!(debugger.interpreter.abyss().to_string(), "0\n0\n");
+        assert_eq!(debugger.mode(), Mode::Command);
+    }
+
+    #[test]
+    fn advance_stops_at_breakpoint() {
+        let program = parse_program("blo 1\nblo 2\nblo 3\nblo 4\ntrm").unwrap();
+        let mut debugger = Debugger::new(&program, Abyss::default());
+        command(&mut debugger, "b 3");
+        command(&mut debugger, "r");
+        debugger.advance(100).unwrap();
+        assert!(!debugger.is_running());
+        assert_eq!(debugger.pc(), Some(2));
+        assert_eq!(debugger.interpreter.abyss().depth(), 2);
+        assert_eq!(debugger.mode(), Mode::Command);
+    }
+
+    #[test]
+    fn step_over_skips_labels() {
+        let program = parse_program("blo 1\nlbl 0\nlbl 1\nblo 2\ntrm").unwrap();
+        let mut debugger = Debugger::new(&program, Abyss::default());
+        command(&mut debugger, "n");
+        debugger.advance(100).unwrap();
+        assert!(!debugger.is_running());
+        assert_eq!(debugger.pc(), Some(3));
+        assert_eq!(debugger.interpreter.abyss().depth(), 1);
+    }
+
+    #[test]
+    fn queued_input_feeds_read_num() {
+        let program = parse_program("r3d\nr3d\nmul\ntrm").unwrap();
+        let mut debugger = Debugger::new(&program, Abyss::default());
+        debugger.queue_input("6\n");
+        debugger.queue_input("-7\n");
+        command(&mut debugger, "s 3");
+        debugger.advance(3).unwrap();
+        assert_eq!(debugger.queued_input(), 0);
+        assert_eq!(debugger.pc(), Some(3));
+        assert_eq!(debugger.interpreter.abyss().to_string(), "-42\n");
+        assert_eq!(debugger.mode(), Mode::Command);
+    }
+}
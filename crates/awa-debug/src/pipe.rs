@@ -1,34 +1,73 @@
 use std::{
     collections::VecDeque,
-    io::{Read, Write},
+    io::{Error as IOError, ErrorKind, Read, Write},
     sync::Arc,
 };
 
-use parking_lot::Mutex;
+use parking_lot::{Condvar, Mutex};
 
+#[derive(Debug, Default)]
+struct State {
+    data: VecDeque<u8>,
+    closed: bool,
+}
+#[derive(Debug, Default)]
+struct Shared {
+    state: Mutex<State>,
+    available: Condvar,
+}
+impl Shared {
+    #[inline]
+    fn close(&self) {
+        self.state.lock().closed = true;
+        self.available.notify_all();
+    }
+}
+
+/// In-memory byte queue with any number of readers and writers.
+///
+/// Readers from [`Pipe::reader`] treat an empty pipe as end of input,
+/// while readers from [`Pipe::blocking_reader`] wait for data until the pipe is closed.
 #[derive(Debug)]
 pub struct Pipe {
-    data: Arc<Mutex<VecDeque<u8>>>,
+    shared: Arc<Shared>,
 }
 impl Pipe {
     #[inline]
     pub fn new() -> Self {
         Self {
-            data: Arc::new(Mutex::new(VecDeque::new())),
+            shared: Arc::new(Shared::default()),
         }
     }
     #[inline(always)]
     pub fn reader(&self) -> PipeReader {
         PipeReader {
-            data: self.data.clone(),
+            shared: self.shared.clone(),
+            blocking: false,
+        }
+    }
+    #[inline(always)]
+    pub fn blocking_reader(&self) -> PipeReader {
+        PipeReader {
+            shared: self.shared.clone(),
+            blocking: true,
         }
     }
     #[inline(always)]
     pub fn writer(&self) -> PipeWriter {
         PipeWriter {
-            data: self.data.clone(),
+            shared: self.shared.clone(),
         }
     }
+    /// Close the pipe, blocking readers will receive end of input once all data was read.
+    #[inline(always)]
+    pub fn close(&self) {
+        self.shared.close();
+    }
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.shared.state.lock().closed
+    }
 }
 impl Default for Pipe {
     #[inline(always)]
@@ -38,17 +77,38 @@ impl Default for Pipe {
 }
 #[derive(Debug)]
 pub struct PipeReader {
-    data: Arc<Mutex<VecDeque<u8>>>,
+    shared: Arc<Shared>,
+    blocking: bool,
+}
+impl PipeReader {
+    /// Append all data currently in the pipe to `buffer` without waiting for more,
+    /// invalid UTF-8 is replaced. Returns the number of bytes removed from the pipe.
+    #[inline]
+    pub fn drain_to_string(&mut self, buffer: &mut String) -> usize {
+        let mut state = self.shared.state.lock();
+        let len = state.data.len();
+        buffer.push_str(&String::from_utf8_lossy(state.data.make_contiguous()));
+        state.data.clear();
+        len
+    }
 }
 impl Read for PipeReader {
     #[inline]
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        let mut data = self.data.lock();
-        let len = buf.len().min(data.len());
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut state = self.shared.state.lock();
+        if self.blocking {
+            while state.data.is_empty() && !state.closed {
+                self.shared.available.wait(&mut state);
+            }
+        }
+        let len = buf.len().min(state.data.len());
         if len == 0 {
             return Ok(0);
         }
-        for (i, byte) in data.drain(0..len).enumerate() {
+        for (i, byte) in state.data.drain(0..len).enumerate() {
             buf[i] = byte;
         }
         Ok(len)
@@ -56,13 +116,24 @@ impl Read for PipeReader {
 }
 #[derive(Debug)]
 pub struct PipeWriter {
-    data: Arc<Mutex<VecDeque<u8>>>,
+    shared: Arc<Shared>,
+}
+impl PipeWriter {
+    /// Close the pipe, blocking readers will receive end of input once all data was read.
+    #[inline(always)]
+    pub fn close(&self) {
+        self.shared.close();
+    }
 }
 impl Write for PipeWriter {
     #[inline]
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        let mut data = self.data.lock();
-        data.extend(buf.iter());
+        let mut state = self.shared.state.lock();
+        if state.closed {
+            return Err(IOError::from(ErrorKind::BrokenPipe));
+        }
+        state.data.extend(buf.iter());
+        self.shared.available.notify_all();
         Ok(buf.len())
     }
     #[inline(always)]
@@ -70,3 +141,61 @@ impl Write for PipeWriter {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn blocking_read_until_closed() {
+        let pipe = Pipe::new();
+        let mut reader = pipe.blocking_reader();
+        let mut writer = pipe.writer();
+        let producer = thread::spawn(move || {
+            for chunk in 0..100u8 {
+                writer.write_all(&[chunk; 10]).unwrap();
+                thread::yield_now();
+            }
+            writer.close();
+        });
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        producer.join().unwrap();
+        assert_eq!(data.len(), 1000);
+        assert!(data.chunks(10).zip(0..).all(|(chunk, i)| chunk == [i; 10]));
+        assert!(pipe.is_closed());
+        assert_eq!(
+            pipe.writer().write(b"late").unwrap_err().kind(),
+            ErrorKind::BrokenPipe
+        );
+    }
+
+    #[test]
+    fn drain_into_reused_buffer() {
+        let pipe = Pipe::new();
+        let mut reader = pipe.reader();
+        let mut writer = pipe.writer();
+        let mut buffer = String::new();
+        assert_eq!(reader.drain_to_string(&mut buffer), 0);
+        assert!(buffer.is_empty());
+
+        writer.write_all(b"awa").unwrap();
+        writer.write_all(b" awawa\n").unwrap();
+        assert_eq!(reader.drain_to_string(&mut buffer), 10);
+        assert_eq!(buffer, "awa awawa\n");
+        let capacity = buffer.capacity();
+
+        buffer.clear();
+        writer.write_all(b"wa").unwrap();
+        assert_eq!(reader.drain_to_string(&mut buffer), 2);
+        assert_eq!(buffer, "wa");
+        assert_eq!(buffer.capacity(), capacity);
+        // NOTE: draining appends, so the previous contents are kept without clearing
+        writer.write_all(&[b'!', 0xff]).unwrap();
+        assert_eq!(reader.drain_to_string(&mut buffer), 2);
+        assert_eq!(buffer, "wa!\u{fffd}");
+        assert_eq!(reader.drain_to_string(&mut buffer), 0);
+    }
+}
@@ -1,41 +1,94 @@
+use std::collections::VecDeque;
+
 use ratatui::{prelude::*, widgets::*};
 
 #[derive(Debug, Clone)]
 pub struct MirrorIO {
-    lines: Vec<String>,
+    lines: VecDeque<String>,
+    max_lines: Option<usize>,
     scroll: u16,
+    /// A `'\r'` was the last character pushed.
+    carriage_return: bool,
 }
 impl MirrorIO {
     #[inline(always)]
     pub const fn new() -> Self {
         Self {
-            lines: Vec::new(),
+            lines: VecDeque::new(),
+            max_lines: None,
             scroll: 0,
+            carriage_return: false,
         }
     }
+    /// Create a widget that only keeps the last `max_lines` lines.
+    #[inline(always)]
+    pub const fn with_max_lines(max_lines: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            max_lines: Some(max_lines),
+            scroll: 0,
+            carriage_return: false,
+        }
+    }
+    #[inline(always)]
+    pub const fn max_lines(&self) -> Option<usize> {
+        self.max_lines
+    }
+    /// Change the line limit, `None` will keep all lines.
+    /// Oldest lines are dropped when the new limit is already exceeded.
     #[inline]
-    pub fn push(&mut self, str: impl AsRef<str>) {
-        let mut line = if self.lines.is_empty() {
-            self.lines.push(String::new());
-            &mut self.lines[0]
-        } else {
-            self.lines.last_mut().unwrap()
+    pub fn set_max_lines(&mut self, max_lines: Option<usize>) {
+        self.max_lines = max_lines;
+        self.truncate();
+    }
+    #[inline]
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().map(String::as_str)
+    }
+    #[inline]
+    fn truncate(&mut self) {
+        let Some(max_lines) = self.max_lines else {
+            return;
         };
+        let excess = self.lines.len().saturating_sub(max_lines.max(1));
+        if excess == 0 {
+            return;
+        }
+        self.lines.drain(..excess);
+        self.scroll = self.scroll.saturating_sub(excess as u16);
+    }
+    /// Append text, starting a new line at every `'\n'`.
+    /// A `'\r'` followed by `'\n'` is part of the line break,
+    /// otherwise it resets the line so the following text overwrites it (like in a terminal).
+    #[inline]
+    pub fn push(&mut self, str: impl AsRef<str>) {
+        if self.lines.is_empty() {
+            self.lines.push_back(String::new());
+        }
         for char in str.as_ref().chars() {
+            let carriage_return = std::mem::replace(&mut self.carriage_return, false);
             match char {
-                '\n' => {
-                    self.lines.push(String::new());
-                    line = self.lines.last_mut().unwrap();
+                '\n' => self.lines.push_back(String::new()),
+                '\r' => self.carriage_return = true,
+                char => {
+                    // SAFETY: unwrap: lines can't be empty here
+                    let line = self.lines.back_mut().unwrap();
+                    if carriage_return {
+                        line.clear();
+                    }
+                    line.push(char);
                 }
-                char => line.push(char),
             }
         }
+        self.truncate();
         self.scroll = 0;
     }
     #[inline]
     pub fn push_line(&mut self, str: impl AsRef<str>) {
         self.push(str);
-        self.lines.push(String::new());
+        self.lines.push_back(String::new());
+        self.carriage_return = false;
+        self.truncate();
     }
     pub fn scroll(&mut self, direction: ScrollDirection) {
         self.scroll = match direction {
@@ -72,3 +125,36 @@ impl WidgetRef for MirrorIO {
             .render(layout[1], buf);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_lines_keeps_tail() {
+        let mut io = MirrorIO::with_max_lines(3);
+        for i in 0..10 {
+            io.push_line(i.to_string());
+        }
+        // NOTE: the last line is the empty one started by the last push_line
+        assert_eq!(io.lines().collect::<Vec<_>>(), ["8", "9", ""]);
+        io.set_max_lines(Some(1));
+        assert_eq!(io.lines().count(), 1);
+        io.set_max_lines(None);
+        io.push("a\nb\nc\n");
+        assert_eq!(io.lines().count(), 4);
+    }
+
+    #[test]
+    fn carriage_returns() {
+        let mut io = MirrorIO::new();
+        io.push("a\r\nb");
+        assert_eq!(io.lines().collect::<Vec<_>>(), ["a", "b"]);
+        io.push("c\rd");
+        assert_eq!(io.lines().collect::<Vec<_>>(), ["a", "d"]);
+        // NOTE: a carriage return at the end of one push still applies to the next one
+        io.push("\r");
+        io.push("\ne");
+        assert_eq!(io.lines().collect::<Vec<_>>(), ["a", "d", "e"]);
+    }
+}
@@ -66,6 +66,11 @@ impl<'a, A: Abyss + Display> View<'a, A> {
             ScrollDirection::Backward => self.active_tab.prev(),
         };
     }
+    /// Update cached layout for a view of `height` lines.
+    #[inline]
+    pub fn resize(&mut self, height: u16) {
+        self.program.resize(height as usize);
+    }
     #[inline]
     pub fn scroll(&mut self, direction: ScrollDirection) {
         match self.active_tab {
@@ -97,8 +102,9 @@ impl<'a, A: Abyss + Display + 'a> StatefulWidgetRef for View<'a, A> {
             .render(inner[0].inner(Margin::new(2, 0)), buf);
         let mut content = inner[1];
         content.x += 1;
-        content.width -= 2;
-        content.height -= 1;
+        // NOTE: the terminal can be resized to be arbitrarily small
+        content.width = content.width.saturating_sub(2);
+        content.height = content.height.saturating_sub(1);
         match self.active_tab {
             Tab::IO => self.io.render_ref(content, buf),
             Tab::Abyss => self.abyss.render_ref(content, buf, state.abyss),
@@ -1,6 +1,6 @@
 use std::collections::HashSet;
 
-use awa_core::Program;
+use awa_core::{AwaSCII, AwaTism, Program};
 use ratatui::{prelude::*, widgets::*};
 use style::Styled;
 
@@ -10,6 +10,8 @@ pub struct ProgramWindow<'a> {
     pc: usize,
     scroll: usize,
     line_digits: usize,
+    context: usize,
+    height: usize,
 }
 impl<'a> ProgramWindow<'a> {
     #[inline]
@@ -19,16 +21,52 @@ impl<'a> ProgramWindow<'a> {
             pc: 0,
             scroll: 0,
             line_digits: (program.len() as f64).log10().trunc() as usize + 1,
+            context: Self::DEFAULT_CONTEXT,
+            height: usize::MAX,
         }
     }
+    /// Lines shown above the current instruction by default.
+    pub const DEFAULT_CONTEXT: usize = 5;
+    /// Show `context` lines above the current instruction after it changes,
+    /// as far as the height allows.
+    #[inline(always)]
+    pub fn with_context(mut self, context: usize) -> Self {
+        self.context = context;
+        self
+    }
+    #[inline(always)]
+    pub fn context(&self) -> usize {
+        self.context
+    }
+    #[inline(always)]
+    pub fn set_context(&mut self, context: usize) {
+        self.context = context;
+    }
+    /// Longest rendered instruction is `blo 63 '\n'`.
     #[inline(always)]
     pub fn min_width(&self) -> usize {
-        self.line_digits + 9
+        self.line_digits + 12
+    }
+    /// Render instruction, annotating blown values with their AwaSCII character.
+    #[inline]
+    fn format(awatism: &AwaTism, awascii: Option<AwaSCII>) -> String {
+        match awascii {
+            Some(awascii) => format!("{} '{}'", awatism, awascii.to_ascii().escape_ascii()),
+            None => awatism.to_string(),
+        }
     }
     #[inline(always)]
     pub fn set_pc(&mut self, pc: usize) {
         self.pc = pc;
-        self.scroll = pc.saturating_sub(5);
+        let context = self.context.min(self.height.saturating_sub(1));
+        self.scroll = pc.saturating_sub(context);
+    }
+    /// Clamp the scroll position so the current instruction stays visible in `height` lines.
+    #[inline]
+    pub fn resize(&mut self, height: usize) {
+        self.height = height;
+        let first = (self.pc + 1).saturating_sub(height.max(1));
+        self.scroll = self.scroll.clamp(first, self.pc);
     }
     #[inline]
     pub fn scroll(&mut self, direction: ScrollDirection) {
@@ -51,11 +89,10 @@ impl<'a> StatefulWidgetRef for ProgramWindow<'a> {
     fn render_ref(&self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
         Text::from_iter(
             self.program
-                .iter()
-                .enumerate()
+                .annotated()
                 .skip(self.scroll)
                 .take(area.height as usize)
-                .map(|(pc, awatism)| {
+                .map(|(pc, awatism, awascii)| {
                     let mut number = (pc + 1).to_string();
                     for _ in number.len()..self.line_digits {
                         number.push(' ')
@@ -65,7 +102,7 @@ impl<'a> StatefulWidgetRef for ProgramWindow<'a> {
                     } else {
                         Self::NUMBER_STYLE
                     });
-                    let instruction = awatism.to_string().set_style(if pc == self.pc {
+                    let instruction = Self::format(awatism, awascii).set_style(if pc == self.pc {
                         Self::CENTER_STYLE
                     } else {
                         Self::AWATISM_STYLE
@@ -76,3 +113,54 @@ impl<'a> StatefulWidgetRef for ProgramWindow<'a> {
         .render(area, buf)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use awa_asm::parse_program;
+
+    use super::*;
+
+    #[test]
+    fn annotates_awascii_values() {
+        let program = parse_program("blo 2\nblo 63\nblo -1\npop").unwrap();
+        let window = ProgramWindow::new(&program);
+        let area = Rect::new(0, 0, window.min_width() as u16, 4);
+        let mut buffer = Buffer::empty(area);
+        window.render_ref(area, &mut buffer, &mut HashSet::new());
+        let lines = buffer
+            .content
+            .chunks(area.width as usize)
+            .map(|line| line.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "1 blo 2 'a'  ",
+                "2 blo 63 '\\n'",
+                "3 blo -1     ",
+                "4 pop        ",
+            ]
+        );
+    }
+
+    #[test]
+    fn scroll_respects_context() {
+        let program = parse_program(&"pop\n".repeat(40)).unwrap();
+        let mut window = ProgramWindow::new(&program);
+        window.set_pc(20);
+        assert_eq!(window.scroll, 20 - ProgramWindow::DEFAULT_CONTEXT);
+        window.set_context(10);
+        window.set_pc(20);
+        assert_eq!(window.scroll, 10);
+        window.set_pc(3);
+        assert_eq!(window.scroll, 0);
+        let mut window = ProgramWindow::new(&program).with_context(0);
+        window.set_pc(20);
+        assert_eq!(window.scroll, 20);
+        // NOTE: the context is limited by the height, so the current instruction stays visible
+        let mut window = ProgramWindow::new(&program).with_context(30);
+        window.resize(8);
+        window.set_pc(20);
+        assert_eq!(window.scroll, 13);
+    }
+}
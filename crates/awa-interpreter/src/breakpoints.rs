@@ -0,0 +1,120 @@
+use std::collections::HashSet;
+
+use awa_core::Abyss;
+
+/// Callback for [`BreakpointController::set_on_hit`].
+type OnHit<'a, A> = Box<dyn FnMut(usize, &A) + 'a>;
+
+/// Set of locations to stop a running program at, with an optional callback when one is hit.
+/// Use [`Iter::run_to_breakpoint`](crate::Iter::run_to_breakpoint) to run until one is hit.
+pub struct BreakpointController<'a, A: Abyss> {
+    locations: HashSet<usize>,
+    on_hit: Option<OnHit<'a, A>>,
+}
+impl<'a, A: Abyss> BreakpointController<'a, A> {
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            locations: HashSet::new(),
+            on_hit: None,
+        }
+    }
+    /// Call `on_hit` with the location and the abyss every time a breakpoint is hit.
+    #[inline]
+    pub fn with_on_hit(mut self, on_hit: impl FnMut(usize, &A) + 'a) -> Self {
+        self.set_on_hit(on_hit);
+        self
+    }
+    #[inline]
+    pub fn set_on_hit(&mut self, on_hit: impl FnMut(usize, &A) + 'a) {
+        self.on_hit = Some(Box::new(on_hit));
+    }
+    #[inline(always)]
+    pub fn locations(&self) -> &HashSet<usize> {
+        &self.locations
+    }
+    #[inline(always)]
+    pub fn locations_mut(&mut self) -> &mut HashSet<usize> {
+        &mut self.locations
+    }
+    #[inline(always)]
+    pub fn contains(&self, pc: usize) -> bool {
+        self.locations.contains(&pc)
+    }
+    /// Returns `false` when there already was a breakpoint at `pc`.
+    #[inline(always)]
+    pub fn insert(&mut self, pc: usize) -> bool {
+        self.locations.insert(pc)
+    }
+    /// Returns `false` when there was no breakpoint at `pc`.
+    #[inline(always)]
+    pub fn remove(&mut self, pc: usize) -> bool {
+        self.locations.remove(&pc)
+    }
+    /// Remove the breakpoint at `pc` if there is one, otherwise add it.
+    /// Returns `true` when there is a breakpoint at `pc` afterwards.
+    #[inline]
+    pub fn toggle(&mut self, pc: usize) -> bool {
+        if self.remove(pc) {
+            false
+        } else {
+            self.insert(pc)
+        }
+    }
+    /// Returns `true` when there is a breakpoint at `pc`, calling the callback in that case.
+    #[inline]
+    pub fn hit(&mut self, pc: usize, abyss: &A) -> bool {
+        if !self.locations.contains(&pc) {
+            return false;
+        }
+        if let Some(on_hit) = &mut self.on_hit {
+            on_hit(pc, abyss);
+        }
+        true
+    }
+}
+impl<'a, A: Abyss> Default for BreakpointController<'a, A> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<'a, A: Abyss> std::fmt::Debug for BreakpointController<'a, A> {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BreakpointController")
+            .field("locations", &self.locations)
+            .field("on_hit", &self.on_hit.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use awa_abyss::linked;
+    use awa_asm::parse_program;
+    use fallible_iterator::FallibleIterator;
+
+    use super::*;
+    use crate::Interpreter;
+
+    #[test]
+    fn on_hit_fires_at_breakpoints() {
+        let program = parse_program("blo 1\nblo 2\nblo 3\npop\ntrm").unwrap();
+        let mut interpreter =
+            Interpreter::new(linked::Abyss::<isize>::default(), &[][..], Vec::new());
+        let mut hits = Vec::new();
+        let mut breakpoints = BreakpointController::new()
+            .with_on_hit(|pc, abyss: &linked::Abyss<isize>| hits.push((pc, abyss.depth())));
+        breakpoints.insert(1);
+        breakpoints.insert(3);
+        let mut iter = interpreter.run(&program);
+        assert_eq!(iter.run_to_breakpoint(&mut breakpoints).unwrap(), Some(1));
+        iter.next().unwrap();
+        assert_eq!(iter.run_to_breakpoint(&mut breakpoints).unwrap(), Some(3));
+        iter.next().unwrap();
+        assert_eq!(iter.run_to_breakpoint(&mut breakpoints).unwrap(), None);
+        drop(breakpoints);
+        assert_eq!(hits, [(1, 1), (3, 3)]);
+    }
+}
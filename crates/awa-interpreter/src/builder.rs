@@ -0,0 +1,95 @@
+use std::io::{BufRead, Write};
+
+use awa_core::{Abyss, AwaSCIITable};
+
+use crate::{ArithmeticMode, EofPolicy, FlushPolicy, InputRadix, Interpreter, ReadValidation};
+
+/// Configures an [`Interpreter`] before creating it.
+#[derive(Debug)]
+pub struct InterpreterBuilder<A: Abyss, I: BufRead, O: Write> {
+    interpreter: Interpreter<A, I, O>,
+}
+impl<A: Abyss, I: BufRead, O: Write> InterpreterBuilder<A, I, O> {
+    #[inline(always)]
+    pub const fn new(abyss: A, input: I, output: O) -> Self {
+        Self {
+            interpreter: Interpreter::new(abyss, input, output),
+        }
+    }
+    /// Use a custom character table for `prn` and `red`.
+    #[inline(always)]
+    pub fn table(mut self, table: AwaSCIITable) -> Self {
+        self.interpreter.set_table(table);
+        self
+    }
+    /// Decide how `red` handles characters that are not valid AwaSCII.
+    #[inline(always)]
+    pub fn validation(mut self, validation: ReadValidation) -> Self {
+        self.interpreter.set_validation(validation);
+        self
+    }
+    /// Decide which base `r3d` reads numbers in.
+    #[inline(always)]
+    pub fn input_radix(mut self, radix: InputRadix) -> Self {
+        self.interpreter.set_input_radix(radix);
+        self
+    }
+    /// Drop the line ending of lines read by `red`.
+    #[inline(always)]
+    pub fn trim_read_newline(mut self, trim_read_newline: bool) -> Self {
+        self.interpreter.set_trim_read_newline(trim_read_newline);
+        self
+    }
+    /// Decide what `red` and `r3d` do when there is no more input.
+    #[inline(always)]
+    pub fn eof_policy(mut self, eof_policy: EofPolicy) -> Self {
+        self.interpreter.set_eof_policy(eof_policy);
+        self
+    }
+    /// Decide how arithmetic handles results that do not fit into a bubble.
+    #[inline(always)]
+    pub fn arithmetic_mode(mut self, arithmetic_mode: ArithmeticMode) -> Self {
+        self.interpreter.set_arithmetic_mode(arithmetic_mode);
+        self
+    }
+    /// Fail instead of running more than `max_steps` instructions.
+    #[inline(always)]
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.interpreter.set_max_steps(Some(max_steps));
+        self
+    }
+    /// Fail instead of printing more than `max_output` bytes.
+    #[inline(always)]
+    pub fn max_output(mut self, max_output: usize) -> Self {
+        self.interpreter.set_max_output(Some(max_output));
+        self
+    }
+    /// Decide when the output is flushed after printing.
+    #[inline(always)]
+    pub fn flush(mut self, flush: FlushPolicy) -> Self {
+        self.interpreter.set_flush(flush);
+        self
+    }
+    /// Record side effects into the event log while running.
+    #[inline(always)]
+    pub fn record(mut self, record: bool) -> Self {
+        self.interpreter.set_record(record);
+        self
+    }
+    /// Keep track of the highest abyss depth while running.
+    #[inline(always)]
+    pub fn track_depth(mut self, track_depth: bool) -> Self {
+        self.interpreter.set_track_depth(track_depth);
+        self
+    }
+    /// Print double bubbles with `pr1` as bracketed groups.
+    #[inline(always)]
+    pub fn structured_print(mut self, structured_print: bool) -> Self {
+        self.interpreter.set_structured_print(structured_print);
+        self
+    }
+    #[inline(always)]
+    pub fn build(self) -> Interpreter<A, I, O> {
+        self.interpreter
+    }
+}
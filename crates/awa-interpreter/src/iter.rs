@@ -7,7 +7,7 @@ use awa_core::{Abyss, AwaTism, Program};
 pub use fallible_iterator::FallibleIterator;
 use num_traits::cast;
 
-use crate::{ContinueAt, Error, Interpreter};
+use crate::{BreakpointController, ContinueAt, Error, Interpreter};
 
 #[inline]
 pub fn run_single<A: Abyss, I: BufRead, O: Write>(
@@ -16,7 +16,16 @@ pub fn run_single<A: Abyss, I: BufRead, O: Write>(
     labels: &[Option<NonZero<usize>>],
     pc: usize,
 ) -> Result<Option<usize>, Error> {
-    match interpreter.next(awatism) {
+    if interpreter.max_steps == Some(interpreter.steps) {
+        return Err(Error::StepLimitExceeded(interpreter.steps));
+    }
+    interpreter.steps += 1;
+    interpreter.last = Some((pc, awatism));
+    let result = interpreter.next(awatism);
+    if interpreter.track_depth {
+        interpreter.max_depth = interpreter.max_depth.max(interpreter.abyss.depth());
+    }
+    match result {
         Ok(ContinueAt::Next) => Ok(Some(pc + 1)),
         Ok(ContinueAt::SkipNext) => Ok(Some(pc + 2)),
         Ok(ContinueAt::None) => Ok(None),
@@ -31,11 +40,76 @@ pub fn run_single<A: Abyss, I: BufRead, O: Write>(
     }
 }
 
+/// Represents the reason a program stopped executing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Exit {
+    /// Program executed a [`AwaTism::Terminate`].
+    Terminated,
+    /// Program continued past its last instruction,
+    /// this includes skipping past the end after a comparison.
+    EndOfProgram,
+}
+
 #[derive(Debug)]
 pub struct Iter<'a, A: Abyss, I: BufRead, O: Write> {
     pub(crate) interpreter: &'a mut Interpreter<A, I, O>,
     pub(crate) program: &'a Program,
     pub(crate) pc: Option<usize>,
+    pub(crate) exit: Option<Exit>,
+}
+impl<'a, A: Abyss, I: BufRead, O: Write> Iter<'a, A, I, O> {
+    /// Returns how the program stopped, will be `None` while it is still running.
+    #[inline(always)]
+    pub fn exit(&self) -> Option<Exit> {
+        self.exit
+    }
+    #[inline(always)]
+    pub fn interpreter(&self) -> &Interpreter<A, I, O> {
+        self.interpreter
+    }
+    /// Returns the location of the next instruction to execute.
+    #[inline(always)]
+    pub fn pc(&self) -> Option<usize> {
+        self.pc
+    }
+    /// Execute up to `n` instructions.
+    /// Returns the number of executed instructions, which is less than `n` when the program ended.
+    #[inline]
+    pub fn advance(&mut self, n: usize) -> Result<usize, Error> {
+        for i in 0..n {
+            if FallibleIterator::next(self)?.is_none() {
+                return Ok(i);
+            }
+        }
+        Ok(n)
+    }
+    /// Execute instructions until `should_stop` returns `true` for the next instruction.
+    /// Returns the location of the instruction that was stopped at (without executing it),
+    /// or `None` when the program ended before that.
+    /// NOTE: calling this again will stop at the same location, step past it with [`FallibleIterator::next`] first.
+    #[inline]
+    pub fn run_until(
+        &mut self,
+        mut should_stop: impl FnMut(usize, &AwaTism, &A) -> bool,
+    ) -> Result<Option<usize>, Error> {
+        while let Some(pc) = self.pc {
+            if let Some(awatism) = self.program.get(pc) {
+                if should_stop(pc, awatism, &self.interpreter.abyss) {
+                    return Ok(Some(pc));
+                }
+            }
+            FallibleIterator::next(self)?;
+        }
+        Ok(None)
+    }
+    /// Same as [`Iter::run_until`], stopping at the breakpoints of `breakpoints`.
+    #[inline]
+    pub fn run_to_breakpoint(
+        &mut self,
+        breakpoints: &mut BreakpointController<'_, A>,
+    ) -> Result<Option<usize>, Error> {
+        self.run_until(|pc, _, abyss| breakpoints.hit(pc, abyss))
+    }
 }
 impl<'a, A, I, O> FallibleIterator for Iter<'a, A, I, O>
 where
@@ -51,9 +125,17 @@ where
             return Ok(None);
         };
         let Some(&awatism) = self.program.get(current) else {
+            (self.pc, self.exit) = (None, Some(Exit::EndOfProgram));
             return Ok(None);
         };
-        self.pc = run_single(self.interpreter, awatism, self.program.labels(), current)?;
+        self.pc = match run_single(self.interpreter, awatism, self.program.labels(), current)? {
+            None => {
+                self.exit = Some(Exit::Terminated);
+                None
+            }
+            // NOTE: a comparison as the last instruction would skip more than one past the end
+            Some(next) => Some(next.min(self.program.len())),
+        };
         Ok(Some((current, awatism)))
     }
 }
@@ -72,6 +154,10 @@ impl<'a> Cursor<'a> {
         }
     }
     #[inline(always)]
+    pub fn program(&self) -> &'a Program {
+        self.program
+    }
+    #[inline(always)]
     pub fn len(&self) -> usize {
         self.program.len()
     }
@@ -86,10 +172,10 @@ impl<'a> Cursor<'a> {
         I: BufRead,
         O: Write,
     {
-        let Some((pc, awatism)) = self.current() else {
+        let Some((pc, _)) = self.current() else {
             return Ok(false);
         };
-        self.pc = run_single(interpreter, awatism, self.program.labels(), pc)?;
+        self.pc = interpreter.step(self.program, pc)?;
         Ok(true)
     }
     #[inline]
@@ -98,3 +184,67 @@ impl<'a> Cursor<'a> {
         self.program.get(pc).cloned().map(|awatism| (pc, awatism))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use awa_abyss::linked::Abyss;
+    use awa_asm::parse_program;
+    use awa_core::Abyss as _;
+
+    use super::*;
+
+    fn exit(src: &str) -> Option<Exit> {
+        let program = parse_program(src).unwrap();
+        let mut interpreter = Interpreter::new(Abyss::<isize>::default(), &[][..], Vec::new());
+        let mut iter = interpreter.run(&program);
+        assert_eq!(iter.exit(), None);
+        while iter.next().unwrap().is_some() {}
+        iter.exit()
+    }
+
+    #[test]
+    fn terminate_and_end_of_program() {
+        assert_eq!(exit("blo 1\ntrm\nblo 2"), Some(Exit::Terminated));
+        assert_eq!(exit("blo 1\nblo 2"), Some(Exit::EndOfProgram));
+        assert_eq!(exit(""), Some(Exit::EndOfProgram));
+    }
+
+    #[test]
+    fn advance_steps() {
+        let program = parse_program(&"blo 1\n".repeat(10)).unwrap();
+        let mut interpreter = Interpreter::new(Abyss::<isize>::default(), &[][..], Vec::new());
+        let mut iter = interpreter.run(&program);
+        assert_eq!(iter.advance(3).unwrap(), 3);
+        assert_eq!(iter.pc(), Some(3));
+        assert_eq!(iter.advance(0).unwrap(), 0);
+        assert_eq!(iter.advance(10).unwrap(), 7);
+        assert_eq!(iter.pc(), None);
+        assert_eq!(iter.advance(1).unwrap(), 0);
+    }
+
+    #[test]
+    fn trailing_comparison_ends_program() {
+        assert_eq!(exit("blo 1\nblo 1\neql"), Some(Exit::EndOfProgram));
+        assert_eq!(exit("blo 1\nblo 2\neql"), Some(Exit::EndOfProgram));
+        assert_eq!(exit("blo 1\nblo 2\nlss\npr1"), Some(Exit::EndOfProgram));
+
+        let program = parse_program("blo 1\nblo 2\neql").unwrap();
+        let mut interpreter = Interpreter::new(Abyss::<isize>::default(), &[][..], Vec::new());
+        let mut iter = interpreter.run(&program);
+        assert_eq!(iter.advance(3).unwrap(), 3);
+        assert_eq!(iter.advance(1).unwrap(), 0);
+    }
+
+    #[test]
+    fn run_until_depth_exceeds() {
+        let program = parse_program("blo 1\nblo 2\nblo 3\nblo 4\ntrm").unwrap();
+        let mut interpreter = Interpreter::new(Abyss::<isize>::default(), &[][..], Vec::new());
+        let mut iter = interpreter.run(&program);
+        let deep = |_: usize, _: &AwaTism, abyss: &Abyss<isize>| abyss.depth() > 2;
+        assert_eq!(iter.run_until(deep).unwrap(), Some(3));
+        assert_eq!(iter.run_until(deep).unwrap(), Some(3));
+        iter.next().unwrap();
+        assert_eq!(iter.run_until(|_, _, _| false).unwrap(), None);
+        assert_eq!(iter.exit(), Some(Exit::Terminated));
+    }
+}
@@ -1,18 +1,29 @@
 #![feature(const_mut_refs)]
 
+mod breakpoints;
+mod builder;
 mod iter;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub use breakpoints::*;
+pub use builder::*;
 pub use iter::*;
 
 use std::{
-    fmt::{Error as FmtError, Write as FmtWrite},
+    cell::Cell,
+    convert::Infallible,
     io::{BufRead, Error as IOError, Write},
-    ops::{Add, Div, Mul, Rem, Sub},
 };
 
-use num_traits::{cast, ConstOne};
+use num_traits::{
+    cast, CheckedAdd, CheckedDiv, CheckedMul, CheckedRem, CheckedSub, ConstOne, SaturatingAdd,
+    SaturatingMul, SaturatingSub, WrappingAdd, WrappingMul, WrappingSub, Zero,
+};
 use thiserror::Error;
 
-use awa_core::{u5, Abyss, AwaSCII, AwaTism, Error as CoreError, Program, Value};
+use awa_core::{
+    u5, Abyss, AwaSCII, AwaSCIITable, AwaTism, Error as CoreError, Nested, Program, Value,
+};
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -25,11 +36,23 @@ pub enum Error {
     #[error("abyss is full")]
     NoSpace,
     #[error(transparent)]
-    FmtError(#[from] FmtError),
-    #[error(transparent)]
     CoreError(#[from] CoreError),
     #[error("label with id {0} not found")]
     UnknownLabel(u5),
+    #[error("input character {0:#04x} is not valid AwaSCII")]
+    InvalidInputChar(u8),
+    #[error("size of the top bubble does not fit into a bubble")]
+    CountOverflow,
+    #[error("program did not end within {0} steps")]
+    StepLimitExceeded(usize),
+    #[error("reached the end of input")]
+    EndOfInput,
+    #[error("result does not fit into a bubble")]
+    ArithmeticOverflow,
+    #[error("division by zero")]
+    DivisionByZero,
+    #[error("program printed more than {0} bytes")]
+    OutputLimitExceeded(usize),
 }
 
 /// Represents location of next instruction to execute.
@@ -40,44 +63,207 @@ pub enum ContinueAt {
     /// Execute next instruction in order.
     Next,
     /// Skip over next instruction, executing the one after that.
+    /// Skipping past the last instruction ends the program like running past the last instruction.
     SkipNext,
     /// Jump to a label.
     Label(u5),
 }
 
+/// Decides what happens to input characters that can not be represented in AwaSCII.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ReadValidation {
+    /// Silently drop invalid characters.
+    #[default]
+    Skip,
+    /// Fail with [`Error::InvalidInputChar`].
+    Error,
+}
+
+/// Decides which base `r3d` uses for reading numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum InputRadix {
+    /// Always read digits in this base, which has to be in `2..=36`.
+    Fixed(u32),
+    /// Read decimal digits, unless they start with `0x`, `0o` or `0b`.
+    Prefixed,
+}
+impl Default for InputRadix {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::Fixed(10)
+    }
+}
+
+/// Decides what `red` and `r3d` do when there is no more input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EofPolicy {
+    /// `red` blows nothing and `r3d` fails with [`Error::NoNumber`].
+    #[default]
+    Ignore,
+    /// Blow a single zero, like reading an empty line with `red`.
+    Zero,
+    /// Fail with [`Error::EndOfInput`].
+    Error,
+}
+
+/// Decides what happens when `4dd`, `sub`, `mul` or `div` produce a value that does not fit into a bubble.
+/// Dividing by zero fails with [`Error::DivisionByZero`] in every mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ArithmeticMode {
+    /// Wrap around at the bounds of [`Abyss::Value`], so dividing the minimum by -1 results in the minimum.
+    #[default]
+    Wrapping,
+    /// Clamp to the bounds of [`Abyss::Value`], so dividing the minimum by -1 results in the maximum.
+    Saturating,
+    /// Fail with [`Error::ArithmeticOverflow`].
+    Checked,
+}
+
+/// Decides when the output is flushed after `prn` and `pr1`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FlushPolicy {
+    /// Flush after every print.
+    #[default]
+    Always,
+    /// Flush only when the printed text contains a newline.
+    OnNewline,
+    /// Leave flushing to the writer and [`Interpreter::finish`].
+    Never,
+}
+
+/// Represents a side effect that happened while running a program.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Event {
+    /// Bytes written to the output.
+    Printed(Vec<u8>),
+    /// Line read from the input, including the line break (empty at the end of input).
+    ReadLine(Vec<u8>),
+    /// Program executed a [`AwaTism::Terminate`].
+    Terminated,
+}
+
 /// Convert ASCII string to AwaSCII string.
 #[inline]
 pub fn parse_awascii_input(src: impl AsRef<str>, out: &mut Vec<AwaSCII>) {
+    parse_awascii_input_with(src, out, &AwaSCIITable::DEFAULT)
+}
+/// Convert ASCII string to AwaSCII string using a custom character table.
+#[inline]
+pub fn parse_awascii_input_with(
+    src: impl AsRef<str>,
+    out: &mut Vec<AwaSCII>,
+    table: &AwaSCIITable,
+) {
     for char in src.as_ref().chars() {
         if !char.is_ascii() {
             continue;
         };
-        let Some(awascii) = AwaSCII::from_ascii(char as u8) else {
+        let Some(awascii) = table.from_ascii(char as u8) else {
             continue;
         };
         out.push(awascii);
     }
 }
-/// Convert ASCII string to number.
+/// Convert ASCII string to AwaSCII string using a custom character table.
+/// Returns the first byte that can not be represented as an error.
+#[inline]
+pub fn try_parse_awascii_input_with(
+    src: impl AsRef<str>,
+    out: &mut Vec<AwaSCII>,
+    table: &AwaSCIITable,
+) -> Result<(), u8> {
+    for byte in src.as_ref().bytes() {
+        out.push(table.from_ascii(byte).ok_or(byte)?);
+    }
+    Ok(())
+}
+/// Convert ASCII string to number, stopping at the first non-digit.
+/// A leading `-` is accepted for signed types, it goes before the prefix when using [`InputRadix::Prefixed`].
+/// Returns `None` when no digit was found or the number does not fit into `T`.
+///
+/// # Panics
+/// When using [`InputRadix::Fixed`] with a base outside of `2..=36`.
 #[inline(always)]
-pub fn parse_number_input<T: Value>(src: impl AsRef<str>) -> Option<T> {
+pub fn parse_number_input<T: Value>(src: impl AsRef<str>, radix: InputRadix) -> Option<T> {
     let mut result = T::zero();
-    // SAFETY: unwrap: every number type can hold 10
-    let ten = cast::<_, T>(10).unwrap();
     let src = src.as_ref();
+    let (negative, src) = match src.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, src),
+    };
+    let (radix, src) = match radix {
+        InputRadix::Fixed(radix) => (radix, src),
+        InputRadix::Prefixed => match src.get(..2) {
+            Some("0x") => (16, &src[2..]),
+            Some("0o") => (8, &src[2..]),
+            Some("0b") => (2, &src[2..]),
+            _ => (10, src),
+        },
+    };
+    // SAFETY: unwrap: every number type can hold 36
+    let base = cast::<_, T>(radix).unwrap();
+    let mut digits = 0;
     for chr in src.chars() {
-        match chr {
-            '0'..='9' => {
-                let digit = (chr as u8) - b'0';
-                // SAFETY: unwrap: every number type can hold 10
-                result = ten * result + cast(digit).unwrap();
-            }
-            _ => return Some(result),
-        }
+        let Some(digit) = chr.to_digit(radix) else {
+            break;
+        };
+        // SAFETY: unwrap: digits are smaller than the base
+        let digit = cast::<_, T>(digit).unwrap();
+        let shifted = result.checked_mul(&base)?;
+        // NOTE: accumulating towards the sign keeps `T::min_value()` in range for signed types
+        result = if negative {
+            shifted.checked_sub(&digit)?
+        } else {
+            shifted.checked_add(&digit)?
+        };
+        digits += 1;
+    }
+    if digits == 0 {
+        return None;
     }
     Some(result)
 }
 
+/// Append the decimal representation of `value` to `out`, this is the inverse of [`parse_number_input`].
+#[inline]
+pub fn push_number<T: Value>(out: &mut String, value: T) {
+    // SAFETY: unwrap: every number type can hold 10
+    let base = cast::<_, T>(10).unwrap();
+    // NOTE: enough for every primitive integer, digits are collected from the back
+    let (mut digits, mut len, mut rest) = ([0u8; 40], 0, value);
+    loop {
+        // NOTE: the remainder is negative for negative values, so the minimum never has to be negated
+        // SAFETY: unwrap: the remainder is in -9..=9
+        let digit = cast::<_, i8>(rest % base).unwrap().unsigned_abs();
+        (digits[len], len, rest) = (b'0' + digit, len + 1, rest / base);
+        if rest.is_zero() {
+            break;
+        }
+    }
+    if value < T::zero() {
+        out.push('-');
+    }
+    out.extend(digits[..len].iter().rev().map(|digit| *digit as char));
+}
+
+/// Run `program` for at most `max_steps` instructions, reading from `input`.
+/// Returns everything the program printed, even when it was stopped early by an error or the step limit.
+pub fn run_capturing_limited<A: Abyss>(
+    abyss: A,
+    program: &Program,
+    input: impl BufRead,
+    max_steps: usize,
+) -> (String, Result<(), Error>) {
+    let mut interpreter = Interpreter::new(abyss, input, Vec::new());
+    let mut iter = interpreter.run(program);
+    let result = iter.advance(max_steps).and_then(|_| match iter.pc() {
+        Some(pc) if pc < program.len() => Err(Error::StepLimitExceeded(max_steps)),
+        _ => Ok(()),
+    });
+    let output = String::from_utf8_lossy(&interpreter.output).into_owned();
+    (output, result)
+}
+
 /// Represents an instruction interpreter that can run [`AwaTism`]s one at a time.
 #[derive(Debug)]
 pub struct Interpreter<A: Abyss, I: BufRead, O: Write> {
@@ -86,6 +272,23 @@ pub struct Interpreter<A: Abyss, I: BufRead, O: Write> {
     output: O,
     iobuffer: String,
     awabuffer: Vec<AwaSCII>,
+    table: AwaSCIITable,
+    validation: ReadValidation,
+    flush: FlushPolicy,
+    record: bool,
+    events: Vec<Event>,
+    track_depth: bool,
+    max_depth: usize,
+    last: Option<(usize, AwaTism)>,
+    structured_print: bool,
+    input_radix: InputRadix,
+    trim_read_newline: bool,
+    eof_policy: EofPolicy,
+    arithmetic_mode: ArithmeticMode,
+    max_steps: Option<usize>,
+    steps: usize,
+    max_output: Option<usize>,
+    output_len: usize,
 }
 impl<A: Abyss, I: BufRead, O: Write> Interpreter<A, I, O> {
     #[inline(always)]
@@ -96,6 +299,311 @@ impl<A: Abyss, I: BufRead, O: Write> Interpreter<A, I, O> {
             abyss,
             iobuffer: String::new(),
             awabuffer: Vec::new(),
+            table: AwaSCIITable::DEFAULT,
+            validation: ReadValidation::Skip,
+            flush: FlushPolicy::Always,
+            record: false,
+            events: Vec::new(),
+            track_depth: false,
+            max_depth: 0,
+            last: None,
+            structured_print: false,
+            input_radix: InputRadix::Fixed(10),
+            trim_read_newline: false,
+            eof_policy: EofPolicy::Ignore,
+            arithmetic_mode: ArithmeticMode::Wrapping,
+            max_steps: None,
+            steps: 0,
+            max_output: None,
+            output_len: 0,
+        }
+    }
+    /// Same as [`Interpreter::new`], but reserves space for `io_capacity` bytes of text
+    /// and `awa_capacity` characters read with `red` up front.
+    #[inline]
+    pub fn with_buffers(
+        abyss: A,
+        input: I,
+        output: O,
+        io_capacity: usize,
+        awa_capacity: usize,
+    ) -> Self {
+        let mut interpreter = Self::new(abyss, input, output);
+        interpreter.iobuffer.reserve(io_capacity);
+        interpreter.awabuffer.reserve(awa_capacity);
+        interpreter
+    }
+    /// Number of bytes of text that can be printed or read without reallocating.
+    #[inline(always)]
+    pub fn io_capacity(&self) -> usize {
+        self.iobuffer.capacity()
+    }
+    /// Number of characters that can be read with `red` without reallocating.
+    #[inline(always)]
+    pub fn awa_capacity(&self) -> usize {
+        self.awabuffer.capacity()
+    }
+    /// Start configuring an interpreter, see [`InterpreterBuilder`].
+    #[inline(always)]
+    pub const fn builder(abyss: A, input: I, output: O) -> InterpreterBuilder<A, I, O> {
+        InterpreterBuilder::new(abyss, input, output)
+    }
+    /// Use a custom character table for `prn` and `red`.
+    #[inline(always)]
+    pub fn with_table(mut self, table: AwaSCIITable) -> Self {
+        self.table = table;
+        self
+    }
+    #[inline(always)]
+    pub fn table(&self) -> &AwaSCIITable {
+        &self.table
+    }
+    #[inline(always)]
+    pub fn set_table(&mut self, table: AwaSCIITable) {
+        self.table = table;
+    }
+    /// Decide how `red` handles characters that are not valid AwaSCII.
+    #[inline(always)]
+    pub fn with_validation(mut self, validation: ReadValidation) -> Self {
+        self.validation = validation;
+        self
+    }
+    #[inline(always)]
+    pub fn validation(&self) -> ReadValidation {
+        self.validation
+    }
+    #[inline(always)]
+    pub fn set_validation(&mut self, validation: ReadValidation) {
+        self.validation = validation;
+    }
+    /// Decide which base `r3d` reads numbers in.
+    #[inline(always)]
+    pub fn with_input_radix(mut self, radix: InputRadix) -> Self {
+        self.input_radix = radix;
+        self
+    }
+    #[inline(always)]
+    pub fn input_radix(&self) -> InputRadix {
+        self.input_radix
+    }
+    #[inline(always)]
+    pub fn set_input_radix(&mut self, radix: InputRadix) {
+        self.input_radix = radix;
+    }
+    /// Drop the line ending (`\n` or `\r\n`) of lines read by `red`.
+    /// By default it is kept and ends up as the last character of the double bubble.
+    #[inline(always)]
+    pub fn with_trim_read_newline(mut self, trim_read_newline: bool) -> Self {
+        self.trim_read_newline = trim_read_newline;
+        self
+    }
+    #[inline(always)]
+    pub fn trim_read_newline(&self) -> bool {
+        self.trim_read_newline
+    }
+    #[inline(always)]
+    pub fn set_trim_read_newline(&mut self, trim_read_newline: bool) {
+        self.trim_read_newline = trim_read_newline;
+    }
+    /// Decide what `red` and `r3d` do when there is no more input.
+    #[inline(always)]
+    pub fn with_eof_policy(mut self, eof_policy: EofPolicy) -> Self {
+        self.eof_policy = eof_policy;
+        self
+    }
+    #[inline(always)]
+    pub fn eof_policy(&self) -> EofPolicy {
+        self.eof_policy
+    }
+    #[inline(always)]
+    pub fn set_eof_policy(&mut self, eof_policy: EofPolicy) {
+        self.eof_policy = eof_policy;
+    }
+    /// Decide how arithmetic handles results that do not fit into a bubble.
+    #[inline(always)]
+    pub fn with_arithmetic_mode(mut self, arithmetic_mode: ArithmeticMode) -> Self {
+        self.arithmetic_mode = arithmetic_mode;
+        self
+    }
+    #[inline(always)]
+    pub fn arithmetic_mode(&self) -> ArithmeticMode {
+        self.arithmetic_mode
+    }
+    #[inline(always)]
+    pub fn set_arithmetic_mode(&mut self, arithmetic_mode: ArithmeticMode) {
+        self.arithmetic_mode = arithmetic_mode;
+    }
+    /// Fail with [`Error::StepLimitExceeded`] instead of running more than `max_steps` instructions of a [`Program`].
+    /// Steps are counted across all runs, see [`Interpreter::steps`].
+    #[inline(always)]
+    pub fn with_max_steps(mut self, max_steps: Option<usize>) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+    #[inline(always)]
+    pub fn max_steps(&self) -> Option<usize> {
+        self.max_steps
+    }
+    #[inline(always)]
+    pub fn set_max_steps(&mut self, max_steps: Option<usize>) {
+        self.max_steps = max_steps;
+    }
+    /// Number of instructions run as part of a [`Program`] so far.
+    #[inline(always)]
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+    /// Fail with [`Error::OutputLimitExceeded`] instead of printing more than `max_output` bytes in total.
+    /// The print that would go over the limit is not written at all.
+    #[inline(always)]
+    pub fn with_max_output(mut self, max_output: Option<usize>) -> Self {
+        self.max_output = max_output;
+        self
+    }
+    #[inline(always)]
+    pub fn max_output(&self) -> Option<usize> {
+        self.max_output
+    }
+    #[inline(always)]
+    pub fn set_max_output(&mut self, max_output: Option<usize>) {
+        self.max_output = max_output;
+    }
+    /// Number of bytes printed so far.
+    #[inline(always)]
+    pub fn output_len(&self) -> usize {
+        self.output_len
+    }
+    /// Decide when the output is flushed after printing.
+    /// Output is always flushed before reading input, so prompts are visible.
+    #[inline(always)]
+    pub fn with_flush(mut self, flush: FlushPolicy) -> Self {
+        self.flush = flush;
+        self
+    }
+    #[inline(always)]
+    pub fn flush(&self) -> FlushPolicy {
+        self.flush
+    }
+    #[inline(always)]
+    pub fn set_flush(&mut self, flush: FlushPolicy) {
+        self.flush = flush;
+    }
+    /// Print double bubbles with `pr1` as bracketed groups like `[1 2 [3 4]]`,
+    /// instead of flattening them to `1 2 3 4`.
+    #[inline(always)]
+    pub fn with_structured_print(mut self, structured_print: bool) -> Self {
+        self.structured_print = structured_print;
+        self
+    }
+    #[inline(always)]
+    pub fn structured_print(&self) -> bool {
+        self.structured_print
+    }
+    #[inline(always)]
+    pub fn set_structured_print(&mut self, structured_print: bool) {
+        self.structured_print = structured_print;
+    }
+    #[inline]
+    fn write_iobuffer(&mut self) -> Result<(), Error> {
+        let output_len = self.output_len + self.iobuffer.len();
+        if let Some(max_output) = self.max_output.filter(|max| output_len > *max) {
+            return Err(Error::OutputLimitExceeded(max_output));
+        }
+        self.output.write_all(self.iobuffer.as_bytes())?;
+        self.output_len = output_len;
+        match self.flush {
+            FlushPolicy::Always => self.output.flush()?,
+            FlushPolicy::OnNewline if self.iobuffer.contains('\n') => self.output.flush()?,
+            _ => (),
+        }
+        Ok(())
+    }
+    /// Handle `red` or `r3d` reading nothing, according to the [`EofPolicy`].
+    #[inline]
+    fn end_of_input(&mut self) -> Result<(), Error> {
+        match self.eof_policy {
+            EofPolicy::Ignore => Ok(()),
+            EofPolicy::Zero => self.abyss.blow(A::Value::zero()).ok_or(Error::NoSpace),
+            EofPolicy::Error => Err(Error::EndOfInput),
+        }
+    }
+    /// Combine the top two bubbles using the operation matching the [`ArithmeticMode`].
+    /// After an overflow in checked mode the combined bubble holds zeros in place of the failed results.
+    #[inline]
+    fn combine_arithmetic(
+        &mut self,
+        wrapping: fn(&A::Value, &A::Value) -> A::Value,
+        saturating: fn(&A::Value, &A::Value) -> A::Value,
+        checked: fn(&A::Value, &A::Value) -> Option<A::Value>,
+    ) -> Result<(), Error> {
+        let overflow = Cell::new(false);
+        let result = match self.arithmetic_mode {
+            ArithmeticMode::Wrapping => self.abyss.combine_single(|lhs, rhs| wrapping(&lhs, &rhs)),
+            ArithmeticMode::Saturating => {
+                self.abyss.combine_single(|lhs, rhs| saturating(&lhs, &rhs))
+            }
+            ArithmeticMode::Checked => self.abyss.combine_single(|lhs, rhs| {
+                checked(&lhs, &rhs).unwrap_or_else(|| {
+                    overflow.set(true);
+                    A::Value::zero()
+                })
+            }),
+        };
+        if result.is_none() {
+            return Err(Error::NotEnoughBubbles(u5::TWO));
+        }
+        if overflow.get() {
+            return Err(Error::ArithmeticOverflow);
+        }
+        Ok(())
+    }
+    /// Record side effects into the event log while running.
+    #[inline(always)]
+    pub fn set_record(&mut self, record: bool) {
+        self.record = record;
+    }
+    /// Returns all recorded events in order.
+    #[inline(always)]
+    pub fn event_log(&self) -> &[Event] {
+        &self.events
+    }
+    /// Returns all recorded events, leaving the event log empty.
+    #[inline(always)]
+    pub fn take_event_log(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+    /// Update [`Interpreter::max_depth`] after every instruction run as part of a [`Program`].
+    /// This is off by default, since [`Abyss::depth`] is not constant time for every abyss.
+    #[inline(always)]
+    pub fn with_track_depth(mut self, track_depth: bool) -> Self {
+        self.track_depth = track_depth;
+        self
+    }
+    #[inline(always)]
+    pub fn track_depth(&self) -> bool {
+        self.track_depth
+    }
+    #[inline(always)]
+    pub fn set_track_depth(&mut self, track_depth: bool) {
+        self.track_depth = track_depth;
+    }
+    /// Highest [`Abyss::depth`] seen after any executed instruction so far,
+    /// stays at zero unless enabled with [`Interpreter::set_track_depth`].
+    #[inline(always)]
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+    /// Position and instruction of the last instruction run as part of a [`Program`],
+    /// it is set before executing, so this is the failing instruction after an error.
+    #[inline(always)]
+    pub fn last_instruction(&self) -> Option<(usize, AwaTism)> {
+        self.last
+    }
+    #[inline]
+    fn log(&mut self, event: impl FnOnce(&Self) -> Event) {
+        if self.record {
+            let event = event(self);
+            self.events.push(event);
         }
     }
     #[inline]
@@ -111,28 +619,80 @@ impl<A: Abyss, I: BufRead, O: Write> Interpreter<A, I, O> {
                 output,
                 iobuffer: self.iobuffer,
                 awabuffer: self.awabuffer,
+                table: self.table,
+                validation: self.validation,
+                flush: self.flush,
+                record: self.record,
+                events: self.events,
+                track_depth: self.track_depth,
+                max_depth: self.max_depth,
+                last: self.last,
+                structured_print: self.structured_print,
+                input_radix: self.input_radix,
+                trim_read_newline: self.trim_read_newline,
+                eof_policy: self.eof_policy,
+                arithmetic_mode: self.arithmetic_mode,
+                max_steps: self.max_steps,
+                steps: self.steps,
+                max_output: self.max_output,
+                output_len: self.output_len,
             },
             (self.input, self.output),
         )
     }
+    /// Check if the input has no more data, without consuming anything.
+    /// This will block until the input has data or is closed.
+    #[inline]
+    pub fn input_eof(&mut self) -> Result<bool, IOError> {
+        Ok(self.input.fill_buf()?.is_empty())
+    }
+    /// Flush the output and return the abyss, input and output.
+    /// Use this when done running to make sure no output stays buffered in the writer.
+    #[inline]
+    pub fn finish(mut self) -> Result<(A, I, O), Error> {
+        self.output.flush()?;
+        Ok((self.abyss, self.input, self.output))
+    }
     #[inline(always)]
     pub fn run<'a>(&'a mut self, program: &'a Program) -> Iter<'a, A, I, O> {
+        self.run_from(program, 0)
+    }
+    /// Like [`Interpreter::run`], but starts executing at instruction `pc` instead of the first one.
+    #[inline(always)]
+    pub fn run_from<'a>(&'a mut self, program: &'a Program, pc: usize) -> Iter<'a, A, I, O> {
         Iter {
             interpreter: self,
             program,
-            pc: Some(0),
+            pc: Some(pc),
+            exit: None,
         }
     }
+    /// Execute the instruction at `pc` and return the location of the next instruction.
+    /// Returns `None` when the program ended, either by [`AwaTism::Terminate`] or because `pc` is past the end.
+    #[inline]
+    pub fn step(&mut self, program: &Program, pc: usize) -> Result<Option<usize>, Error> {
+        let Some(&awatism) = program.get(pc) else {
+            return Ok(None);
+        };
+        // NOTE: a comparison as the last instruction would skip more than one past the end
+        Ok(run_single(self, awatism, program.labels(), pc)?.map(|next| next.min(program.len())))
+    }
     #[inline(always)]
     pub fn abyss(&self) -> &A {
         &self.abyss
     }
+    #[inline(always)]
+    pub fn abyss_mut(&mut self) -> &mut A {
+        &mut self.abyss
+    }
     #[inline]
     pub fn next(&mut self, awatism: AwaTism) -> Result<ContinueAt, Error> {
         match awatism {
             AwaTism::NoOp => (),
             AwaTism::Print => {
                 self.iobuffer.clear();
+                // NOTE: nested double bubbles are not counted, so this is only a lower bound
+                self.iobuffer.reserve(self.abyss.top_size().unwrap_or(0));
                 match self.abyss.consume(|v| {
                     let awascii = match cast(v) {
                         None => return Err(CoreError::OutOfBounds(6)),
@@ -140,12 +700,12 @@ impl<A: Abyss, I: BufRead, O: Write> Interpreter<A, I, O> {
                         // SAFETY: v is a valid 6 bit number here
                         Some(v) => unsafe { AwaSCII::new_unchecked(v) },
                     };
-                    self.iobuffer.push(awascii.to_ascii() as char);
+                    self.iobuffer.push(self.table.to_ascii(awascii) as char);
                     Ok(())
                 })? {
                     Some(_) => {
-                        self.output.write_all(self.iobuffer.as_bytes())?;
-                        self.output.flush()?;
+                        self.write_iobuffer()?;
+                        self.log(|this| Event::Printed(this.iobuffer.as_bytes().to_vec()));
                     }
                     None => return Err(Error::NotEnoughBubbles(u5::ONE)),
                 }
@@ -153,49 +713,97 @@ impl<A: Abyss, I: BufRead, O: Write> Interpreter<A, I, O> {
             AwaTism::PrintNum => {
                 self.iobuffer.clear();
                 let mut first = true;
-                match self.abyss.consume::<_, FmtError>(|v| {
-                    if first {
+                let result = if self.structured_print {
+                    self.abyss.consume_nested::<_, Infallible>(|part| {
+                        if !first && part != Nested::End {
+                            self.iobuffer.push(' ');
+                        }
                         first = false;
-                    } else {
-                        self.iobuffer.push(' ');
-                    }
-                    write!(self.iobuffer, "{}", v)?;
-                    Ok(())
-                })? {
-                    Some(_) => {
-                        self.output.write_all(self.iobuffer.as_bytes())?;
-                        self.output.flush()?;
+                        match part {
+                            Nested::Begin => {
+                                self.iobuffer.push('[');
+                                first = true;
+                            }
+                            Nested::Value(v) => push_number(&mut self.iobuffer, v),
+                            Nested::End => self.iobuffer.push(']'),
+                        }
+                        Ok(())
+                    })
+                } else {
+                    self.abyss.consume::<_, Infallible>(|v| {
+                        if first {
+                            first = false;
+                        } else {
+                            self.iobuffer.push(' ');
+                        }
+                        push_number(&mut self.iobuffer, v);
+                        Ok(())
+                    })
+                };
+                match result {
+                    Ok(Some(_)) => {
+                        self.write_iobuffer()?;
+                        self.log(|this| Event::Printed(this.iobuffer.as_bytes().to_vec()));
                     }
-                    None => return Err(Error::NotEnoughBubbles(u5::ONE)),
+                    Ok(None) => return Err(Error::NotEnoughBubbles(u5::ONE)),
+                    Err(never) => match never {},
                 }
             }
             AwaTism::Read => {
+                // NOTE: printed text might be buffered, depending on the flush policy
+                self.output.flush()?;
                 self.iobuffer.clear();
                 // SAFETY: no limit on read bytes
                 let count = self.input.read_line(&mut self.iobuffer)?;
+                self.log(|this| Event::ReadLine(this.iobuffer.as_bytes().to_vec()));
                 if count > 0 {
                     self.awabuffer.clear();
-                    parse_awascii_input(&self.iobuffer, &mut self.awabuffer);
+                    let mut line = self.iobuffer.as_str();
+                    if self.trim_read_newline {
+                        line = line.strip_suffix('\n').unwrap_or(line);
+                        line = line.strip_suffix('\r').unwrap_or(line);
+                    }
+                    match self.validation {
+                        ReadValidation::Skip => {
+                            parse_awascii_input_with(line, &mut self.awabuffer, &self.table)
+                        }
+                        ReadValidation::Error => {
+                            try_parse_awascii_input_with(line, &mut self.awabuffer, &self.table)
+                                .map_err(Error::InvalidInputChar)?
+                        }
+                    }
                     if self.abyss.blow_awascii(&self.awabuffer).is_none() {
                         return Err(Error::NoSpace);
                     }
+                } else {
+                    self.end_of_input()?;
                 }
             }
             AwaTism::ReadNum => {
+                // NOTE: printed text might be buffered, depending on the flush policy
+                self.output.flush()?;
                 self.iobuffer.clear();
                 // SAFETY: no limit on read bytes
                 let count = self.input.read_line(&mut self.iobuffer)?;
+                self.log(|this| Event::ReadLine(this.iobuffer.as_bytes().to_vec()));
                 if count == 0 {
-                    return Err(Error::NoNumber);
+                    return match self.eof_policy {
+                        EofPolicy::Ignore => Err(Error::NoNumber),
+                        _ => self.end_of_input().map(|_| ContinueAt::Next),
+                    };
                 }
-                let Some(value) = parse_number_input::<A::Value>(&self.iobuffer) else {
+                let Some(value) = parse_number_input::<A::Value>(&self.iobuffer, self.input_radix)
+                else {
                     return Err(Error::NoNumber);
                 };
                 if self.abyss.blow(value).is_none() {
                     return Err(Error::NoSpace);
                 }
             }
-            AwaTism::Terminate => return Ok(ContinueAt::None),
+            AwaTism::Terminate => {
+                self.log(|_| Event::Terminated);
+                return Ok(ContinueAt::None);
+            }
             AwaTism::Blow(value) => {
                 // SAFETY: unwrap: A::Value should be able to represent an i8, thats its whole purpose
                 if self.abyss.blow(cast(value).unwrap()).is_none() {
@@ -203,7 +811,7 @@ impl<A: Abyss, I: BufRead, O: Write> Interpreter<A, I, O> {
                 }
             }
             AwaTism::Submerge(distance) => {
-                if self.abyss.submerge((*distance) as usize).is_none() {
+                if self.abyss.submerge((*distance).into()).is_none() {
                     return Err(Error::NotEnoughBubbles(distance));
                 }
             }
@@ -227,33 +835,66 @@ impl<A: Abyss, I: BufRead, O: Write> Interpreter<A, I, O> {
                     return Err(Error::NotEnoughBubbles(u5::TWO));
                 }
             }
-            AwaTism::Add => {
-                if self.abyss.combine_single(<A::Value as Add>::add).is_none() {
-                    return Err(Error::NotEnoughBubbles(u5::TWO));
-                }
-            }
-            AwaTism::Subtract => {
-                if self.abyss.combine_single(<A::Value as Sub>::sub).is_none() {
-                    return Err(Error::NotEnoughBubbles(u5::TWO));
-                }
-            }
-            AwaTism::Multiply => {
-                if self.abyss.combine_single(<A::Value as Mul>::mul).is_none() {
-                    return Err(Error::NotEnoughBubbles(u5::TWO));
-                }
-            }
+            AwaTism::Add => self.combine_arithmetic(
+                WrappingAdd::wrapping_add,
+                SaturatingAdd::saturating_add,
+                CheckedAdd::checked_add,
+            )?,
+            AwaTism::Subtract => self.combine_arithmetic(
+                WrappingSub::wrapping_sub,
+                SaturatingSub::saturating_sub,
+                CheckedSub::checked_sub,
+            )?,
+            AwaTism::Multiply => self.combine_arithmetic(
+                WrappingMul::wrapping_mul,
+                SaturatingMul::saturating_mul,
+                CheckedMul::checked_mul,
+            )?,
             AwaTism::Divide => {
-                if self
-                    .abyss
-                    .combine_double(<A::Value as Div>::div, <A::Value as Rem>::rem)
-                    .is_none()
-                {
+                // NOTE: checked division only fails when dividing by zero or the minimum by -1,
+                // the latter has no remainder and a quotient depending on the arithmetic mode
+                let (mode, failure) = (self.arithmetic_mode, Cell::new(None));
+                let fail = |rhs: A::Value| {
+                    failure.set(Some(if rhs.is_zero() {
+                        Error::DivisionByZero
+                    } else {
+                        Error::ArithmeticOverflow
+                    }));
+                    A::Value::zero()
+                };
+                let result = self.abyss.combine_double(
+                    |lhs, rhs| {
+                        lhs.checked_div(&rhs).unwrap_or_else(|| match mode {
+                            ArithmeticMode::Wrapping if !rhs.is_zero() => lhs,
+                            ArithmeticMode::Saturating if !rhs.is_zero() => A::Value::max_value(),
+                            _ => fail(rhs),
+                        })
+                    },
+                    |lhs, rhs| {
+                        lhs.checked_rem(&rhs).unwrap_or_else(|| match mode {
+                            ArithmeticMode::Wrapping | ArithmeticMode::Saturating
+                                if !rhs.is_zero() =>
+                            {
+                                A::Value::zero()
+                            }
+                            _ => fail(rhs),
+                        })
+                    },
+                );
+                if result.is_none() {
                     return Err(Error::NotEnoughBubbles(u5::TWO));
                 }
+                if let Some(error) = failure.take() {
+                    return Err(error);
+                }
             }
             AwaTism::Count => {
                 if self.abyss.count().is_none() {
-                    return Err(Error::NotEnoughBubbles(u5::ONE));
+                    return Err(if self.abyss.is_empty() {
+                        Error::NotEnoughBubbles(u5::ONE)
+                    } else {
+                        Error::CountOverflow
+                    });
                 }
             }
             AwaTism::Label(_label) => (),
@@ -282,3 +923,586 @@ impl<A: Abyss, I: BufRead, O: Write> Interpreter<A, I, O> {
         Ok(ContinueAt::Next)
     }
 }
+
+/// Interpreter with input and output chosen at runtime.
+/// Trades dynamic dispatch on every I/O call for a single instantiation per abyss type.
+pub type DynInterpreter<'a, A> = Interpreter<A, Box<dyn BufRead + 'a>, Box<dyn Write + 'a>>;
+impl<'a, A: Abyss> DynInterpreter<'a, A> {
+    /// Same as [`Interpreter::new`], but boxes `input` and `output`.
+    #[inline]
+    pub fn new_dyn(abyss: A, input: impl BufRead + 'a, output: impl Write + 'a) -> Self {
+        Self::new(abyss, Box::new(input), Box::new(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use awa_abyss::linked::Abyss;
+    use awa_asm::parse_program;
+    use awa_core::Abyss as _;
+
+    use super::*;
+
+    #[test]
+    fn max_depth_records_peak() {
+        let program = parse_program("blo 1\nblo 2\nblo 3\npop\npop\nblo 4\ntrm").unwrap();
+        let mut interpreter =
+            Interpreter::new(Abyss::<isize>::default(), &[][..], Vec::new()).with_track_depth(true);
+        interpreter.run(&program).count().unwrap();
+        assert_eq!(interpreter.max_depth(), 3);
+        assert_eq!(interpreter.abyss().depth(), 2);
+    }
+
+    #[test]
+    fn push_number_matches_display() {
+        fn check<T: Value>(values: impl IntoIterator<Item = T>) {
+            for value in values {
+                let mut out = String::new();
+                push_number(&mut out, value);
+                assert_eq!(out, value.to_string());
+            }
+        }
+        check(i8::MIN..=i8::MAX);
+        check([u8::MIN, u8::MAX]);
+        check([i64::MIN, -1, 0, 1, i64::MAX]);
+        check([u128::MAX, 0]);
+        check([i128::MIN, i128::MAX]);
+    }
+
+    #[test]
+    fn print_many_numbers() {
+        let values = (-1000..1000).map(|v| v * 7919).collect::<Vec<isize>>();
+        let mut abyss = Abyss::<isize>::default();
+        abyss.blow_double(&values).unwrap();
+        let mut interpreter = Interpreter::new(abyss, &[][..], Vec::new());
+        interpreter.next(AwaTism::PrintNum).unwrap();
+        let expected = values
+            .iter()
+            .rev()
+            .map(isize::to_string)
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert_eq!(
+            String::from_utf8(interpreter.finish().unwrap().2).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn max_depth_is_opt_in() {
+        let program = parse_program("blo 1\nblo 2\ntrm").unwrap();
+        let mut interpreter = Interpreter::new(Abyss::<isize>::default(), &[][..], Vec::new());
+        interpreter.run(&program).count().unwrap();
+        assert_eq!(interpreter.max_depth(), 0);
+    }
+
+    #[test]
+    fn trim_read_newline() {
+        let program = parse_program("red\nprn\nred\ntrm").unwrap();
+        let run = |trim| {
+            let mut interpreter =
+                Interpreter::new(Abyss::<isize>::default(), &b"awa\n\n"[..], Vec::new())
+                    .with_trim_read_newline(trim);
+            interpreter.run(&program).count().unwrap();
+            let (mut abyss, _, output) = interpreter.finish().unwrap();
+            (String::from_utf8(output).unwrap(), abyss.pop_value())
+        };
+        // NOTE: a trimmed empty line blows a single zero, the untrimmed one a double bubble holding the newline
+        assert_eq!(run(false), ("awa\n".to_string(), None));
+        assert_eq!(run(true), ("awa".to_string(), Some(0)));
+    }
+
+    #[test]
+    fn presized_buffers_do_not_grow() {
+        let program = parse_program("red\nprn\ntrm").unwrap();
+        let input = format!("{}\n", "awa".repeat(1000));
+        let mut interpreter = Interpreter::with_buffers(
+            Abyss::<isize>::default(),
+            input.as_bytes(),
+            Vec::new(),
+            4096,
+            4096,
+        );
+        let capacity = (interpreter.io_capacity(), interpreter.awa_capacity());
+        assert!(capacity.0 >= 4096 && capacity.1 >= 4096);
+        interpreter.run(&program).count().unwrap();
+        assert_eq!(
+            (interpreter.io_capacity(), interpreter.awa_capacity()),
+            capacity
+        );
+        let output = interpreter.finish().unwrap().2;
+
+        let mut interpreter =
+            Interpreter::new(Abyss::<isize>::default(), input.as_bytes(), Vec::new());
+        interpreter.run(&program).count().unwrap();
+        assert_eq!(interpreter.finish().unwrap().2, output);
+        assert_eq!(output, input.as_bytes());
+    }
+
+    #[test]
+    fn builder_applies_policies() {
+        let program = parse_program("r3d\nblo 127\n4dd\nprn\ntrm").unwrap();
+        let mut interpreter = Interpreter::builder(Abyss::<i8>::default(), &[][..], Vec::new())
+            .eof_policy(EofPolicy::Zero)
+            .arithmetic_mode(ArithmeticMode::Saturating)
+            .max_steps(3)
+            .build();
+        assert!(matches!(
+            interpreter.run(&program).count(),
+            Err(Error::StepLimitExceeded(3))
+        ));
+        assert_eq!(interpreter.steps(), 3);
+        assert_eq!(interpreter.abyss().depth(), 1);
+        assert_eq!(interpreter.abyss_mut().pop_value(), Some(127));
+        // NOTE: the quotient ends up on top of the remainder
+        let program = parse_program("blo -1\nblo -128\ndiv\npr1\nblo 0\nblo 5\ndiv\ntrm").unwrap();
+        for (mode, expected) in [
+            (ArithmeticMode::Wrapping, Some("-128 0")),
+            (ArithmeticMode::Saturating, Some("127 0")),
+            (ArithmeticMode::Checked, None),
+        ] {
+            let mut interpreter = Interpreter::builder(Abyss::<i8>::default(), &[][..], Vec::new())
+                .arithmetic_mode(mode)
+                .max_output(6)
+                .build();
+            let result = interpreter.run(&program).count();
+            let (_, _, output) = interpreter.finish().unwrap();
+            match expected {
+                Some(expected) => {
+                    assert!(matches!(result, Err(Error::DivisionByZero)), "{mode:?}");
+                    assert_eq!(output, expected.as_bytes());
+                }
+                None => assert!(matches!(result, Err(Error::ArithmeticOverflow))),
+            }
+        }
+        let program = parse_program("blo 12\npr1\nblo 34\npr1\ntrm").unwrap();
+        let mut interpreter = Interpreter::builder(Abyss::<i8>::default(), &[][..], Vec::new())
+            .max_output(3)
+            .build();
+        assert!(matches!(
+            interpreter.run(&program).count(),
+            Err(Error::OutputLimitExceeded(3))
+        ));
+        assert_eq!(interpreter.output_len(), 2);
+    }
+
+    #[test]
+    fn checked_arithmetic_overflows() {
+        let program = parse_program("blo 100\nblo 100\n4dd\ntrm").unwrap();
+        let mut interpreter = Interpreter::new(Abyss::<i8>::default(), &[][..], Vec::new())
+            .with_arithmetic_mode(ArithmeticMode::Checked);
+        assert!(matches!(
+            interpreter.run(&program).count(),
+            Err(Error::ArithmeticOverflow)
+        ));
+        let program = parse_program("red\ntrm").unwrap();
+        let mut interpreter = Interpreter::new(Abyss::<i8>::default(), &[][..], Vec::new())
+            .with_eof_policy(EofPolicy::Error);
+        assert!(matches!(
+            interpreter.run(&program).count(),
+            Err(Error::EndOfInput)
+        ));
+    }
+
+    #[test]
+    fn abyss_mut_seeds_before_running() {
+        let program = parse_program("blo 2\n4dd\npr1\ntrm").unwrap();
+        let mut interpreter = Interpreter::new(Abyss::<isize>::default(), &[][..], Vec::new());
+        interpreter.abyss_mut().blow(40).unwrap();
+        interpreter.run(&program).count().unwrap();
+        assert_eq!(interpreter.finish().unwrap().2, b"42");
+    }
+
+    #[test]
+    fn print_flattens_front_first() {
+        fn output(src: &str) -> String {
+            let program = parse_program(src).unwrap();
+            let mut interpreter = Interpreter::new(Abyss::<isize>::default(), &[][..], Vec::new());
+            interpreter.run(&program).count().unwrap();
+            String::from_utf8(interpreter.finish().unwrap().2).unwrap()
+        }
+        // [0, 1, 2]
+        assert_eq!(output("blo 2\nblo 1\nblo 0\nsrn 3\npr1"), "0 1 2");
+        // [[0, 1], 2, [3]]
+        let nested = "blo 3\nsrn 1\nblo 2\nblo 1\nblo 0\nsrn 2\nsrn 3";
+        assert_eq!(output(&format!("{nested}\npr1")), "0 1 2 3");
+        assert_eq!(output(&format!("{nested}\nprn")), "AWaw");
+        // [[[0], 1], [2, 3]]
+        let deep = "blo 3\nblo 2\nsrn 2\nblo 1\nblo 0\nsrn 1\nsrn 2\nsrn 2";
+        assert_eq!(output(&format!("{deep}\npr1")), "0 1 2 3");
+    }
+
+    #[test]
+    fn permuted_table_changes_text() {
+        let rotated = AwaSCIITable::new(core::array::from_fn(|code| {
+            AwaSCII::new((code as u8 + 1) % 64).unwrap().to_ascii()
+        }))
+        .unwrap();
+        let program = parse_program("blo 1\nblo 0\nsrn 2\nprn\nred\nprn\ntrm").unwrap();
+        let run = |table: AwaSCIITable| {
+            let mut interpreter =
+                Interpreter::new(Abyss::<isize>::default(), &b"Wa"[..], Vec::new())
+                    .with_table(table);
+            interpreter.run(&program).count().unwrap();
+            String::from_utf8(interpreter.finish().unwrap().2).unwrap()
+        };
+        assert_eq!(run(AwaSCIITable::DEFAULT), "AWWa");
+        assert_eq!(run(rotated), "WaWa");
+    }
+
+    #[test]
+    fn event_log_orders_io() {
+        let program = parse_program("r3d\nblo 1\n4dd\npr1\ntrm").unwrap();
+        let mut interpreter = Interpreter::new(Abyss::<isize>::default(), &b"42\n"[..], Vec::new());
+        interpreter.run(&program).count().unwrap();
+        assert!(interpreter.event_log().is_empty());
+
+        let mut interpreter = Interpreter::new(Abyss::<isize>::default(), &b"42\n"[..], Vec::new());
+        interpreter.set_record(true);
+        interpreter.run(&program).count().unwrap();
+        assert_eq!(
+            interpreter.take_event_log(),
+            [
+                Event::ReadLine(b"42\n".to_vec()),
+                Event::Printed(b"43".to_vec()),
+                Event::Terminated,
+            ]
+        );
+        assert!(interpreter.event_log().is_empty());
+    }
+
+    #[test]
+    fn parse_number_input_bounds() {
+        let decimal = InputRadix::Fixed(10);
+        assert_eq!(parse_number_input::<i8>("-128", decimal), Some(i8::MIN));
+        assert_eq!(parse_number_input::<i8>("127\n", decimal), Some(i8::MAX));
+        assert_eq!(parse_number_input::<i8>("128", decimal), None);
+        assert_eq!(parse_number_input::<i8>("-129", decimal), None);
+        assert_eq!(parse_number_input::<u8>("255", decimal), Some(u8::MAX));
+        assert_eq!(parse_number_input::<u8>("256", decimal), None);
+        assert_eq!(parse_number_input::<u8>("-5", decimal), None);
+        assert_eq!(parse_number_input::<isize>("-5", decimal), Some(-5));
+        assert_eq!(parse_number_input::<isize>("12abc", decimal), Some(12));
+        assert_eq!(parse_number_input::<isize>("", decimal), None);
+        assert_eq!(parse_number_input::<isize>("-", decimal), None);
+        assert_eq!(parse_number_input::<isize>("abc", decimal), None);
+        assert_eq!(
+            parse_number_input::<i8>("-0x80", InputRadix::Prefixed),
+            Some(i8::MIN)
+        );
+    }
+
+    #[test]
+    fn read_validation_policies() {
+        let program = parse_program("red\nprn\ntrm").unwrap();
+        let run = |input: &'static str, validation| {
+            let mut interpreter =
+                Interpreter::new(Abyss::<isize>::default(), input.as_bytes(), Vec::new())
+                    .with_validation(validation);
+            interpreter.run(&program).count()?;
+            Ok::<_, Error>(String::from_utf8(interpreter.finish()?.2).unwrap())
+        };
+        assert_eq!(run("a\tw\n", ReadValidation::Skip).unwrap(), "aw\n");
+        assert_eq!(run("aé\n", ReadValidation::Skip).unwrap(), "a\n");
+        assert!(matches!(
+            run("a\tw\n", ReadValidation::Error),
+            Err(Error::InvalidInputChar(b'\t'))
+        ));
+        assert!(matches!(
+            run("aé\n", ReadValidation::Error),
+            Err(Error::InvalidInputChar(0xC3))
+        ));
+        assert_eq!(run("aw\n", ReadValidation::Error).unwrap(), "aw\n");
+    }
+
+    #[test]
+    fn step_through_jump() {
+        let program = parse_program("jmp 0\nblo 9\nlbl 0\nblo 1\ntrm").unwrap();
+        let mut interpreter = Interpreter::new(Abyss::<isize>::default(), &[][..], Vec::new());
+        assert_eq!(interpreter.step(&program, 0).unwrap(), Some(3));
+        assert_eq!(interpreter.step(&program, 3).unwrap(), Some(4));
+        assert_eq!(interpreter.step(&program, 4).unwrap(), None);
+        assert_eq!(interpreter.step(&program, 5).unwrap(), None);
+        assert_eq!(interpreter.abyss().depth(), 1);
+        assert_eq!(interpreter.abyss_mut().pop_value(), Some(1));
+    }
+
+    #[test]
+    fn finish_flushes_batched_output() {
+        let program = parse_program("blo 1\npr1\nblo 2\npr1\nblo 3\npr1\ntrm").unwrap();
+        let output = std::io::BufWriter::with_capacity(64, Vec::new());
+        let mut interpreter = Interpreter::new(Abyss::<isize>::default(), &[][..], output)
+            .with_flush(FlushPolicy::Never);
+        {
+            // NOTE: stop after the second print, the iterator is dropped early
+            let mut iter = interpreter.run(&program);
+            for _ in 0..4 {
+                iter.next().unwrap();
+            }
+        }
+        assert!(interpreter.output.get_ref().is_empty());
+        let (_, _, output) = interpreter.finish().unwrap();
+        assert_eq!(output.get_ref(), b"12");
+    }
+
+    #[test]
+    fn input_eof_peeks() {
+        let program = parse_program("red\ntrm").unwrap();
+        let mut interpreter =
+            Interpreter::new(Abyss::<isize>::default(), &b"awa\n"[..], Vec::new());
+        assert!(!interpreter.input_eof().unwrap());
+        assert!(!interpreter.input_eof().unwrap());
+        interpreter.run(&program).count().unwrap();
+        assert!(interpreter.input_eof().unwrap());
+        let mut empty = Interpreter::new(Abyss::<isize>::default(), &[][..], Vec::new());
+        assert!(empty.input_eof().unwrap());
+    }
+
+    #[test]
+    fn run_parsed_program() {
+        let program = parse_program("r3d\nblo 2\nmul\npr1\ntrm").unwrap();
+        let mut interpreter = Interpreter::new(Abyss::<isize>::default(), &b"21\n"[..], Vec::new());
+        interpreter.run(&program).count().unwrap();
+        assert_eq!(interpreter.finish().unwrap().2, b"42");
+    }
+
+    #[test]
+    fn limited_run_keeps_partial_output() {
+        let looping = parse_program("lbl 0\nblo 1\npr1\njmp 0").unwrap();
+        let (output, result) =
+            run_capturing_limited(Abyss::<isize>::default(), &looping, &[][..], 7);
+        assert!(matches!(result, Err(Error::StepLimitExceeded(7))));
+        assert_eq!(output, "11");
+        let failing = parse_program("blo 5\npr1\npop\ntrm").unwrap();
+        let (output, result) =
+            run_capturing_limited(Abyss::<isize>::default(), &failing, &[][..], 10);
+        assert!(matches!(result, Err(Error::NotEnoughBubbles(_))));
+        assert_eq!(output, "5");
+        let ending = parse_program("blo 5\npr1\ntrm").unwrap();
+        let (output, result) =
+            run_capturing_limited(Abyss::<isize>::default(), &ending, &[][..], 3);
+        assert!(result.is_ok());
+        assert_eq!(output, "5");
+    }
+
+    #[test]
+    fn reverse_top_prints_reversed() {
+        fn output<A: awa_core::Abyss<Value = isize>>(mut abyss: A) -> String {
+            abyss.reverse_top().unwrap();
+            let program = parse_program("prn").unwrap();
+            let mut interpreter = Interpreter::new(abyss, &[][..], Vec::new());
+            interpreter.run(&program).count().unwrap();
+            String::from_utf8(interpreter.finish().unwrap().2).unwrap()
+        }
+        // the last element is the front, so this prints "WwA" unreversed
+        let awa = [0, 3, 1];
+        let expected = output(Abyss::<isize>::from_double(awa));
+        assert_eq!(expected, "AwW");
+        assert_eq!(
+            output(awa_abyss::Buffered::<Abyss<isize>>::from_double(awa)),
+            expected
+        );
+        let inner = awa_abyss::Buffered::from_inner(Abyss::<isize>::from_double(awa));
+        assert_eq!(output(inner), expected);
+    }
+
+    #[test]
+    fn print_long_string() {
+        let text = "the big red dog jumps on the moon, 0123456789! ".repeat(64);
+        let awascii = text
+            .bytes()
+            .map(|byte| AwaSCII::from_ascii(byte).unwrap())
+            .collect::<Vec<_>>();
+        let program = parse_program("prn\ntrm").unwrap();
+        let mut linked = Abyss::<isize>::default();
+        linked.blow_awascii(&awascii).unwrap();
+        let mut buffered = awa_abyss::Buffered::<Abyss<isize>>::default();
+        buffered.blow_awascii(&awascii).unwrap();
+        assert_eq!(buffered.top_size(), Some(text.len()));
+
+        let mut interpreter = Interpreter::new(linked, &[][..], Vec::new());
+        interpreter.run(&program).count().unwrap();
+        assert_eq!(interpreter.finish().unwrap().2, text.as_bytes());
+        let mut interpreter = Interpreter::new(buffered, &[][..], Vec::new());
+        interpreter.run(&program).count().unwrap();
+        let (abyss, _, output) = interpreter.finish().unwrap();
+        assert_eq!(output, text.as_bytes());
+        assert!(abyss.is_empty());
+    }
+
+    #[test]
+    fn flush_on_newline() {
+        // "Aw" then "a\n" then "W"
+        let program =
+            parse_program("blo 3\nblo 0\nsrn 2\nprn\nblo 63\nblo 2\nsrn 2\nprn\nblo 1\nprn\ntrm")
+                .unwrap();
+        let output = std::io::BufWriter::with_capacity(64, Vec::new());
+        let mut interpreter = Interpreter::new(Abyss::<isize>::default(), &[][..], output)
+            .with_flush(FlushPolicy::OnNewline);
+        let mut iter = interpreter.run(&program);
+        let mut flushed = Vec::new();
+        while let Some((_, awatism)) = iter.next().unwrap() {
+            if awatism == AwaTism::Print {
+                flushed.push(iter.interpreter().output.get_ref().clone());
+            }
+        }
+        assert_eq!(flushed, [&b""[..], b"Awa\n", b"Awa\n"]);
+        let (_, _, output) = interpreter.finish().unwrap();
+        assert_eq!(output.get_ref(), b"Awa\nW");
+    }
+
+    #[test]
+    fn count_overflow_is_reported() {
+        fn count<A: awa_core::Abyss<Value = i8>>(abyss: A) -> Result<(), Error> {
+            let program = parse_program("cnt\ntrm").unwrap();
+            let mut interpreter = Interpreter::new(abyss, &[][..], Vec::new());
+            interpreter.run(&program).count().map(|_| ())
+        }
+        let values = [1i8; 200];
+        let mut buffered = awa_abyss::Buffered::<Abyss<i8>>::default();
+        buffered.blow_double(values).unwrap();
+        assert!(matches!(count(buffered), Err(Error::CountOverflow)));
+        // NOTE: with cached counts the bubble can't be built in the first place
+        let mut linked = Abyss::<i8>::default();
+        if linked.blow_double(values).is_some() {
+            assert!(matches!(count(linked), Err(Error::CountOverflow)));
+        }
+        let fits = Abyss::<i8>::from_double([1; 100]);
+        assert!(count(fits).is_ok());
+        assert!(matches!(
+            count(Abyss::<i8>::default()),
+            Err(Error::NotEnoughBubbles(_))
+        ));
+    }
+
+    #[test]
+    fn shared_program_across_threads() {
+        // doubles every number until the input is empty
+        let program = parse_program("lbl 0\nr3d\nblo 2\nmul\npr1\nblo 63\nprn\njmp 0").unwrap();
+        let inputs = ["1\n2\n3\n", "10\n", "-4\n5\n"];
+        let outputs = std::thread::scope(|scope| {
+            let program = &program;
+            let handles = inputs.map(|input| {
+                scope.spawn(move || {
+                    let mut interpreter =
+                        Interpreter::new(Abyss::<isize>::default(), input.as_bytes(), Vec::new());
+                    // NOTE: reading past the end of the input fails, the output up to then is kept
+                    let _ = interpreter.run(program).count();
+                    String::from_utf8(interpreter.finish().unwrap().2).unwrap()
+                })
+            });
+            handles.map(|handle| handle.join().unwrap())
+        });
+        assert_eq!(outputs, ["2\n4\n6\n", "20\n", "-8\n10\n"]);
+    }
+
+    #[test]
+    fn dyn_interpreter_with_file_input() {
+        let path = std::env::temp_dir().join(format!("awa-dyn-input-{}", std::process::id()));
+        std::fs::write(&path, "20\n22\n").unwrap();
+        let program = parse_program("r3d\nr3d\n4dd\npr1\ntrm").unwrap();
+        let mut output = Vec::new();
+        {
+            let input = std::io::BufReader::new(std::fs::File::open(&path).unwrap());
+            let mut interpreter =
+                DynInterpreter::new_dyn(Abyss::<isize>::default(), input, &mut output);
+            interpreter.run(&program).count().unwrap();
+            interpreter.finish().unwrap();
+        }
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(output, b"42");
+    }
+
+    #[test]
+    fn optimized_program_behaves_the_same() {
+        // counts down from 3, with nops scattered around the loop
+        let src = "nop\nblo 3\nlbl 1\nnop\ndpl\npr1\nblo 1\nnop\nsbm 1\nsub\nblo 0\neql\ntrm\npop\nnop\njmp 1";
+        let program = parse_program(src).unwrap();
+        let optimized = awa_core::optimize(&program);
+        assert_eq!(optimized.len(), program.len() - 4);
+        let output = |program: &Program| {
+            let mut interpreter = Interpreter::new(Abyss::<isize>::default(), &[][..], Vec::new());
+            let steps = interpreter.run(program).count().unwrap();
+            (
+                String::from_utf8(interpreter.finish().unwrap().2).unwrap(),
+                steps,
+            )
+        };
+        let (expected, steps) = output(&program);
+        assert_eq!(expected, "321");
+        let (actual, optimized_steps) = output(&optimized);
+        assert_eq!(actual, expected);
+        assert!(optimized_steps < steps);
+    }
+
+    #[test]
+    fn last_instruction_after_partial_run() {
+        let program = parse_program("blo 1\nblo 2\n4dd\npop\n4dd\ntrm").unwrap();
+        let mut interpreter = Interpreter::new(Abyss::<isize>::default(), &[][..], Vec::new());
+        assert_eq!(interpreter.last_instruction(), None);
+        {
+            let mut iter = interpreter.run(&program);
+            for _ in 0..3 {
+                iter.next().unwrap();
+            }
+        }
+        assert_eq!(interpreter.last_instruction(), Some((2, AwaTism::Add)));
+        // NOTE: the second 4dd fails, the abyss is empty after the pop
+        assert!(interpreter.run_from(&program, 3).count().is_err());
+        assert_eq!(interpreter.last_instruction(), Some((4, AwaTism::Add)));
+    }
+
+    #[test]
+    fn structured_print_keeps_groups() {
+        let program = parse_program("blo 4\nblo 3\nsrn 2\nblo 2\nblo 1\nsrn 3\npr1\ntrm").unwrap();
+        let output = |structured_print: bool| {
+            let mut interpreter = Interpreter::new(Abyss::<isize>::new(), &[][..], Vec::new())
+                .with_structured_print(structured_print);
+            interpreter.run(&program).count().unwrap();
+            String::from_utf8(interpreter.finish().unwrap().2).unwrap()
+        };
+        assert_eq!(output(false), "1 2 3 4");
+        assert_eq!(output(true), "[1 2 [3 4]]");
+    }
+
+    #[test]
+    fn read_num_in_other_radix() {
+        let program = parse_program("r3d\npr1\ntrm").unwrap();
+        let output = |input: &str, radix: InputRadix| {
+            let mut interpreter =
+                Interpreter::new(Abyss::<isize>::new(), input.as_bytes(), Vec::new())
+                    .with_input_radix(radix);
+            interpreter.run(&program).count().unwrap();
+            String::from_utf8(interpreter.finish().unwrap().2).unwrap()
+        };
+        assert_eq!(output("ff\n", InputRadix::Fixed(16)), "255");
+        assert_eq!(output("101\n", InputRadix::Fixed(2)), "5");
+        assert_eq!(output("101\n", InputRadix::default()), "101");
+        assert_eq!(output("0xff\n", InputRadix::Prefixed), "255");
+        assert_eq!(output("-0b101\n", InputRadix::Prefixed), "-5");
+        assert_eq!(
+            parse_number_input::<i8>("12", InputRadix::Fixed(2)),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn submerge_full_range() {
+        for distance in 0..=31u8 {
+            let program = parse_program(&format!("sbm {distance}\ntrm")).unwrap();
+            let mut expected = Abyss::<isize>::from_singles(1..=40);
+            expected.submerge(distance.into()).unwrap();
+            let mut interpreter =
+                Interpreter::new(Abyss::<isize>::from_singles(1..=40), &[][..], Vec::new());
+            interpreter.run(&program).count().unwrap();
+            assert_eq!(interpreter.abyss().to_nested(), expected.to_nested());
+            let mut buffered = Interpreter::new(
+                awa_abyss::Buffered::<Abyss<isize>>::from_singles(1..=40),
+                &[][..],
+                Vec::new(),
+            );
+            buffered.run(&program).count().unwrap();
+            assert_eq!(buffered.abyss().to_nested(), expected.to_nested());
+        }
+    }
+}
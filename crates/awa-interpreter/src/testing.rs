@@ -0,0 +1,35 @@
+use std::io::{sink, BufRead};
+
+use awa_core::{Abyss, AwaTism, Program};
+
+use crate::{Error, FallibleIterator, Interpreter};
+
+/// Run `program` to completion on an empty abyss, reading from `input` and discarding all output.
+/// Returns every executed instruction together with its location, in order.
+pub fn trace<A: Abyss + Default>(
+    program: &Program,
+    input: impl BufRead,
+) -> Result<Vec<(usize, AwaTism)>, Error> {
+    let mut interpreter = Interpreter::new(A::default(), input, sink());
+    interpreter.run(program).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use awa_abyss::linked::Abyss;
+    use awa_asm::parse_program;
+
+    use super::*;
+
+    #[test]
+    fn counted_loop() {
+        let program =
+            parse_program("blo 5\nlbl 0\nblo 1\nsbm 1\nsub\nblo 0\neql\ntrm\npop\njmp 0").unwrap();
+        let trace = trace::<Abyss<isize>>(&program, &[][..]).unwrap();
+        let count = |pc| trace.iter().filter(|(at, _)| *at == pc).count();
+        assert_eq!(count(4), 5);
+        assert_eq!(count(9), 4);
+        assert_eq!(trace.first(), Some(&(0, AwaTism::Blow(5))));
+        assert_eq!(trace.last(), Some(&(7, AwaTism::Terminate)));
+    }
+}
@@ -1,18 +1,31 @@
 use std::{
-    fs::File,
-    io::{stdin, stdout, BufReader, Error as IOError, IsTerminal, Read, Write},
-    path::PathBuf,
+    collections::HashMap,
+    fmt::Display,
+    fs::{File, OpenOptions},
+    io::{
+        stderr, stdin, stdout, BufRead, BufReader, BufWriter, Error as IOError, ErrorKind,
+        IsTerminal, Read, Write,
+    },
+    num::{NonZero, ParseIntError},
+    ops::Range,
+    path::{Path, PathBuf},
     str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 
-use awa_abyss::Abyss;
-use awa_asm::{load_program, MacroTable};
+use awa_abyss::{linked, Abyss, Buffered};
+use awa_asm::{load_program, MacroTable, ParserOptions};
 use awa_core::{
-    load_awatalk, AwaTism, BigEndian, BitError, BitReadBuffer, BitWriteStream, Endianness,
-    ParseError, Program,
+    program_from_awatalk, save_awatalk, AwaTism, BigEndian, BitError, BitReadBuffer,
+    BitWriteStream, Endianness, LittleEndian, ParseError, Program, Value,
+};
+use awa_debug::{CursorStyle, Debugger, Error as DebugError};
+use awa_interpreter::{
+    DynInterpreter, Error as RuntimeError, Exit, FallibleIterator, FlushPolicy, Interpreter,
 };
-use awa_debug::{Debugger, Error as DebugError};
-use awa_interpreter::{Error as RuntimeError, FallibleIterator, Interpreter};
 
 use clap::{Args, Parser, Subcommand, ValueEnum, ValueHint};
 use thiserror::Error;
@@ -23,6 +36,8 @@ pub enum Error {
     UnknownFormat,
     #[error("can't read source code from a terminal input")]
     InputFromTerminal,
+    #[error("stdin can't be used for both the source code and the program input")]
+    StdinConflict,
     #[error("failed to assemble program")]
     AssemblyFailed(#[from] awa_asm::Error),
     #[error("debugger failed")]
@@ -33,10 +48,247 @@ pub enum Error {
     BitError(#[from] BitError),
     #[error(transparent)]
     RuntimeError(#[from] RuntimeError),
+    #[error("line {line} of abyss init file: {inner}")]
+    AbyssInitError { line: usize, inner: ParseIntError },
+    #[error("line {line} of annotation file: {msg}")]
+    AnnotationError { line: usize, msg: String },
+    #[error("can't start at instruction {start}, the program only has {len} instructions")]
+    StartOutOfRange { start: usize, len: usize },
+    #[error("output file {0} already exists, use --force to overwrite it")]
+    OutputExists(PathBuf),
+    #[error("failed to install Ctrl-C handler")]
+    SignalError(#[from] ctrlc::Error),
     #[error(transparent)]
     IOError(#[from] IOError),
 }
 
+/// Render `error` followed by every error in its [`source`](std::error::Error::source) chain, one per line.
+pub fn report(error: &dyn std::error::Error) -> String {
+    let mut report = format!("error: {}", error);
+    let mut source = error.source();
+    while let Some(error) = source {
+        report.push_str(&format!("\n  caused by: {}", error));
+        source = error.source();
+    }
+    report
+}
+
+/// Advance `iter` until it ends or `interrupted` is set, checked before every instruction.
+/// `step` is called after every instruction.
+/// Returns the number of executed instructions.
+pub fn run_interruptible<I: FallibleIterator>(
+    iter: &mut I,
+    interrupted: &AtomicBool,
+    mut step: impl FnMut(&I, I::Item) -> Result<(), I::Error>,
+) -> Result<usize, I::Error> {
+    let mut count = 0;
+    while !interrupted.load(Ordering::Relaxed) {
+        let Some(item) = iter.next()? else {
+            break;
+        };
+        step(iter, item)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Write a single executed instruction as a JSON line.
+pub fn write_trace(
+    mut writer: impl Write,
+    pc: usize,
+    awatism: AwaTism,
+    depth: usize,
+) -> Result<(), IOError> {
+    let awatism = awatism.to_string();
+    let (op, arg) = awatism.split_once(' ').unwrap_or((&awatism, "null"));
+    writeln!(
+        writer,
+        "{{\"pc\":{},\"op\":\"{}\",\"arg\":{},\"abyss_depth\":{}}}",
+        pc, op, arg, depth
+    )
+}
+
+/// Format the binary encoding of an instruction,
+/// with the discriminant separated from the operand by a space.
+pub fn bit_string(awatism: &AwaTism) -> Result<String, BitError> {
+    let mut buffer = Vec::new();
+    BitWriteStream::new(&mut buffer, BigEndian).write(awatism)?;
+    let raw = BitReadBuffer::new(&buffer, BigEndian);
+    let mut result = String::with_capacity(AwaTism::MAX_BIT_WIDTH + 1);
+    for i in 0..awatism.bit_width() {
+        if i == 5 {
+            result.push(' ');
+        }
+        result.push(if raw.read_bool(i)? { '1' } else { '0' });
+    }
+    Ok(result)
+}
+
+/// Describe the control flow of `program` as a Graphviz graph with one node per basic block.
+/// Nodes are labeled with the range of lines they contain, edges taken by a jump are labeled `jmp`,
+/// edges after a comparison `true` when the next instruction runs and `skip` when it is skipped.
+/// All edges leaving the program lead to a single `end` node.
+pub fn cfg_dot(program: &Program) -> String {
+    let mut dot = "digraph cfg {\n    node [shape=box];\n".to_string();
+    let blocks = program.basic_blocks();
+    for block in &blocks {
+        dot.push_str(&format!(
+            "    b{} [label=\"{}..{}\"];\n",
+            block.start,
+            block.start + 1,
+            block.end
+        ));
+    }
+    let ends = blocks.is_empty()
+        || blocks
+            .iter()
+            .any(|block| block.successors.contains(&program.len()));
+    if ends {
+        dot.push_str("    end [shape=doublecircle];\n");
+    }
+    for block in &blocks {
+        for &next in &block.successors {
+            let target = if next == program.len() {
+                "end".to_string()
+            } else {
+                format!("b{}", next)
+            };
+            let label = match program[block.last()] {
+                AwaTism::Jump(_) => " [label=\"jmp\"]",
+                AwaTism::EqualTo | AwaTism::LessThan | AwaTism::GreaterThan
+                    if next == block.end =>
+                {
+                    " [label=\"true\"]"
+                }
+                AwaTism::EqualTo | AwaTism::LessThan | AwaTism::GreaterThan => " [label=\"skip\"]",
+                _ => "",
+            };
+            dot.push_str(&format!("    b{} -> {}{};\n", block.start, target, label));
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Static metrics of a [`Program`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub instructions: usize,
+    pub control_flow: usize,
+    pub io: usize,
+    pub arithmetic: usize,
+    pub stack: usize,
+    /// Number of distinct labels defined, jumps to missing labels are not counted.
+    pub labels: usize,
+    pub max_blow: Option<i8>,
+    pub bit_len: usize,
+}
+impl Stats {
+    pub fn new(program: &Program) -> Self {
+        let mut stats = Self {
+            instructions: program.len(),
+            labels: program.defined_labels().count(),
+            bit_len: program.bit_len(),
+            ..Default::default()
+        };
+        for awatism in program {
+            if awatism.is_control_flow() {
+                stats.control_flow += 1;
+            } else if awatism.is_io() {
+                stats.io += 1;
+            } else if awatism.is_arithmetic() {
+                stats.arithmetic += 1;
+            } else {
+                stats.stack += 1;
+            }
+            if let AwaTism::Blow(value) = awatism {
+                stats.max_blow = stats.max_blow.max(Some(*value));
+            }
+        }
+        stats
+    }
+    /// Format as a single JSON object.
+    pub fn to_json(&self) -> String {
+        format!(
+            concat!(
+                "{{\"instructions\":{},\"control_flow\":{},\"io\":{},\"arithmetic\":{},",
+                "\"stack\":{},\"labels\":{},\"max_blow\":{},\"bit_len\":{}}}"
+            ),
+            self.instructions,
+            self.control_flow,
+            self.io,
+            self.arithmetic,
+            self.stack,
+            self.labels,
+            self.max_blow
+                .map_or_else(|| "null".to_string(), |value| value.to_string()),
+            self.bit_len,
+        )
+    }
+}
+impl Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "instructions: {}", self.instructions)?;
+        writeln!(f, "control flow: {}", self.control_flow)?;
+        writeln!(f, "io: {}", self.io)?;
+        writeln!(f, "arithmetic: {}", self.arithmetic)?;
+        writeln!(f, "stack: {}", self.stack)?;
+        writeln!(f, "labels: {}", self.labels)?;
+        match self.max_blow {
+            Some(value) => writeln!(f, "max blow: {}", value)?,
+            None => writeln!(f, "max blow: -")?,
+        }
+        write!(f, "bits: {}", self.bit_len)
+    }
+}
+
+/// Read newline-separated numbers from a file, empty lines are skipped.
+pub fn read_abyss_init(path: impl AsRef<Path>) -> Result<Vec<isize>, Error> {
+    let mut values = Vec::new();
+    for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let value = line
+            .parse()
+            .map_err(|inner| Error::AbyssInitError { line: i + 1, inner })?;
+        values.push(value);
+    }
+    Ok(values)
+}
+
+/// Read `N: comment` lines from a file, mapping 1-based instruction numbers to comments.
+/// Empty lines are skipped, multiple comments for the same instruction are joined.
+pub fn read_annotations(path: impl AsRef<Path>) -> Result<HashMap<usize, String>, Error> {
+    let mut annotations = HashMap::<usize, String>::new();
+    for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |msg: String| Error::AnnotationError { line: i + 1, msg };
+        let (number, comment) = line
+            .split_once(':')
+            .ok_or_else(|| error("expected `N: comment`".to_string()))?;
+        let number = number
+            .trim()
+            .parse()
+            .map_err(|inner: ParseIntError| error(inner.to_string()))?;
+        let comment = comment.trim();
+        annotations
+            .entry(number)
+            .and_modify(|existing| {
+                existing.push_str("; ");
+                existing.push_str(comment);
+            })
+            .or_insert_with(|| comment.to_string());
+    }
+    Ok(annotations)
+}
+
 /// Format of the source code.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum)]
 pub enum SourceFormat {
@@ -60,6 +312,84 @@ impl SourceFormat {
             _ => None,
         }
     }
+    /// File extension used for this format.
+    #[inline]
+    pub const fn extension(&self) -> &'static str {
+        match self {
+            Self::AwaTalk => "awa",
+            Self::AwaTism => "awasm",
+            Self::Binary => "bin",
+        }
+    }
+    /// Load a program in this format from `buffer`.
+    /// `file` is only used for error reporting, `comment` and `macros` only for assembly code.
+    pub fn load<E: Endianness>(
+        self,
+        file: &Path,
+        buffer: &[u8],
+        comment: u8,
+        macros: &MacroTable,
+    ) -> Result<Program, Error> {
+        let program = match self {
+            Self::AwaTalk => program_from_awatalk::<E>(buffer)?,
+            Self::AwaTism => {
+                let options = ParserOptions {
+                    comment,
+                    catch_panics: true,
+                };
+                load_program(file, buffer, macros, &options)?
+            }
+            Self::Binary => {
+                let raw = BitReadBuffer::new(buffer, E::endianness());
+                Program::from_bitbuffer(raw)?
+            }
+        };
+        Ok(program)
+    }
+}
+
+/// Load a program in `format` from `bytes` without touching the filesystem.
+/// This behaves like [`SourceFormat::load`] with `;` as comment character,
+/// except that `!include` is not available, errors will point to a file named `<str>`.
+pub fn load_bytes<E: Endianness>(bytes: &[u8], format: SourceFormat) -> Result<Program, Error> {
+    format.load::<E>(Path::new("<str>"), bytes, b';', &MacroTable::in_memory())
+}
+
+/// Run the program in `src` to completion, reading from `input`.
+/// Returns everything the program printed.
+pub fn run_source(format: SourceFormat, src: &[u8], input: &[u8]) -> Result<String, Error> {
+    let program = load_bytes::<BigEndian>(src, format)?;
+    let mut interpreter = Interpreter::new(Abyss::<isize>::default(), input, Vec::new());
+    interpreter.run(&program).count()?;
+    let (_, _, output) = interpreter.finish()?;
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+/// Describe the version, abyss backend and features this binary was compiled with
+/// as well as the supported source formats.
+pub fn build_info() -> String {
+    let mut info = format!(
+        "{} {}\nbackend: {}\nfeatures: {}\nformats:",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        awa_abyss::DEFAULT_BACKEND,
+        if awa_abyss::FEATURES.is_empty() {
+            "none".to_string()
+        } else {
+            awa_abyss::FEATURES.join(", ")
+        }
+    );
+    for format in SourceFormat::value_variants() {
+        // SAFETY: unwrap: no variant is skipped
+        let value = format.to_possible_value().unwrap();
+        let names = value.get_name_and_aliases().collect::<Vec<_>>();
+        info.push_str(&format!(
+            "\n  {} (.{})",
+            names.join(", "),
+            format.extension()
+        ));
+    }
+    info
 }
 
 /// Describes the location and format of the source code.
@@ -75,26 +405,61 @@ pub struct Source {
         value_hint = ValueHint::FilePath
     )]
     file: PathBuf,
+    #[command(flatten)]
+    options: SourceOptions,
+}
+
+/// Describes how to read source code, independent of where it is read from.
+#[derive(Debug, Clone, Args)]
+pub struct SourceOptions {
     /// Format of the source.
     ///
     /// When no format is given, a guess based on the context is made.
     #[arg(long, short = 'f', value_enum)]
     format: Option<SourceFormat>,
+    /// Character starting a comment in assembly code.
+    #[arg(long, value_name = "CHAR", default_value = ";", value_parser = parse_ascii)]
+    comment: u8,
+    /// Also look for files included by assembly code in DIR, can be given multiple times.
+    ///
+    /// Including files is not possible when reading from stdin.
+    #[arg(long = "include-path", short = 'I', value_name = "DIR", value_hint = ValueHint::DirPath)]
+    include_paths: Vec<PathBuf>,
+    /// Bit order of binary and AwaTalk code, also used when writing them
+    #[arg(long, value_enum, default_value = "big")]
+    endian: Endian,
+}
+#[inline]
+fn parse_ascii(s: &str) -> Result<u8, String> {
+    match s.as_bytes() {
+        [char] if char.is_ascii() => Ok(*char),
+        _ => Err("expected a single ASCII character".to_string()),
+    }
 }
 impl Source {
+    /// Same as [`Source::read`] using the endianness passed with `--endian`.
+    #[inline]
+    pub fn load(&self) -> Result<Program, Error> {
+        match self.options.endian {
+            Endian::Big => self.read::<BigEndian>(),
+            Endian::Little => self.read::<LittleEndian>(),
+        }
+    }
     pub fn read<E: Endianness>(&self) -> Result<Program, Error> {
         let mut buffer = Vec::new();
-        let format = if self.file.to_str() == Some("-") {
+        let from_stdin = self.file.to_str() == Some("-");
+        let format = if from_stdin {
             let mut handle = stdin();
             if handle.is_terminal() {
                 return Err(Error::InputFromTerminal);
             }
             handle.read_to_end(&mut buffer)?;
-            self.format.ok_or(Error::UnknownFormat)?
+            self.options.format.ok_or(Error::UnknownFormat)?
         } else {
             let mut handle = File::open(self.file.clone())?;
             handle.read_to_end(&mut buffer)?;
-            self.format
+            self.options
+                .format
                 .or_else(|| SourceFormat::from_extension(self.file.extension()?.to_str()?))
                 .or_else(|| {
                     if buffer[0..3].eq_ignore_ascii_case("awa".as_bytes()) {
@@ -105,24 +470,74 @@ impl Source {
                 })
                 .ok_or(Error::UnknownFormat)?
         };
-        let program = match format {
-            SourceFormat::AwaTalk => {
-                let (raw, length) = load_awatalk::<E>(&buffer)?;
-                Program::from_bitbuffer_with_length(raw, length)?
-            }
-            SourceFormat::AwaTism => {
-                let macros = MacroTable::default();
-                load_program(&self.file, &buffer, &macros)?
+        if from_stdin {
+            let macros = MacroTable::in_memory();
+            format.load::<E>(Path::new("<stdin>"), &buffer, self.options.comment, &macros)
+        } else {
+            let paths = self.options.include_paths.iter().cloned();
+            let macros = MacroTable::with_include_paths(paths);
+            format.load::<E>(&self.file, &buffer, self.options.comment, &macros)
+        }
+    }
+}
+
+/// Inclusive range of 1-based line numbers, either side can be omitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LineRange {
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+}
+impl LineRange {
+    /// Convert into a range of 0-based indices, clamped to `len`.
+    #[inline]
+    pub fn clamp(&self, len: usize) -> Range<usize> {
+        let start = self.start.unwrap_or(1).saturating_sub(1).min(len);
+        let end = self.end.unwrap_or(len).min(len).max(start);
+        start..end
+    }
+}
+impl FromStr for LineRange {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |s: &str| -> Result<Option<usize>, Self::Err> {
+            let s = s.trim();
+            if s.is_empty() {
+                return Ok(None);
             }
-            SourceFormat::Binary => {
-                let raw = BitReadBuffer::new(&buffer, E::endianness());
-                Program::from_bitbuffer(raw)?
+            match s.parse::<usize>() {
+                Ok(0) => Err("line numbers start at 1".to_string()),
+                Ok(line) => Ok(Some(line)),
+                Err(error) => Err(error.to_string()),
             }
         };
-        Ok(program)
+        let Some((start, end)) = s.split_once("..") else {
+            let line = parse(s)?;
+            return Ok(Self {
+                start: line,
+                end: line,
+            });
+        };
+        let (start, end) = (parse(start)?, parse(end)?);
+        if let (Some(start), Some(end)) = (start, end) {
+            if start > end {
+                return Err(format!("range start {} is after end {}", start, end));
+            }
+        }
+        Ok(Self { start, end })
     }
 }
 
+/// What to do when the output file already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum)]
+pub enum OutputIfExists {
+    /// replace the existing file
+    Overwrite,
+    /// keep the existing file and don't write anything
+    Skip,
+    /// write to the first free `name.N.ext` instead
+    Rename,
+}
+
 /// Describes compiler output location.
 #[derive(Debug, Args)]
 pub struct Out {
@@ -135,35 +550,92 @@ pub struct Out {
         value_hint = ValueHint::FilePath
     )]
     out: Option<PathBuf>,
-    /// Overwrite file if it already exists
-    #[arg(long, short = 'F')]
-    force: Option<bool>,
+    /// Overwrite file if it already exists (same as --output-if-exists overwrite)
+    #[arg(long, short = 'F', conflicts_with = "output_if_exists")]
+    force: bool,
+    /// What to do when the output file already exists.
+    ///
+    /// When not given, an existing file is an error.
+    #[arg(long, value_enum, value_name = "STRATEGY")]
+    output_if_exists: Option<OutputIfExists>,
+    /// Format of the output.
+    ///
+    /// When no format is given, it is guessed from the output file extension, falling back to binary.
+    #[arg(long, short = 't', value_enum)]
+    to: Option<SourceFormat>,
+    /// Put N instructions on each line of AwaTalk output
+    #[arg(long, value_name = "N")]
+    wrap: Option<NonZero<usize>>,
 }
 impl Out {
-    pub fn write(&self, source: &Source, program: &Program) -> Result<(), Error> {
+    /// Encode `program` as binary or AwaTalk code.
+    fn encode<E: Endianness>(
+        &self,
+        program: &Program,
+        format: SourceFormat,
+    ) -> Result<Vec<u8>, Error> {
         let mut buffer = Vec::new();
-        let mut writer = BitWriteStream::new(&mut buffer, BigEndian);
+        let mut writer = BitWriteStream::new(&mut buffer, E::endianness());
         for awatism in program {
             writer.write(awatism)?;
         }
+        let length = writer.bit_len();
+        if format != SourceFormat::AwaTalk {
+            return Ok(buffer);
+        }
+        let raw = BitReadBuffer::new(&buffer, E::endianness());
+        let breaks = self.wrap.map(|wrap| {
+            program
+                .iter()
+                .scan(0, |pos, awatism| {
+                    *pos += awatism.bit_width();
+                    Some(*pos)
+                })
+                .skip(wrap.get() - 1)
+                .step_by(wrap.get())
+        });
+        Ok(save_awatalk(raw, length, breaks.into_iter().flatten())?.into_bytes())
+    }
+    /// Write `program` in the requested format, binary and AwaTalk code use the endianness of `source`.
+    pub fn write(&self, source: &Source, program: &Program) -> Result<(), Error> {
+        let format = self
+            .to
+            .or_else(|| SourceFormat::from_extension(self.out.as_ref()?.extension()?.to_str()?))
+            .unwrap_or(SourceFormat::Binary);
+        let buffer = match (format, source.options.endian) {
+            (SourceFormat::AwaTism, _) => program.disassemble().into_bytes(),
+            (_, Endian::Big) => self.encode::<BigEndian>(program, format)?,
+            (_, Endian::Little) => self.encode::<LittleEndian>(program, format)?,
+        };
         if self.out.as_ref().and_then(|f| f.to_str()) == Some("-") {
             let mut handle = stdout();
             handle.write_all(&buffer)?;
         } else {
             let mut out = self.out.as_ref().cloned().unwrap_or_else(|| {
                 if source.file.to_str() == Some("-") {
-                    PathBuf::from_str("out.bin").unwrap()
+                    PathBuf::from_str("out")
+                        .unwrap()
+                        .with_extension(format.extension())
                 } else {
-                    source.file.with_extension("bin")
+                    source.file.with_extension(format.extension())
                 }
             });
             if *source.file == out {
-                out.set_extension("bin.bin");
+                out.set_extension(format!("{0}.{0}", format.extension()));
             }
-            let mut handle = if self.force.unwrap_or(false) {
-                File::create(out)?
-            } else {
-                File::create_new(out)?
+            let strategy = self
+                .output_if_exists
+                .or(self.force.then_some(OutputIfExists::Overwrite));
+            let mut handle = match strategy {
+                Some(OutputIfExists::Overwrite) => File::create(out)?,
+                Some(OutputIfExists::Skip) if out.exists() => return Ok(()),
+                Some(OutputIfExists::Rename) => create_renamed(&out)?,
+                _ => match File::create_new(&out) {
+                    Err(error) if error.kind() == ErrorKind::AlreadyExists => {
+                        return Err(Error::OutputExists(out))
+                    }
+                    handle => handle?,
+                },
             };
             handle.write_all(&buffer)?;
         }
@@ -171,6 +643,194 @@ impl Out {
     }
 }
 
+/// Create `path`, or the first `name.N.ext` next to it that doesn't exist yet.
+fn create_renamed(path: &Path) -> Result<File, IOError> {
+    let (stem, extension) = (path.file_stem().unwrap_or_default(), path.extension());
+    for n in 0.. {
+        let candidate = if n == 0 {
+            path.to_path_buf()
+        } else {
+            let mut name = stem.to_os_string();
+            name.push(format!(".{}", n));
+            if let Some(extension) = extension {
+                name.push(".");
+                name.push(extension);
+            }
+            path.with_file_name(name)
+        };
+        match File::create_new(candidate) {
+            Err(error) if error.kind() == ErrorKind::AlreadyExists => continue,
+            result => return result,
+        }
+    }
+    unreachable!()
+}
+
+/// Program input used when stdin was already consumed by the source code.
+/// Reading fails instead of silently reporting the end of input.
+struct StdinTaken;
+impl Read for StdinTaken {
+    #[inline]
+    fn read(&mut self, _buf: &mut [u8]) -> Result<usize, IOError> {
+        self.fill_buf().map(|_| 0)
+    }
+}
+impl BufRead for StdinTaken {
+    #[inline]
+    fn fill_buf(&mut self) -> Result<&[u8], IOError> {
+        Err(IOError::other(
+            "stdin was used for the source code, pass the program input with --input",
+        ))
+    }
+    #[inline(always)]
+    fn consume(&mut self, _amt: usize) {}
+}
+
+/// Run `program` to completion on `interpreter`.
+fn run_with<A: awa_core::Abyss<Value = isize>>(
+    program: &Program,
+    mut interpreter: DynInterpreter<'static, A>,
+    verbose: Option<TraceStream>,
+    quiet: bool,
+    abyss_init: Option<&Path>,
+    trace_file: Option<&Path>,
+    start: usize,
+) -> Result<(), Error> {
+    if let Some(path) = abyss_init {
+        interpreter
+            .abyss_mut()
+            .blow_many(read_abyss_init(path)?)
+            .ok_or(RuntimeError::NoSpace)?;
+    }
+    // NOTE: batch output into lines, traces need the output of every instruction right away
+    let flush = match verbose {
+        Some(_) => FlushPolicy::Always,
+        None => FlushPolicy::OnNewline,
+    };
+    interpreter.set_flush(flush);
+    let mut trace = trace_file
+        .map(File::create)
+        .transpose()?
+        .map(BufWriter::new);
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let flag = interrupted.clone();
+    ctrlc::set_handler(move || {
+        // NOTE: the flag is only checked between instructions, a program waiting for input needs a way out
+        if flag.swap(true, Ordering::Relaxed) {
+            std::process::exit(130);
+        }
+    })?;
+    let digits = (program.len() as f64).log10().trunc() as usize + 1;
+    let mut iter = interpreter.run_from(program, start);
+    let mut verbose = verbose.map(|stream| -> Box<dyn Write> {
+        match stream {
+            TraceStream::Stdout => Box::new(stdout()),
+            TraceStream::Stderr => Box::new(stderr()),
+        }
+    });
+    let count = run_interruptible(&mut iter, &interrupted, |iter, (pc, awatism)| {
+        if let Some(verbose) = &mut verbose {
+            // NOTE: program output has to be written before the trace, even when using different streams
+            stdout().flush()?;
+            if matches!(awatism, AwaTism::Print | AwaTism::PrintNum) {
+                writeln!(verbose)?;
+            }
+            writeln!(verbose, "{0:>1$} {2}", pc + 1, digits, awatism)?;
+            verbose.flush()?;
+        }
+        if let Some(trace) = &mut trace {
+            let depth = iter.interpreter().abyss().depth();
+            write_trace(trace, pc, awatism, depth)?;
+        }
+        Ok(())
+    });
+    if let Some(trace) = &mut trace {
+        trace.flush()?;
+    }
+    let exit = iter.exit();
+    // NOTE: output printed before an error still has to show up
+    let finished = interpreter.finish();
+    let count = count?;
+    finished?;
+    if quiet {
+        return Ok(());
+    }
+    if interrupted.load(Ordering::Relaxed) {
+        eprintln!();
+        eprintln!("interrupted after {} instructions", count);
+    } else if verbose.is_some() && exit == Some(Exit::EndOfProgram) {
+        eprintln!("warning: program ended without terminate");
+    }
+    Ok(())
+}
+
+/// Implementation of the abyss used to run a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum)]
+pub enum Backend {
+    /// linked list backed by an arena
+    Linked,
+    /// linked list with a buffer for the top bubbles
+    #[value(name = "buffered", alias = "buffered-linked")]
+    Buffered,
+}
+/// Output stream for tracing executed instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum)]
+pub enum TraceStream {
+    /// mixed into the program output
+    Stdout,
+    Stderr,
+}
+/// Cursor shown in the debugger command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum)]
+pub enum Cursor {
+    RapidBlink,
+    SlowBlink,
+    /// no blinking, for terminals that do not render it
+    Static,
+}
+/// Bit order used for binary and AwaTalk code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum)]
+pub enum Endian {
+    Big,
+    /// for code written by tools using little-endian bit streams
+    Little,
+}
+/// Width of the integers a program is assumed to run with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum)]
+pub enum IntWidth {
+    I8,
+    I16,
+    I32,
+    I64,
+}
+impl IntWidth {
+    #[inline]
+    pub const fn bits(&self) -> u32 {
+        match self {
+            Self::I8 => 8,
+            Self::I16 => 16,
+            Self::I32 => 32,
+            Self::I64 => 64,
+        }
+    }
+}
+/// Construct an abyss with room for `capacity` bubbles.
+trait Preallocate {
+    fn preallocated(capacity: usize) -> Self;
+}
+impl<T: Value> Preallocate for linked::Abyss<T> {
+    #[inline(always)]
+    fn preallocated(capacity: usize) -> Self {
+        Self::with_capacity(capacity)
+    }
+}
+impl<T: Value> Preallocate for Buffered<linked::Abyss<T>> {
+    #[inline(always)]
+    fn preallocated(capacity: usize) -> Self {
+        Self::with_capacity(linked::Abyss::with_capacity(capacity), capacity)
+    }
+}
+
 #[derive(Debug, Parser)]
 #[command(about = "AWA CLI toolkit")]
 pub struct Cli {
@@ -187,20 +847,64 @@ impl Cli {
 pub enum Commands {
     /// Print file content as AwaTisms.
     #[command(arg_required_else_help = true)]
-    Echo(Source),
+    Echo {
+        #[command(flatten)]
+        source: Source,
+        /// Only print lines in START..END (1-based, inclusive, either side can be omitted)
+        #[arg(long, short = 'r', value_name = "START..END")]
+        range: Option<LineRange>,
+        /// Show the binary encoding of each instruction
+        #[arg(long)]
+        bits: bool,
+        /// Only print the instructions, so the output can be assembled again
+        #[arg(long, alias = "raw", conflicts_with = "bits")]
+        plain: bool,
+        /// Append comments from FILE to the instructions, every line has the form `N: comment`
+        #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+        annotations: Option<PathBuf>,
+    },
+    /// Statically analyze program from file or stdin and print warnings.
+    #[command(arg_required_else_help = true)]
+    Check {
+        #[command(flatten)]
+        source: Source,
+        /// Also warn when constant arithmetic overflows integers of this width
+        #[arg(long, value_enum)]
+        int_width: Option<IntWidth>,
+    },
+    /// Print static metrics of program from file or stdin.
+    #[command(arg_required_else_help = true)]
+    Stats {
+        #[command(flatten)]
+        source: Source,
+        /// Print metrics as a JSON object
+        #[arg(long)]
+        json: bool,
+    },
     /// Build program from file or stdin.
     ///
-    /// This will output data in the Binary format and can be ran using
+    /// This will output data in the Binary format by default and can be ran using
     ///
     /// awa run --format binary out.bin
+    ///
+    /// Use --to to convert into another format instead.
     #[command(arg_required_else_help = true)]
     Build {
         #[command(flatten)]
         source: Source,
         #[command(flatten)]
         output: Out,
+        /// Only read and validate the program without writing any output
+        #[arg(long)]
+        dry_run: bool,
+        /// Remove instructions without effect before writing the output
+        #[arg(long)]
+        optimize: bool,
     },
     /// Run program from file or stdin.
+    ///
+    /// Pressing Ctrl-C stops the program at the next instruction,
+    /// pressing it again exits immediately (e.g. while waiting for input).
     #[command(arg_required_else_help = true)]
     Run {
         #[command(flatten)]
@@ -208,6 +912,43 @@ pub enum Commands {
         /// Print every instruction before it is executed
         #[arg(long, short = 'v')]
         verbose: bool,
+        /// Only print the program output, reports about the run are not written to stderr.
+        ///
+        /// Errors are still reported.
+        #[arg(long, short = 'q', conflicts_with = "verbose")]
+        quiet: bool,
+        /// Stream used for printing instructions in verbose mode
+        #[arg(long, value_enum, value_name = "STREAM", default_value = "stderr")]
+        trace_stream: TraceStream,
+        /// Blow newline-separated numbers onto the abyss before running (first line ends up at the bottom)
+        #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+        abyss_init: Option<PathBuf>,
+        /// Write every executed instruction to FILE as JSON lines
+        #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+        trace_file: Option<PathBuf>,
+        /// Abyss implementation to use [default: the one selected at compile time]
+        #[arg(long, value_enum)]
+        backend: Option<Backend>,
+        /// Reserve space for N bubbles before running
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        abyss_capacity: usize,
+        /// Read program input from FILE instead of stdin.
+        ///
+        /// Passing '-' reads from stdin, which is not possible when the source code is read from stdin.
+        #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+        input: Option<PathBuf>,
+        /// Write program output to FILE instead of stdout, overwriting it if it exists
+        #[arg(long, short = 'o', value_name = "FILE", value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+        /// Append to the output file instead of overwriting it
+        #[arg(long, requires = "output")]
+        append: bool,
+        /// Also write the assembled binary to FILE before running, overwriting it if it exists
+        #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+        emit: Option<PathBuf>,
+        /// Start executing at instruction N (1-based) instead of the first one
+        #[arg(long, value_name = "N")]
+        start: Option<NonZero<usize>>,
     },
     /// Debug program from file or stdin.
     #[command(
@@ -218,60 +959,301 @@ Debug program from file or stdin.
 Commands
 - s:      advance a single step (default)
 - s N:    advance N steps
+- n:      step over, continue executing until the next line is reached
 - r:      continue executing until interrupted
 - b:      set breakpoint at current line
 - b N:    set breakpoint at line N
 - b +/-N: set breakpoint relative from current line
+- push N: blow N onto the abyss
+- pop:    pop the top bubble from the abyss
 - q:      quit
 
 Shortcuts
 - Tab/Shift-Tab: switch tabs
 - Ctrl-j/Ctrl-k: scroll view
 - Ctrl-h/Ctrl-l: scroll instructions
+- Esc:           stop running s N or r
 - Ctrl-c:        quit"
     )]
     Debug {
         #[command(flatten)]
         source: Source,
+        /// Answer input requests with lines from this file before asking interactively
+        #[arg(long, short = 'i', value_name = "FILE", value_hint = ValueHint::FilePath)]
+        input: Option<PathBuf>,
+        /// Also write program output to FILE while debugging
+        #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+        tee: Option<PathBuf>,
+        /// Cursor in the command line
+        #[arg(
+            long,
+            value_name = "STYLE",
+            env = "AWA_CURSOR",
+            default_value = "rapid-blink"
+        )]
+        cursor: Cursor,
+        /// Keep N lines visible above the current instruction
+        #[arg(long, value_name = "N", default_value_t = 5)]
+        context: usize,
+    },
+    /// Print the control flow of program from file or stdin as a Graphviz graph.
+    ///
+    /// Every node is a basic block labeled with its lines, render it using
+    ///
+    /// awa cfg program.awasm | dot -Tsvg > cfg.svg
+    #[command(arg_required_else_help = true)]
+    Cfg {
+        #[command(flatten)]
+        source: Source,
     },
+    /// Print the instructions that differ between two programs.
+    ///
+    /// Removed instructions are marked with '-' and the line in OLD,
+    /// added instructions with '+' and the line in NEW,
+    /// changed instructions with '~' and the line in OLD.
+    #[command(arg_required_else_help = true)]
+    Diff {
+        #[arg(value_name = "OLD", value_hint = ValueHint::FilePath)]
+        old: PathBuf,
+        #[arg(value_name = "NEW", value_hint = ValueHint::FilePath)]
+        new: PathBuf,
+        #[command(flatten)]
+        options: SourceOptions,
+    },
+    /// Print version, compiled features and supported formats.
+    Info,
 }
 impl Commands {
     pub fn run(&self) -> Result<(), Error> {
         match self {
-            Self::Echo(source) => {
-                let program = source.read::<BigEndian>()?;
+            Self::Echo {
+                source,
+                range,
+                bits,
+                plain,
+                annotations,
+            } => {
+                let program = source.load()?;
+                let annotations = annotations
+                    .as_ref()
+                    .map(read_annotations)
+                    .transpose()?
+                    .unwrap_or_default();
+                let note = |line: usize| {
+                    annotations
+                        .get(&(line + 1))
+                        .map(|comment| format!(" {} {}", source.options.comment as char, comment))
+                        .unwrap_or_default()
+                };
                 let digits = (program.len() as f64).log10().trunc() as usize + 1;
-                for (line, awatism) in program.into_iter().enumerate() {
+                let range = range
+                    .map(|range| range.clamp(program.len()))
+                    .unwrap_or(0..program.len());
+                for (line, awatism, awascii) in
+                    program.annotated().take(range.end).skip(range.start)
+                {
+                    if *plain {
+                        println!("{}{}", awatism, note(line));
+                        continue;
+                    }
                     // TODO: look ahead for prn instruction and print AWASCII chatacter instead of number
-                    println!("{0:>1$} {2}", line + 1, digits, awatism)
+                    let annotation = awascii
+                        .map(|awascii| format!(" '{}'", awascii.to_ascii().escape_ascii()))
+                        .unwrap_or_default();
+                    if *bits {
+                        let width = AwaTism::MAX_BIT_WIDTH + 1;
+                        let bits = bit_string(awatism)?;
+                        println!(
+                            "{0:>1$} {2:<3$} {4}{5}{6}",
+                            line + 1,
+                            digits,
+                            bits,
+                            width,
+                            awatism,
+                            annotation,
+                            note(line)
+                        )
+                    } else {
+                        println!(
+                            "{0:>1$} {2}{3}{4}",
+                            line + 1,
+                            digits,
+                            awatism,
+                            annotation,
+                            note(line)
+                        )
+                    }
+                }
+            }
+            Self::Check { source, int_width } => {
+                let program = source.load()?;
+                let mut diagnostics = program.check();
+                if let Some(width) = int_width {
+                    diagnostics.extend(program.check_int_width(width.bits()));
+                }
+                for diagnostic in diagnostics {
+                    match diagnostic.pc() {
+                        Some(pc) => println!("warning: line {}: {}", pc + 1, diagnostic),
+                        None => println!("warning: {}", diagnostic),
+                    }
                 }
             }
-            Self::Build { source, output } => {
-                let program = source.read::<BigEndian>()?;
-                output.write(source, &program)?;
+            Self::Stats { source, json } => {
+                let stats = Stats::new(&source.load()?);
+                if *json {
+                    println!("{}", stats.to_json());
+                } else {
+                    println!("{}", stats);
+                }
             }
-            Self::Run { source, verbose } => {
-                let (program, abyss) = (source.read::<BigEndian>()?, Abyss::<isize>::default());
-                let mut interpreter = Interpreter::new(abyss, BufReader::new(stdin()), stdout());
-                if *verbose {
-                    let digits = (program.len() as f64).log10().trunc() as usize + 1;
-                    interpreter.run(&program).for_each(|(pc, awatism)| {
-                        if matches!(awatism, AwaTism::Print) {
-                            stdout().flush()?;
-                            eprintln!();
-                        }
-                        eprintln!("{0:>1$} {2}", pc + 1, digits, awatism);
-                        Ok(())
-                    })?;
+            Self::Build {
+                source,
+                output,
+                dry_run,
+                optimize,
+            } => {
+                let mut program = source.load()?;
+                if *optimize {
+                    program = awa_core::optimize(&program);
+                }
+                if *dry_run {
+                    println!("program is valid ({} instructions)", program.len());
                 } else {
-                    interpreter.run(&program).last()?;
+                    output.write(source, &program)?;
+                }
+            }
+            Self::Run {
+                source,
+                verbose,
+                quiet,
+                trace_stream,
+                abyss_init,
+                trace_file,
+                backend,
+                abyss_capacity,
+                input,
+                output,
+                append,
+                emit,
+                start,
+            } => {
+                let source_from_stdin = source.file.to_str() == Some("-");
+                let input: Box<dyn BufRead> = match input.as_deref() {
+                    Some(path) if path.to_str() != Some("-") => {
+                        Box::new(BufReader::new(File::open(path)?))
+                    }
+                    Some(_) if source_from_stdin => return Err(Error::StdinConflict),
+                    None if source_from_stdin => Box::new(StdinTaken),
+                    _ => Box::new(BufReader::new(stdin())),
+                };
+                let program = source.load()?;
+                let start = start.map_or(0, |start| start.get() - 1);
+                if start >= program.len() && start > 0 {
+                    return Err(Error::StartOutOfRange {
+                        start: start + 1,
+                        len: program.len(),
+                    });
+                }
+                // NOTE: only truncate the output once the program is known to run
+                let output: Box<dyn Write> = match output {
+                    Some(path) => Box::new(BufWriter::new(
+                        OpenOptions::new()
+                            .create(true)
+                            .append(*append)
+                            .truncate(!*append)
+                            .write(true)
+                            .open(path)?,
+                    )),
+                    None => Box::new(BufWriter::new(stdout())),
+                };
+                if let Some(emit) = emit {
+                    let output = Out {
+                        out: Some(emit.clone()),
+                        force: true,
+                        output_if_exists: None,
+                        to: Some(SourceFormat::Binary),
+                        wrap: None,
+                    };
+                    output.write(source, &program)?;
+                }
+                let (abyss_init, trace_file) = (abyss_init.as_deref(), trace_file.as_deref());
+                let capacity = *abyss_capacity;
+                let verbose = verbose.then_some(*trace_stream);
+                match backend {
+                    None => run_with(
+                        &program,
+                        Interpreter::new_dyn(Abyss::<isize>::preallocated(capacity), input, output),
+                        verbose,
+                        *quiet,
+                        abyss_init,
+                        trace_file,
+                        start,
+                    )?,
+                    Some(Backend::Linked) => run_with(
+                        &program,
+                        Interpreter::new_dyn(
+                            linked::Abyss::<isize>::preallocated(capacity),
+                            input,
+                            output,
+                        ),
+                        verbose,
+                        *quiet,
+                        abyss_init,
+                        trace_file,
+                        start,
+                    )?,
+                    Some(Backend::Buffered) => run_with(
+                        &program,
+                        Interpreter::new_dyn(
+                            Buffered::<linked::Abyss<isize>>::preallocated(capacity),
+                            input,
+                            output,
+                        ),
+                        verbose,
+                        *quiet,
+                        abyss_init,
+                        trace_file,
+                        start,
+                    )?,
                 }
             }
-            Self::Debug { source } => {
-                let (program, abyss) = (source.read::<BigEndian>()?, Abyss::<isize>::default());
-                let mut debugger = Debugger::new(&program, abyss);
+            Self::Debug {
+                source,
+                input,
+                tee,
+                cursor,
+                context,
+            } => {
+                let (program, abyss) = (source.load()?, Abyss::<isize>::default());
+                let mut debugger = Debugger::new(&program, abyss)
+                    .with_cursor_style(match cursor {
+                        Cursor::RapidBlink => CursorStyle::RapidBlink,
+                        Cursor::SlowBlink => CursorStyle::SlowBlink,
+                        Cursor::Static => CursorStyle::Static,
+                    })
+                    .with_program_context(*context);
+                if let Some(path) = input {
+                    debugger.queue_input(std::fs::read_to_string(path)?);
+                }
+                if let Some(path) = tee {
+                    debugger.tee_output(File::create(path)?);
+                }
                 debugger.run()?;
             }
+            Self::Cfg { source } => print!("{}", cfg_dot(&source.load()?)),
+            Self::Diff { old, new, options } => {
+                let read = |file: &PathBuf| {
+                    Source {
+                        file: file.clone(),
+                        options: options.clone(),
+                    }
+                    .load()
+                };
+                for diff in read(old)?.diff(&read(new)?) {
+                    println!("{}", diff);
+                }
+            }
+            Self::Info => println!("{}", build_info()),
         }
         Ok(())
     }
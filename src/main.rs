@@ -1,7 +1,12 @@
+use std::process::ExitCode;
+
 use clap::Parser;
 use rusty_awa::*;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    Cli::parse().run()?;
-    Ok(())
+fn main() -> ExitCode {
+    if let Err(error) = Cli::parse().run() {
+        eprintln!("{}", report(&error));
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,35 @@
+use awa_asm::parse_program;
+use rusty_awa::cfg_dot;
+
+/// Count node and edge statements, ignoring the graph-wide node attributes.
+fn count(dot: &str) -> (usize, usize) {
+    let statements = dot
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.ends_with(';'));
+    let (edges, nodes): (Vec<_>, Vec<_>) = statements
+        .filter(|line| !line.starts_with("node "))
+        .partition(|line| line.contains("->"));
+    (nodes.len(), edges.len())
+}
+
+#[test]
+fn loop_with_condition() {
+    let program =
+        parse_program("blo 5\nlbl 0\nblo 1\nsub\ndpl\nblo 0\neql\ntrm\npop\njmp 0").unwrap();
+    let dot = cfg_dot(&program);
+    assert!(dot.starts_with("digraph cfg {\n"));
+    assert_eq!(count(&dot), (4, 4));
+    assert!(dot.contains("    b2 -> b7 [label=\"true\"];\n"));
+    assert!(dot.contains("    b2 -> b8 [label=\"skip\"];\n"));
+    assert!(dot.contains("    b8 -> b2 [label=\"jmp\"];\n"));
+    assert!(!dot.contains("end"));
+}
+
+#[test]
+fn falling_off_the_end() {
+    let dot = cfg_dot(&parse_program("blo 1\npr1").unwrap());
+    // NOTE: the end node counts as well
+    assert_eq!(count(&dot), (2, 1));
+    assert!(dot.contains("    b0 -> end;\n"));
+}
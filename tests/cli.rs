@@ -0,0 +1,575 @@
+use std::{
+    fs,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+/// Fresh directory for the files of a single test.
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rusty-awa-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn awa(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_awa"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Run with `stdin` piped into the process.
+fn awa_with_stdin(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_awa"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn output_survives_invalid_start() {
+    let dir = scratch("invalid-start");
+    let (source, output) = (dir.join("program.awasm"), dir.join("out.txt"));
+    fs::write(&source, "blo 1\nprn\ntrm\n").unwrap();
+    fs::write(&output, "previous run").unwrap();
+    let result = awa(&[
+        "run",
+        source.to_str().unwrap(),
+        "--output",
+        output.to_str().unwrap(),
+        "--start",
+        "10",
+    ]);
+    assert!(!result.status.success());
+    assert_eq!(fs::read_to_string(&output).unwrap(), "previous run");
+}
+
+#[test]
+fn output_survives_invalid_source() {
+    let dir = scratch("invalid-source");
+    let (source, output) = (dir.join("program.awasm"), dir.join("out.txt"));
+    fs::write(&source, "nope\n").unwrap();
+    fs::write(&output, "previous run").unwrap();
+    let result = awa(&[
+        "run",
+        source.to_str().unwrap(),
+        "--output",
+        output.to_str().unwrap(),
+    ]);
+    assert!(!result.status.success());
+    assert_eq!(fs::read_to_string(&output).unwrap(), "previous run");
+}
+
+#[test]
+fn diff_uses_source_options() {
+    let dir = scratch("diff-options");
+    let (old, new) = (dir.join("old.awasm"), dir.join("new.awasm"));
+    fs::write(&old, "blo 1 # one\nprn\n").unwrap();
+    fs::write(&new, "blo 2 # two\nprn\ntrm\n").unwrap();
+    let (old, new) = (old.to_str().unwrap(), new.to_str().unwrap());
+    assert!(!awa(&["diff", old, new]).status.success());
+    let result = awa(&["diff", "--comment", "#", old, new]);
+    assert!(result.status.success());
+    assert_eq!(
+        String::from_utf8(result.stdout).unwrap(),
+        "~1 blo 1 -> blo 2\n+3 trm\n"
+    );
+}
+
+#[test]
+fn little_endian_round_trip() {
+    let dir = scratch("little-endian");
+    let source = dir.join("program.awasm");
+    fs::write(&source, "blo 5\npr1\nblo -3\npr1\ntrm\n").unwrap();
+    let source = source.to_str().unwrap();
+    for (name, endian) in [
+        ("big.bin", "big"),
+        ("little.bin", "little"),
+        ("little.awa", "little"),
+    ] {
+        let out = dir.join(name);
+        let out = out.to_str().unwrap();
+        assert!(awa(&["build", source, "--endian", endian, "-o", out])
+            .status
+            .success());
+        let result = awa(&["run", out, "--endian", endian]);
+        assert!(result.status.success());
+        assert_eq!(String::from_utf8(result.stdout).unwrap(), "5-3");
+    }
+    assert_ne!(
+        fs::read(dir.join("big.bin")).unwrap(),
+        fs::read(dir.join("little.bin")).unwrap()
+    );
+}
+
+#[test]
+fn abyss_init_seeds_bubbles() {
+    let dir = scratch("abyss-init");
+    let (source, init) = (dir.join("add.awasm"), dir.join("init.txt"));
+    fs::write(&source, "4dd\npr1\ntrm\n").unwrap();
+    fs::write(&init, "10\n\n20\n").unwrap();
+    let (source, init) = (source.to_str().unwrap(), init.to_str().unwrap());
+    let result = awa(&["run", source, "--abyss-init", init]);
+    assert!(result.status.success());
+    assert_eq!(String::from_utf8(result.stdout).unwrap(), "30");
+
+    fs::write(init, "10\ntwenty\n").unwrap();
+    let result = awa(&["run", source, "--abyss-init", init]);
+    assert!(!result.status.success());
+    assert!(String::from_utf8(result.stderr)
+        .unwrap()
+        .contains("line 2 of abyss init file"));
+}
+
+#[test]
+fn echo_range_keeps_line_numbers() {
+    let dir = scratch("echo-range");
+    let source = dir.join("program.awasm");
+    fs::write(&source, "blo 1\nblo 2\n4dd\npr1\ntrm\n").unwrap();
+    let source = source.to_str().unwrap();
+    let echo = |range: &str| {
+        let result = awa(&["echo", source, "--range", range]);
+        assert!(result.status.success());
+        String::from_utf8(result.stdout).unwrap()
+    };
+    assert_eq!(echo("2..3"), "2 blo 2 'a'\n3 4dd\n");
+    assert_eq!(echo("4..9"), "4 pr1\n5 trm\n");
+    assert_eq!(echo("..1"), echo("1"));
+    assert!(!awa(&["echo", source, "--range", "3..2"]).status.success());
+    assert!(!awa(&["echo", source, "--range", "0..2"]).status.success());
+}
+
+#[test]
+fn build_converts_between_formats() {
+    let dir = scratch("build-formats");
+    let source = dir.join("program.awasm");
+    fs::write(&source, "lbl 3\nblo -5\nsrn 2\nprn\njmp 3\ntrm\n").unwrap();
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+    let (awatalk, awasm) = (path("program.awa"), path("round-trip.awasm"));
+    assert!(awa(&[
+        "build",
+        source.to_str().unwrap(),
+        "--to",
+        "awatalk",
+        "-o",
+        &awatalk
+    ])
+    .status
+    .success());
+    assert!(fs::read_to_string(&awatalk).unwrap().starts_with("awa"));
+    assert!(awa(&["build", &awatalk, "-o", &awasm]).status.success());
+    let echo = |file: &str| awa(&["echo", file]).stdout;
+    assert_eq!(echo(&awasm), echo(source.to_str().unwrap()));
+    assert_eq!(echo(&awatalk), echo(source.to_str().unwrap()));
+}
+
+#[test]
+fn trace_file_has_one_line_per_instruction() {
+    let dir = scratch("trace-file");
+    let (source, trace) = (dir.join("program.awasm"), dir.join("trace.jsonl"));
+    fs::write(&source, "blo 1\nblo 2\n4dd\npr1\ntrm\nblo 3\n").unwrap();
+    let result = awa(&[
+        "run",
+        source.to_str().unwrap(),
+        "--trace-file",
+        trace.to_str().unwrap(),
+    ]);
+    assert!(result.status.success());
+    assert_eq!(
+        fs::read_to_string(&trace).unwrap(),
+        concat!(
+            "{\"pc\":0,\"op\":\"blo\",\"arg\":1,\"abyss_depth\":1}\n",
+            "{\"pc\":1,\"op\":\"blo\",\"arg\":2,\"abyss_depth\":2}\n",
+            "{\"pc\":2,\"op\":\"4dd\",\"arg\":null,\"abyss_depth\":1}\n",
+            "{\"pc\":3,\"op\":\"pr1\",\"arg\":null,\"abyss_depth\":0}\n",
+            "{\"pc\":4,\"op\":\"trm\",\"arg\":null,\"abyss_depth\":0}\n",
+        )
+    );
+}
+
+#[test]
+fn backends_agree() {
+    let corpus = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let (source, input) = (corpus.join("fibs.awasm"), corpus.join("fibs.in"));
+    let outputs = ["linked", "buffered"].map(|backend| {
+        let result = awa(&[
+            "run",
+            source.to_str().unwrap(),
+            "--input",
+            input.to_str().unwrap(),
+            "--backend",
+            backend,
+        ]);
+        assert!(result.status.success(), "{backend}");
+        result.stdout
+    });
+    assert_eq!(outputs[0], fs::read(corpus.join("fibs.out")).unwrap());
+    assert_eq!(outputs[0], outputs[1]);
+}
+
+#[test]
+fn echo_bits_match_encoding() {
+    let dir = scratch("echo-bits");
+    let (source, binary) = (dir.join("program.awasm"), dir.join("program.bin"));
+    fs::write(&source, "blo 2\nsrn 2\npr1\n").unwrap();
+    let result = awa(&["echo", "--bits", source.to_str().unwrap()]);
+    assert!(result.status.success());
+    assert_eq!(
+        String::from_utf8(result.stdout).unwrap(),
+        "1 00101 00000010 blo 2 'a'\n2 01001 00010    srn 2\n3 00010          pr1\n"
+    );
+    let args = [
+        "build",
+        source.to_str().unwrap(),
+        "-o",
+        binary.to_str().unwrap(),
+    ];
+    assert!(awa(&args).status.success());
+    // NOTE: the same bits back to back, padded to a whole byte
+    let bits = concat!("0010100000010", "0100100010", "00010", "0000");
+    let expected = (0..bits.len())
+        .step_by(8)
+        .map(|i| u8::from_str_radix(&bits[i..i + 8], 2).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(fs::read(&binary).unwrap(), expected);
+}
+
+#[test]
+fn abyss_capacity_keeps_output() {
+    let corpus = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let (source, input) = (corpus.join("fibs.awasm"), corpus.join("fibs.in"));
+    for backend in ["linked", "buffered"] {
+        for capacity in ["0", "100000"] {
+            let result = awa(&[
+                "run",
+                source.to_str().unwrap(),
+                "--input",
+                input.to_str().unwrap(),
+                "--backend",
+                backend,
+                "--abyss-capacity",
+                capacity,
+            ]);
+            assert!(result.status.success(), "{backend} {capacity}");
+            assert_eq!(result.stdout, fs::read(corpus.join("fibs.out")).unwrap());
+        }
+    }
+}
+
+#[test]
+fn existing_output_needs_force() {
+    let dir = scratch("output-exists");
+    let (source, output) = (dir.join("program.awasm"), dir.join("program.awa"));
+    fs::write(&source, "blo 1\npr1\ntrm\n").unwrap();
+    fs::write(&output, "previous build").unwrap();
+    let build = |force: bool| {
+        let mut args = vec![
+            "build",
+            source.to_str().unwrap(),
+            "-o",
+            output.to_str().unwrap(),
+        ];
+        if force {
+            args.push("--force");
+        }
+        awa(&args)
+    };
+    let result = build(false);
+    assert!(!result.status.success());
+    let stderr = String::from_utf8(result.stderr).unwrap();
+    assert!(stderr.contains("already exists, use --force"), "{stderr}");
+    assert_eq!(fs::read_to_string(&output).unwrap(), "previous build");
+    assert!(build(true).status.success());
+    assert_ne!(fs::read_to_string(&output).unwrap(), "previous build");
+}
+
+#[test]
+fn output_if_exists_strategies() {
+    let dir = scratch("output-if-exists");
+    let source = dir.join("program.awasm");
+    fs::write(&source, "blo 1\npr1\ntrm\n").unwrap();
+    let build = |output: &PathBuf, strategy: &str| {
+        let (source, output) = (source.to_str().unwrap(), output.to_str().unwrap());
+        let result = awa(&[
+            "build",
+            source,
+            "-o",
+            output,
+            "--output-if-exists",
+            strategy,
+        ]);
+        assert!(result.status.success(), "{strategy}");
+    };
+    let output = dir.join("program.awa");
+    let fresh = dir.join("fresh.awa");
+    build(&fresh, "skip");
+    let built = fs::read_to_string(&fresh).unwrap();
+    fs::write(&output, "previous build").unwrap();
+    build(&output, "skip");
+    assert_eq!(fs::read_to_string(&output).unwrap(), "previous build");
+    build(&output, "rename");
+    build(&output, "rename");
+    assert_eq!(fs::read_to_string(&output).unwrap(), "previous build");
+    assert_eq!(
+        fs::read_to_string(dir.join("program.1.awa")).unwrap(),
+        built
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("program.2.awa")).unwrap(),
+        built
+    );
+    build(&output, "overwrite");
+    assert_eq!(fs::read_to_string(&output).unwrap(), built);
+    assert!(!dir.join("program.3.awa").exists());
+}
+
+#[test]
+fn stdin_for_source_and_input() {
+    let source = b"r3d\npr1\ntrm\n";
+    let result = awa_with_stdin(&["run", "-", "--format", "awasm", "--input", "-"], source);
+    assert!(!result.status.success());
+    let stderr = String::from_utf8(result.stderr).unwrap();
+    assert!(stderr.contains("stdin can't be used for both"), "{stderr}");
+    // NOTE: without --input the program must not silently read nothing
+    let result = awa_with_stdin(&["run", "-", "--format", "awasm"], source);
+    assert!(!result.status.success());
+    let stderr = String::from_utf8(result.stderr).unwrap();
+    assert!(
+        stderr.contains("pass the program input with --input"),
+        "{stderr}"
+    );
+    let dir = scratch("stdin-source");
+    let input = dir.join("input.txt");
+    fs::write(&input, "42\n").unwrap();
+    let args = [
+        "run",
+        "-",
+        "--format",
+        "awasm",
+        "--input",
+        input.to_str().unwrap(),
+    ];
+    let result = awa_with_stdin(&args, source);
+    assert!(result.status.success());
+    assert_eq!(result.stdout, b"42");
+}
+
+#[test]
+fn plain_echo_reassembles() {
+    let dir = scratch("plain-echo");
+    let corpus = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let (binary, rebuilt) = (dir.join("fibs.bin"), dir.join("rebuilt.bin"));
+    let source = corpus.join("fibs.awasm");
+    let args = [
+        "build",
+        source.to_str().unwrap(),
+        "-o",
+        binary.to_str().unwrap(),
+    ];
+    assert!(awa(&args).status.success());
+    let echo = awa(&["echo", binary.to_str().unwrap(), "--plain"]);
+    assert!(echo.status.success());
+    let args = [
+        "build",
+        "-",
+        "--format",
+        "awasm",
+        "-o",
+        rebuilt.to_str().unwrap(),
+    ];
+    let result = awa_with_stdin(&args, &echo.stdout);
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    assert_eq!(fs::read(&rebuilt).unwrap(), fs::read(&binary).unwrap());
+}
+
+#[test]
+fn run_emits_binary() {
+    let dir = scratch("run-emit");
+    let corpus = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let source = corpus.join("hello.awasm");
+    let (emitted, built) = (dir.join("emitted.bin"), dir.join("built.bin"));
+    let result = awa(&[
+        "run",
+        source.to_str().unwrap(),
+        "--emit",
+        emitted.to_str().unwrap(),
+    ]);
+    assert!(result.status.success());
+    assert_eq!(result.stdout, fs::read(corpus.join("hello.out")).unwrap());
+    let build = awa(&[
+        "build",
+        source.to_str().unwrap(),
+        "-o",
+        built.to_str().unwrap(),
+    ]);
+    assert!(build.status.success());
+    assert_eq!(fs::read(&emitted).unwrap(), fs::read(&built).unwrap());
+    let rerun = awa(&["run", emitted.to_str().unwrap()]);
+    assert_eq!(rerun.stdout, result.stdout);
+}
+
+#[test]
+fn start_skips_instructions() {
+    let dir = scratch("start");
+    let source = dir.join("program.awasm");
+    fs::write(&source, "blo 1\npr1\nblo 2\npr1\ntrm\n").unwrap();
+    let source = source.to_str().unwrap();
+    let run = |start: &str| awa(&["run", source, "--start", start]);
+    assert_eq!(run("1").stdout, b"12");
+    assert_eq!(run("3").stdout, b"2");
+    let result = run("6");
+    assert!(!result.status.success());
+    let stderr = String::from_utf8(result.stderr).unwrap();
+    assert!(stderr.contains("only has 5 instructions"), "{stderr}");
+}
+
+#[test]
+fn buffered_output_survives_failed_run() {
+    let dir = scratch("buffered-output");
+    let source = dir.join("program.awasm");
+    // prints "1\n2" and fails on the last pr1
+    fs::write(&source, "blo 1\npr1\nblo 63\nprn\nblo 2\npr1\npr1\ntrm\n").unwrap();
+    let result = awa(&["run", source.to_str().unwrap()]);
+    assert!(!result.status.success());
+    assert_eq!(result.stdout, b"1\n2");
+}
+
+#[test]
+fn info_mentions_backend() {
+    let result = awa(&["info"]);
+    assert!(result.status.success());
+    let info = String::from_utf8(result.stdout).unwrap();
+    assert!(
+        info.contains(&format!("backend: {}", awa_abyss::DEFAULT_BACKEND)),
+        "{info}"
+    );
+    assert!(info.contains(env!("CARGO_PKG_VERSION")));
+    for format in ["awatalk", "awatism", "binary"] {
+        assert!(info.contains(format), "{format} missing from {info}");
+    }
+}
+
+#[test]
+fn echo_merges_annotations() {
+    let dir = scratch("annotations");
+    let (source, binary, notes) = (
+        dir.join("program.awasm"),
+        dir.join("program.bin"),
+        dir.join("notes.txt"),
+    );
+    fs::write(&source, "blo 1\npr1\ntrm\n").unwrap();
+    fs::write(&notes, "2: show it\n\n1: seed\n2: again\n").unwrap();
+    let (binary, notes) = (binary.to_str().unwrap(), notes.to_str().unwrap());
+    assert!(awa(&["build", source.to_str().unwrap(), "-o", binary])
+        .status
+        .success());
+    let result = awa(&["echo", binary, "--plain", "--annotations", notes]);
+    assert!(result.status.success());
+    assert_eq!(
+        String::from_utf8(result.stdout).unwrap(),
+        "blo 1 ; seed\npr1 ; show it; again\ntrm\n"
+    );
+
+    fs::write(dir.join("notes.txt"), "1 missing colon\n").unwrap();
+    let result = awa(&["echo", binary, "--annotations", notes]);
+    assert!(!result.status.success());
+    assert!(String::from_utf8(result.stderr)
+        .unwrap()
+        .contains("line 1 of annotation file"));
+}
+
+#[test]
+fn failures_print_their_cause() {
+    let dir = scratch("cause-chain");
+    let source = dir.join("program.awasm");
+    fs::write(&source, "blo 1\nnope\n").unwrap();
+    let result = awa(&["run", source.to_str().unwrap()]);
+    assert!(!result.status.success());
+    let stderr = String::from_utf8(result.stderr).unwrap();
+    let mut lines = stderr.lines();
+    assert_eq!(lines.next(), Some("error: failed to assemble program"));
+    let cause = lines.next().unwrap();
+    assert!(
+        cause.starts_with("  caused by: ") && cause.contains(":2:"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn run_reads_input_file() {
+    let corpus = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let (source, input) = (corpus.join("cat_lines.awasm"), corpus.join("cat_lines.in"));
+    let result = awa(&[
+        "run",
+        source.to_str().unwrap(),
+        "--input",
+        input.to_str().unwrap(),
+    ]);
+    assert!(result.status.success());
+    assert_eq!(
+        result.stdout,
+        fs::read(corpus.join("cat_lines.out")).unwrap()
+    );
+}
+
+#[test]
+fn build_dry_run_writes_nothing() {
+    let dir = scratch("dry-run");
+    let (valid, invalid, output) = (
+        dir.join("valid.awasm"),
+        dir.join("invalid.awasm"),
+        dir.join("out.bin"),
+    );
+    fs::write(&valid, "blo 1\npr1\ntrm\n").unwrap();
+    fs::write(&invalid, "blo 1\nnope\n").unwrap();
+    let build = |source: &PathBuf| {
+        awa(&[
+            "build",
+            source.to_str().unwrap(),
+            "-o",
+            output.to_str().unwrap(),
+            "--dry-run",
+        ])
+    };
+    let result = build(&valid);
+    assert!(result.status.success());
+    assert_eq!(result.stdout, b"program is valid (3 instructions)\n");
+    assert!(!output.exists());
+    let result = build(&invalid);
+    assert!(!result.status.success());
+    assert!(String::from_utf8(result.stderr)
+        .unwrap()
+        .contains("failed to assemble program"));
+    assert!(!output.exists());
+}
+
+#[test]
+fn quiet_run_only_prints_program_output() {
+    let dir = scratch("quiet");
+    let source = dir.join("program.awasm");
+    fs::write(&source, "blo 4\nblo 2\n4dd\npr1\ntrm\n").unwrap();
+    let source = source.to_str().unwrap();
+    let result = awa(&["run", "--quiet", source]);
+    assert!(result.status.success());
+    assert_eq!(result.stdout, b"6");
+    assert!(result.stderr.is_empty(), "{:?}", result.stderr);
+    assert!(!awa(&["run", "-q", "-v", source]).status.success());
+
+    let failing = dir.join("failing.awasm");
+    fs::write(&failing, "blo 1\npr1\npr1\ntrm\n").unwrap();
+    let result = awa(&["run", "-q", failing.to_str().unwrap()]);
+    assert!(!result.status.success());
+    assert_eq!(result.stdout, b"1");
+    assert!(!result.stderr.is_empty());
+}
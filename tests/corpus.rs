@@ -0,0 +1,47 @@
+//! Runs every program in `tests/corpus` and compares its output.
+//! A program `name.ext` reads from `name.in` (if present) and has to print exactly `name.out`,
+//! every program is run once for each abyss backend.
+
+use std::{fs, path::Path};
+
+use awa_abyss::{linked, Buffered};
+use awa_core::{Abyss, BigEndian};
+use awa_interpreter::{FallibleIterator, Interpreter};
+use rusty_awa::{load_bytes, SourceFormat};
+
+fn run(abyss: impl Abyss<Value = isize>, program: &awa_core::Program, input: &[u8]) -> String {
+    let mut interpreter = Interpreter::new(abyss, input, Vec::new());
+    interpreter.run(program).count().unwrap();
+    let (_, _, output) = interpreter.finish().unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn corpus() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let mut programs = fs::read_dir(&root)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .and_then(|extension| SourceFormat::from_extension(extension.to_str()?))
+                .is_some()
+        })
+        .collect::<Vec<_>>();
+    programs.sort();
+    assert!(!programs.is_empty());
+    for path in programs {
+        let format = SourceFormat::from_extension(path.extension().unwrap().to_str().unwrap());
+        let program = load_bytes::<BigEndian>(&fs::read(&path).unwrap(), format.unwrap()).unwrap();
+        let input = fs::read(path.with_extension("in")).unwrap_or_default();
+        let expected = fs::read_to_string(path.with_extension("out")).unwrap();
+        let linked = run(linked::Abyss::default(), &program, &input);
+        assert_eq!(linked, expected, "{} (linked)", path.display());
+        let buffered = run(
+            Buffered::<linked::Abyss<isize>>::default(),
+            &program,
+            &input,
+        );
+        assert_eq!(buffered, expected, "{} (buffered)", path.display());
+    }
+}
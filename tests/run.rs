@@ -0,0 +1,69 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use awa_abyss::linked::Abyss;
+use awa_asm::parse_program;
+use awa_core::{encode_awatalk, BigEndian, BitReadBuffer, BitReadStream, BitWriteStream};
+use awa_interpreter::Interpreter;
+use rusty_awa::{load_bytes, run_interruptible, run_source, SourceFormat};
+
+#[test]
+fn interrupt_stops_between_instructions() {
+    let program = parse_program("lbl 0\nblo 1\npop\njmp 0").unwrap();
+    let mut interpreter = Interpreter::new(Abyss::<isize>::default(), &[][..], Vec::new());
+    let mut iter = interpreter.run(&program);
+    let interrupted = AtomicBool::new(false);
+    let mut steps = 0;
+    let count = run_interruptible(&mut iter, &interrupted, |_, _| {
+        steps += 1;
+        if steps == 5 {
+            interrupted.store(true, Ordering::Relaxed);
+        }
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(count, 5);
+    assert!(iter.pc().is_some());
+}
+
+#[test]
+fn run_source_hello_world() {
+    let src = include_bytes!("corpus/hello.awasm");
+    let output = run_source(SourceFormat::AwaTism, src, b"").unwrap();
+    assert_eq!(output, include_str!("corpus/hello.out"));
+    let echo = run_source(SourceFormat::AwaTism, b"r3d\npr1\ntrm", b"42\n").unwrap();
+    assert_eq!(echo, "42");
+    assert!(run_source(SourceFormat::AwaTism, b"nope", b"").is_err());
+}
+
+#[test]
+fn load_bytes_in_every_format() {
+    let awasm = b"lbl 3\nblo -5\n!str \"awa\"\nsrn 2\nprn\njmp 3\ntrm\n";
+    let expected = load_bytes::<BigEndian>(awasm, SourceFormat::AwaTism).unwrap();
+    let mut binary = Vec::new();
+    let mut writer = BitWriteStream::new(&mut binary, BigEndian);
+    for awatism in &expected {
+        writer.write(awatism).unwrap();
+    }
+    let length = writer.bit_len();
+    let mut bits = BitReadStream::new(BitReadBuffer::new(&binary, BigEndian));
+    let awatalk = encode_awatalk((0..length).map(|_| bits.read_bool().unwrap()));
+
+    let from_binary = load_bytes::<BigEndian>(&binary, SourceFormat::Binary).unwrap();
+    let from_awatalk = load_bytes::<BigEndian>(awatalk.as_bytes(), SourceFormat::AwaTalk).unwrap();
+    assert_eq!(from_binary.instructions(), expected.instructions());
+    assert_eq!(from_awatalk.instructions(), expected.instructions());
+    assert_eq!(from_awatalk.labels(), expected.labels());
+}
+
+#[test]
+fn report_renders_source_chain() {
+    let error = rusty_awa::Error::DebugError(awa_debug::Error::RuntimeError(
+        awa_interpreter::Error::DivisionByZero,
+    ));
+    assert_eq!(
+        rusty_awa::report(&error),
+        "error: debugger failed\n  caused by: division by zero"
+    );
+    let error = rusty_awa::Error::RuntimeError(awa_interpreter::Error::DivisionByZero);
+    assert_eq!(rusty_awa::report(&error), "error: division by zero");
+}
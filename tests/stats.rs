@@ -0,0 +1,32 @@
+use awa_asm::parse_program;
+use rusty_awa::Stats;
+
+#[test]
+fn stats_of_known_program() {
+    let program =
+        parse_program("lbl 1\nblo 5\nblo -7\n4dd\nprn\nlbl 1\njmp 2\njmp 1\ntrm").unwrap();
+    let stats = Stats::new(&program);
+    assert_eq!(
+        stats,
+        Stats {
+            instructions: 9,
+            control_flow: 5,
+            io: 1,
+            arithmetic: 1,
+            stack: 2,
+            labels: 1,
+            max_blow: Some(5),
+            bit_len: 9 * 5 + 2 * 8 + 4 * 5,
+        }
+    );
+    assert_eq!(
+        stats.to_json(),
+        concat!(
+            r#"{"instructions":9,"control_flow":5,"io":1,"arithmetic":1,"#,
+            r#""stack":2,"labels":1,"max_blow":5,"bit_len":81}"#
+        )
+    );
+    let empty = Stats::new(&parse_program("").unwrap());
+    assert_eq!(empty.max_blow, None);
+    assert!(empty.to_json().contains(r#""max_blow":null"#));
+}